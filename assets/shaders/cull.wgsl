@@ -0,0 +1,59 @@
+// Frustum culling, one invocation per instance.
+// Visible instances are compacted into `visible`, `visible_count` ends up
+// as the instance count of the indirect draws.
+
+struct CullParams {
+    planes: array<vec4<f32>, 6>,
+    // object space bounding sphere, xyz = center, w = radius
+    sphere: vec4<f32>,
+    count: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: CullParams;
+// InstanceRaw as plain floats: model mat4, normal mat3, color vec4
+@group(0) @binding(1)
+var<storage, read> instances: array<f32>;
+@group(0) @binding(2)
+var<storage, read_write> visible: array<f32>;
+@group(0) @binding(3)
+var<storage, read_write> visible_count: atomic<u32>;
+
+const INSTANCE_FLOATS: u32 = 29u;
+
+fn column(base: u32) -> vec4<f32> {
+    return vec4<f32>(instances[base], instances[base + 1u], instances[base + 2u], instances[base + 3u]);
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.count) {
+        return;
+    }
+
+    let base = index * INSTANCE_FLOATS;
+    let x = column(base);
+    let y = column(base + 4u);
+    let z = column(base + 8u);
+    let w = column(base + 12u);
+
+    let local = params.sphere.xyz;
+    let center = (x * local.x + y * local.y + z * local.z + w).xyz;
+    // non uniform scale grows the sphere by the largest axis
+    let scale = max(length(x.xyz), max(length(y.xyz), length(z.xyz)));
+    let radius = params.sphere.w * scale;
+
+    for (var i = 0u; i < 6u; i += 1u) {
+        let plane = params.planes[i];
+        if (dot(plane.xyz, center) + plane.w < -radius) {
+            return;
+        }
+    }
+
+    let slot = atomicAdd(&visible_count, 1u);
+    let out = slot * INSTANCE_FLOATS;
+    for (var f = 0u; f < INSTANCE_FLOATS; f += 1u) {
+        visible[out + f] = instances[base + f];
+    }
+}
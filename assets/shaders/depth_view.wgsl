@@ -0,0 +1,56 @@
+// Depth buffer -> linear grayscale, see src/depth_view.rs
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+};
+
+// full screen triangle, no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+struct DepthParams {
+    znear: f32,
+    zfar: f32,
+    reversed: u32,
+    _padding: u32,
+};
+
+@group(0) @binding(0)
+var t_depth: texture_depth_2d;
+// NOTE: same slot, used instead of t_depth when the scene renders with msaa
+@group(0) @binding(0)
+var t_depth_ms: texture_depth_multisampled_2d;
+@group(0) @binding(1)
+var<uniform> params: DepthParams;
+
+fn shade(raw: f32) -> vec4<f32> {
+    var depth = raw;
+    if params.reversed != 0u {
+        depth = 1.0 - depth;
+    }
+
+    // undo the OpenGL style perspective remapped to 0..1
+    let ndc = depth * 2.0 - 1.0;
+    let n = params.znear;
+    let f = params.zfar;
+    let linear = 2.0 * n * f / (f + n - ndc * (f - n));
+
+    let gray = clamp((linear - n) / (f - n), 0.0, 1.0);
+    return vec4<f32>(vec3<f32>(gray), 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(textureLoad(t_depth, vec2<i32>(in.position.xy), 0));
+}
+
+// first sample only, good enough for a preview
+@fragment
+fn fs_main_ms(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(textureLoad(t_depth_ms, vec2<i32>(in.position.xy), 0));
+}
@@ -0,0 +1,68 @@
+// ID buffer picking: every pixel stores which model / mesh / instance covers it
+// and the world position of the surface.
+
+struct PickCamera {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: PickCamera;
+
+// one entry per draw, selected with a dynamic offset
+struct PickIds {
+    model: u32,
+    mesh: u32,
+};
+@group(1) @binding(0)
+var<uniform> ids: PickIds;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) @interpolate(flat) instance: u32,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    out.instance = instance_index;
+    return out;
+}
+
+struct FragmentOutput {
+    // model + 1 (0 = nothing), mesh, instance
+    @location(0) id: vec4<u32>,
+    @location(1) position: vec4<f32>,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.id = vec4<u32>(ids.model + 1u, ids.mesh, in.instance, 0u);
+    out.position = vec4<f32>(in.world_position, 1.0);
+    return out;
+}
@@ -0,0 +1,111 @@
+// Fullscreen post effects, one fragment entry point per effect, see src/post.rs
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// full screen triangle, no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // texture space has y pointing down
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+struct PostParams {
+    a: vec4<f32>,
+    b: vec4<f32>,
+    texel_size: vec2<f32>,
+    _padding: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+@group(0) @binding(2)
+var<uniform> params: PostParams;
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn sample(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(t_input, s_input, uv, 0.0).rgb;
+}
+
+// a = threshold, intensity, radius in texels
+@fragment
+fn fs_bloom(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample(in.uv);
+    var glow = vec3<f32>(0.0);
+    var weight = 0.0;
+    // NOTE: single pass approximation, two rings of bright samples
+    for (var ring = 1; ring <= 2; ring++) {
+        for (var i = 0; i < 8; i++) {
+            let angle = f32(i) * 0.785398 + f32(ring) * 0.392699;
+            let offset = vec2<f32>(cos(angle), sin(angle)) * params.a.z * f32(ring) * 0.5;
+            let tap = sample(in.uv + offset * params.texel_size);
+            let w = 1.0 / f32(ring);
+            glow += max(tap - vec3<f32>(params.a.x), vec3<f32>(0.0)) * w;
+            weight += w;
+        }
+    }
+    return vec4<f32>(color + glow / weight * params.a.y, 1.0);
+}
+
+// a = span max, reduce mul, reduce min
+@fragment
+fn fs_fxaa(in: VertexOutput) -> @location(0) vec4<f32> {
+    let t = params.texel_size;
+    let nw = luma(sample(in.uv + vec2<f32>(-1.0, -1.0) * t));
+    let ne = luma(sample(in.uv + vec2<f32>(1.0, -1.0) * t));
+    let sw = luma(sample(in.uv + vec2<f32>(-1.0, 1.0) * t));
+    let se = luma(sample(in.uv + vec2<f32>(1.0, 1.0) * t));
+    let m = luma(sample(in.uv));
+
+    let luma_min = min(m, min(min(nw, ne), min(sw, se)));
+    let luma_max = max(m, max(max(nw, ne), max(sw, se)));
+
+    var dir = vec2<f32>(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+    let reduce = max((nw + ne + sw + se) * 0.25 * params.a.y, params.a.z);
+    let scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, vec2<f32>(-params.a.x), vec2<f32>(params.a.x)) * t;
+
+    let a = 0.5 * (sample(in.uv + dir * (1.0 / 3.0 - 0.5)) + sample(in.uv + dir * (2.0 / 3.0 - 0.5)));
+    let b = a * 0.5 + 0.25 * (sample(in.uv - dir * 0.5) + sample(in.uv + dir * 0.5));
+    let luma_b = luma(b);
+    if luma_b < luma_min || luma_b > luma_max {
+        return vec4<f32>(a, 1.0);
+    }
+    return vec4<f32>(b, 1.0);
+}
+
+// a = strength, radius, softness
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample(in.uv);
+    let distance = length(in.uv - vec2<f32>(0.5)) * 1.41421;
+    let falloff = smoothstep(params.a.y, params.a.y - params.a.z, distance);
+    return vec4<f32>(color * mix(1.0 - params.a.x, 1.0, falloff), 1.0);
+}
+
+// a = contrast, saturation, brightness, b = tint
+@fragment
+fn fs_color_grading(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = sample(in.uv) * params.b.rgb;
+    color = mix(vec3<f32>(luma(color)), color, params.a.y);
+    color = (color - vec3<f32>(0.5)) * params.a.x + vec3<f32>(0.5) + vec3<f32>(params.a.z);
+    return vec4<f32>(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+}
+
+// a = amount
+@fragment
+fn fs_grayscale(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample(in.uv);
+    return vec4<f32>(mix(color, vec3<f32>(luma(color)), params.a.x), 1.0);
+}
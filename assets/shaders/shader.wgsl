@@ -5,15 +5,30 @@ struct InstanceInput {
     @location(6) model_matrix_1: vec4<f32>,
     @location(7) model_matrix_2: vec4<f32>,
     @location(8) model_matrix_3: vec4<f32>,
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+    @location(12) color: vec4<f32>,
 };
  
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_tangent: vec3<f32>,
+    @location(4) world_bitangent: vec3<f32>,
+    @location(5) color: vec4<f32>,
 }
 
 struct CameraUniform {
     view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    view_position: vec4<f32>,
 };
 @group(1) @binding(0) // 1.
 var<uniform> camera: CameraUniform;
@@ -21,6 +36,9 @@ var<uniform> camera: CameraUniform;
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
 }
 
 @vertex
@@ -35,23 +53,213 @@ fn vs_main(
         instance.model_matrix_2,
         instance.model_matrix_3,
     );
-    
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+    // tangents follow the surface, they use the plain model matrix
+    let tangent_matrix = mat3x3<f32>(
+        model_matrix[0].xyz,
+        model_matrix[1].xyz,
+        model_matrix[2].xyz,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
-    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0); // 2.
+    out.world_position = world_position.xyz;
+    out.world_normal = normal_matrix * model.normal;
+    out.world_tangent = tangent_matrix * model.tangent;
+    out.world_bitangent = tangent_matrix * model.bitangent;
+    out.color = instance.color;
+    out.clip_position = camera.view_proj * world_position; // 2.
     return out;
 }
 
 // Fragment shader
 
+// every diffuse texture is an array, material.layer picks the layer
 @group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
+var t_diffuse: texture_2d_array<f32>;
 
 @group(0)@binding(1)
 var s_diffuse: sampler;
 
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var s_normal: sampler;
+
+@group(0) @binding(4)
+var t_specular: texture_2d<f32>;
+@group(0) @binding(5)
+var s_specular: sampler;
+
+@group(0) @binding(6)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(7)
+var s_emissive: sampler;
+
+// Ka Kd Ks Ns Ke d
+struct Material {
+    ambient: vec3<f32>,
+    diffuse: vec3<f32>,
+    specular: vec3<f32>,
+    shininess: f32,
+    emissive: vec3<f32>,
+    opacity: f32,
+    layer: u32,
+};
+@group(0) @binding(8)
+var<uniform> material: Material;
+
+// Lights, see src/light.rs
+const MAX_LIGHTS: u32 = 16u;
+const LIGHT_POINT: u32 = 0u;
+const LIGHT_DIRECTIONAL: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+
+struct Light {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    inner_cos: f32,
+    outer_cos: f32,
+};
+
+struct Lights {
+    ambient: vec3<f32>,
+    count: u32,
+    lights: array<Light, 16>,
+};
+@group(2) @binding(0)
+var<uniform> lights: Lights;
+
+// Shadows, see src/shadow.rs
+const MAX_SHADOWS: u32 = 4u;
+
+struct Shadows {
+    view_proj: array<mat4x4<f32>, 4>,
+    count: u32,
+    pcf_radius: u32,
+    texel_size: f32,
+};
+@group(3) @binding(0)
+var<uniform> shadows: Shadows;
+@group(3) @binding(1)
+var t_shadow: texture_depth_2d_array;
+@group(3) @binding(2)
+var s_shadow: sampler_comparison;
+
+// 1.0 = lit, 0.0 = in shadow, lights past shadows.count are never shadowed
+fn fetch_shadow(light_id: u32, world_position: vec3<f32>) -> f32 {
+    if light_id >= min(shadows.count, MAX_SHADOWS) {
+        return 1.0;
+    }
+    let homogeneous_coords = shadows.view_proj[light_id] * vec4<f32>(world_position, 1.0);
+    if homogeneous_coords.w <= 0.0 {
+        return 1.0;
+    }
+    // compensate for the Y-flip difference between the NDC and texture coordinates
+    let flip_correction = vec2<f32>(0.5, -0.5);
+    let proj_correction = 1.0 / homogeneous_coords.w;
+    let light_local = homogeneous_coords.xy * flip_correction * proj_correction + vec2<f32>(0.5, 0.5);
+    let depth = homogeneous_coords.z * proj_correction;
+    if depth > 1.0 {
+        return 1.0;
+    }
+
+    // pcf, every lookup is already a hardware 2x2 comparison
+    let radius = i32(shadows.pcf_radius);
+    var lit = 0.0;
+    var samples = 0.0;
+    for (var y = -radius; y <= radius; y += 1) {
+        for (var x = -radius; x <= radius; x += 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadows.texel_size;
+            lit += textureSampleCompareLevel(t_shadow, s_shadow, light_local + offset, i32(light_id), depth);
+            samples += 1.0;
+        }
+    }
+    return lit / samples;
+}
+
+// tangent space normal map -> world space normal
+fn world_normal(in: VertexOutput) -> vec3<f32> {
+    let tangent_normal = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+    let tbn = mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    );
+    return normalize(tbn * tangent_normal);
+}
+
+// Blinn-Phong, diffuse + specular of a single light
+fn blinn_phong(
+    light: Light,
+    world_position: vec3<f32>,
+    normal: vec3<f32>,
+    view_dir: vec3<f32>,
+    diffuse_color: vec3<f32>,
+    specular_color: vec3<f32>,
+) -> vec3<f32> {
+    var light_dir = normalize(-light.direction);
+    var attenuation = 1.0;
+
+    if light.kind != LIGHT_DIRECTIONAL {
+        let to_light = light.position - world_position;
+        let distance = length(to_light);
+        light_dir = to_light / distance;
+
+        // inverse square, windowed to reach zero at the range
+        let falloff = clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+        attenuation = falloff * falloff / (distance * distance + 1.0);
+
+        if light.kind == LIGHT_SPOT {
+            let theta = dot(-light_dir, normalize(light.direction));
+            attenuation *= smoothstep(light.outer_cos, light.inner_cos, theta);
+        }
+    }
+
+    let diffuse_strength = max(dot(normal, light_dir), 0.0);
+
+    let half_dir = normalize(view_dir + light_dir);
+    var specular_strength = pow(max(dot(normal, half_dir), 0.0), material.shininess);
+    if diffuse_strength <= 0.0 {
+        specular_strength = 0.0;
+    }
+
+    let radiance = light.color * light.intensity * attenuation;
+    return (diffuse_color * diffuse_strength + specular_color * specular_strength) * radiance;
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
-}
+    let object_color = textureSample(t_diffuse, s_diffuse, in.tex_coords, i32(material.layer)) * in.color;
+    let specular_map = textureSample(t_specular, s_specular, in.tex_coords).rgb;
+    let emissive = textureSample(t_emissive, s_emissive, in.tex_coords).rgb * material.emissive;
 
+    let normal = world_normal(in);
+    let view_dir = normalize(camera.view_position.xyz - in.world_position);
+
+    let diffuse_color = material.diffuse * object_color.rgb;
+    let specular_color = material.specular * specular_map;
+
+    var color = lights.ambient * material.ambient * object_color.rgb;
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i += 1u) {
+        color += fetch_shadow(i, in.world_position) * blinn_phong(
+            lights.lights[i],
+            in.world_position,
+            normal,
+            view_dir,
+            diffuse_color,
+            specular_color,
+        );
+    }
+
+    return vec4<f32>(color + emissive, object_color.a * material.opacity);
+}
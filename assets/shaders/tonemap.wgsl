@@ -0,0 +1,63 @@
+// HDR scene -> display range, see src/hdr.rs
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+};
+
+// full screen triangle, no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+struct ToneMapParams {
+    exposure: f32,
+    // 0 clamp, 1 reinhard, 2 aces
+    curve: u32,
+    // 1 when the output is not an srgb format and needs manual encoding
+    encode_srgb: u32,
+    _padding: u32,
+};
+
+@group(0) @binding(0)
+var t_hdr: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> params: ToneMapParams;
+
+// Narkowicz fit of the ACES filmic curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    return clamp(x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn srgb(x: vec3<f32>) -> vec3<f32> {
+    let low = x * 12.92;
+    let high = 1.055 * pow(x, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, x <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let hdr = textureLoad(t_hdr, vec2<i32>(in.position.xy), 0);
+    let color = max(hdr.rgb * params.exposure, vec3<f32>(0.0));
+
+    var mapped: vec3<f32>;
+    switch params.curve {
+        case 1u: {
+            mapped = color / (color + vec3<f32>(1.0));
+        }
+        case 2u: {
+            mapped = aces(color);
+        }
+        default: {
+            mapped = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+    }
+
+    if params.encode_srgb != 0u {
+        mapped = srgb(mapped);
+    }
+    return vec4<f32>(mapped, 1.0);
+}
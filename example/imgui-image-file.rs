@@ -91,9 +91,11 @@ pub async fn run() {
             _ => {}
         }
 
-        state
-            .platform
-            .handle_event(state.imgui_context.io_mut(), &state.window, &event);
+        state.platform.handle_event(
+            state.imgui_context.io_mut(),
+            state.window.as_ref().unwrap(),
+            &event,
+        );
     });
 }
 
@@ -11,6 +11,7 @@ use winit::{
     window::Window,
 };
 
+use wgpu_tutorial_rs::hot_reload::ShaderWatcher;
 use wgpu_tutorial_rs::texture::Texture as ImguiTexture;
 use wgpu_tutorial_rs::{gpu::Gpu, imgui_layer::Layer};
 use wgpu_tutorial_rs::{share::create_cube_texels, texture::Context};
@@ -19,6 +20,8 @@ use wgpu_tutorial_rs::{share::create_empty_texels, swapchain::Swapchain};
 struct State {
     swapchain1 : Swapchain,
     swapchain2 : Swapchain,
+    // NOTE: debug builds recompile cube.wgsl when it is saved
+    shader_watcher : Option<ShaderWatcher>,
 }
 
 impl State {
@@ -42,6 +45,7 @@ impl State {
         State {
             swapchain1,
             swapchain2,
+            shader_watcher : ShaderWatcher::development(),
         }
     }
 
@@ -93,6 +97,18 @@ impl State {
         rpass.draw_indexed(0..self.swapchain2.index_count as u32, 0, 0..1);
     }
 
+    fn reload_shaders(&mut self, device : &wgpu::Device) {
+
+        if let Some(watcher) = &mut self.shader_watcher {
+
+            let changed = watcher.poll();
+
+            self.swapchain1.reload_shaders(device, watcher, &changed);
+
+            self.swapchain2.reload_shaders(device, watcher, &changed);
+        }
+    }
+
     fn render(&mut self, view : &wgpu::TextureView, device : &wgpu::Device, queue : &wgpu::Queue) {
 
         let mut encoder =
@@ -348,6 +364,13 @@ async fn run() {
 
                 let ui = imgui.frame();
 
+                state.reload_shaders(&gpu.device);
+
+                if let Some(watcher) = &state.shader_watcher {
+
+                    watcher.ui(ui);
+                }
+
                 // Render example normally at background
                 state.swapchain1.update(ui.io().delta_time);
 
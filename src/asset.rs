@@ -0,0 +1,969 @@
+// NOTE: handle based asset cache, one gpu copy per path
+// the server only keeps weak references, the asset is freed with its last handle
+
+use crate::model::{MaterialDefaults, Model};
+use crate::resource::{self, DecodedObj, ObjMaterial};
+use crate::texture::{MipmapGenerator, Texture, TextureUpload};
+use crate::worker::WorkerPool;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, OnceLock, RwLock, Weak};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+
+pub enum LoadState {
+    Pending,
+    Loaded,
+    Failed(String),
+}
+
+enum Value<T> {
+    Pending,
+    Loaded(Arc<T>),
+    Failed(String),
+}
+
+struct Slot<T> {
+    path : String,
+    // replaced when the load finishes, or again when a model gets its real textures
+    value : RwLock<Value<T>>,
+    // progress, in load steps (decode, upload)
+    done : AtomicU32,
+    total : u32,
+}
+
+pub struct Handle<T> {
+    slot : Arc<Slot<T>>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+
+        Self {
+            slot : self.slot.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+        write!(f, "Handle({:?}, {:?})", self.slot.path, self.state())
+    }
+}
+
+impl<T> Handle<T> {
+    fn pending(path : &str, total : u32) -> Self {
+
+        Self {
+            slot : Arc::new(Slot {
+                path : path.to_string(),
+                value : RwLock::new(Value::Pending),
+                done : AtomicU32::new(0),
+                total,
+            }),
+        }
+    }
+
+    // NOTE: for assets made in code, not cached by any server
+    pub fn loaded(path : &str, value : T) -> Self {
+
+        let handle = Self::pending(path, 1);
+
+        handle.finish(Ok(value));
+
+        handle
+    }
+
+    fn finish(&self, result : anyhow::Result<T>) {
+
+        let value = match result {
+            Ok(value) => Value::Loaded(Arc::new(value)),
+            Err(e) => {
+
+                log::warn!("failed to load {}: {:#}", self.slot.path, e);
+
+                Value::Failed(format!("{:#}", e))
+            }
+        };
+
+        *self.slot.value.write().unwrap() = value;
+
+        self.slot.done.store(self.slot.total, Ordering::Release);
+    }
+
+    fn advance(&self) { self.slot.done.fetch_add(1, Ordering::AcqRel); }
+
+    pub fn path(&self) -> &str { &self.slot.path }
+
+    pub fn state(&self) -> LoadState {
+
+        match &*self.slot.value.read().unwrap() {
+            Value::Pending => LoadState::Pending,
+            Value::Loaded(_) => LoadState::Loaded,
+            Value::Failed(e) => LoadState::Failed(e.clone()),
+        }
+    }
+
+    pub fn is_pending(&self) -> bool { matches!(*self.slot.value.read().unwrap(), Value::Pending) }
+
+    // None while pending or after a failed load
+    // NOTE: a model may be swapped once its real textures arrive, don't keep this across frames
+    pub fn get(&self) -> Option<Arc<T>> {
+
+        match &*self.slot.value.read().unwrap() {
+            Value::Loaded(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    // the shared asset, e.g. a texture referenced by several materials
+    pub fn loaded_arc(&self) -> anyhow::Result<Arc<T>> {
+
+        match &*self.slot.value.read().unwrap() {
+            Value::Loaded(value) => Ok(value.clone()),
+            Value::Failed(e) => Err(anyhow::anyhow!("{}: {}", self.slot.path, e)),
+            Value::Pending => Err(anyhow::anyhow!("{} is still loading", self.slot.path)),
+        }
+    }
+
+    // 0..1, for loading bars
+    pub fn progress(&self) -> f32 {
+
+        self.slot.done.load(Ordering::Acquire).min(self.slot.total) as f32 / self.slot.total as f32
+    }
+
+    // both handles point at the same asset
+    pub fn ptr_eq(&self, other : &Self) -> bool { Arc::ptr_eq(&self.slot, &other.slot) }
+}
+
+// one map per asset type, entries die with their last handle
+struct Cache<K, T> {
+    slots : Mutex<HashMap<K, Weak<Slot<T>>>>,
+}
+
+impl<K : Eq + Hash, T> Cache<K, T> {
+    fn new() -> Self {
+
+        Self {
+            slots : Mutex::new(HashMap::new()),
+        }
+    }
+
+    // the live handle for `key`, or a new pending one the caller has to finish
+    fn get_or_insert(&self, key : K, path : &str, steps : u32) -> (Handle<T>, bool) {
+
+        let mut slots = self.slots.lock().unwrap();
+
+        if let Some(slot) = slots.get(&key).and_then(Weak::upgrade) {
+
+            return (Handle { slot }, false);
+        }
+
+        slots.retain(|_, slot| slot.strong_count() > 0);
+
+        let handle = Handle::pending(path, steps);
+
+        slots.insert(key, Arc::downgrade(&handle.slot));
+
+        (handle, true)
+    }
+
+    fn live(&self) -> usize {
+
+        let slots = self.slots.lock().unwrap();
+
+        slots
+            .values()
+            .filter(|slot| slot.strong_count() > 0)
+            .count()
+    }
+}
+
+// one row of the loading overlay
+#[derive(Debug, Clone, PartialEq)]
+
+pub struct AssetProgress {
+    pub kind : &'static str,
+    pub path : String,
+    pub fraction : f32,
+    pub state : LoadState,
+}
+
+// type erased view on a background load, gone when its handles are
+struct Tracked {
+    kind : &'static str,
+    report : Box<dyn Fn() -> Option<(String, f32, LoadState)> + Send>,
+}
+
+impl Tracked {
+    fn new<T : Send + Sync + 'static>(kind : &'static str, handle : &Handle<T>) -> Self {
+
+        let slot = Arc::downgrade(&handle.slot);
+
+        Self {
+            kind,
+            report : Box::new(move || {
+
+                let handle = Handle {
+                    slot : slot.upgrade()?,
+                };
+
+                Some((handle.path().to_string(), handle.progress(), handle.state()))
+            }),
+        }
+    }
+}
+
+// what the render thread hands to uploads queued by the workers
+pub struct UploadContext<'a> {
+    pub device : &'a wgpu::Device,
+    pub queue : &'a wgpu::Queue,
+    // material layout, see Material::bind_group_layout
+    pub layout : &'a wgpu::BindGroupLayout,
+    server : &'a AssetServer,
+    // NOTE: mip blits of every upload in one poll, created on first use and submitted once
+    encoder : RefCell<Option<wgpu::CommandEncoder>>,
+}
+
+impl UploadContext<'_> {
+    pub fn texture_upload<T>(&self, upload : impl FnOnce(&mut TextureUpload) -> T) -> T {
+
+        let mut encoder = self.encoder.borrow_mut();
+
+        upload(&mut TextureUpload {
+            device : self.device,
+            queue : self.queue,
+            encoder : encoder.get_or_insert_with(|| AssetServer::mipmap_encoder(self.device)),
+            mipmaps : self.server.mipmaps(self.device),
+        })
+    }
+}
+
+type Upload = Box<dyn for<'a> FnOnce(&UploadContext<'a>) + Send>;
+
+// a model drawn with placeholders until its textures are done
+struct WaitingModel {
+    model : Weak<Slot<Model>>,
+    obj : DecodedObj,
+    textures : Vec<[Option<Handle<Texture>>; 4]>,
+}
+
+pub struct AssetServer {
+    // normal maps are uploaded linear, so they get their own entry
+    textures : Cache<(String, bool), Texture>,
+    models : Cache<String, Model>,
+    shaders : Cache<String, wgpu::ShaderModule>,
+    // NOTE: background loads, started on first use
+    workers : OnceLock<WorkerPool>,
+    upload_sender : mpsc::Sender<Upload>,
+    upload_receiver : Mutex<mpsc::Receiver<Upload>>,
+    // decodes queued or running, plus uploads not yet run
+    in_flight : AtomicUsize,
+    placeholders : OnceLock<MaterialDefaults>,
+    // blit pipelines shared by every texture loaded through here
+    mipmaps : OnceLock<MipmapGenerator>,
+    waiting : Mutex<Vec<WaitingModel>>,
+    tracked : Mutex<Vec<Tracked>>,
+}
+
+impl Default for AssetServer {
+    fn default() -> Self { Self::new() }
+}
+
+impl AssetServer {
+    pub fn new() -> Self {
+
+        let (upload_sender, upload_receiver) = mpsc::channel();
+
+        Self {
+            textures : Cache::new(),
+            models : Cache::new(),
+            shaders : Cache::new(),
+            workers : OnceLock::new(),
+            upload_sender,
+            upload_receiver : Mutex::new(upload_receiver),
+            in_flight : AtomicUsize::new(0),
+            placeholders : OnceLock::new(),
+            mipmaps : OnceLock::new(),
+            waiting : Mutex::new(Vec::new()),
+            tracked : Mutex::new(Vec::new()),
+        }
+    }
+
+    // NOTE: paths go through resource::vfs, the first caller does the load
+    pub async fn load_texture(
+        &self,
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        path : &str,
+        is_normal_map : bool,
+    ) -> Handle<Texture> {
+
+        let (handle, is_new) =
+            self.textures
+                .get_or_insert((path.to_string(), is_normal_map), path, 1);
+
+        // NOTE: the caller needs the texture now, a slot a background load left pending is
+        // finished here and its queued upload skipped, see spawn_load
+        if is_new || handle.is_pending() {
+
+            let decoded = match resource::load_binary(path).await {
+                Ok(data) => resource::decode_texture(path, &data, is_normal_map),
+                Err(e) => Err(e),
+            };
+
+            // poll may have run the background upload in the meantime
+            if handle.is_pending() {
+
+                handle.finish(decoded.and_then(|decoded| {
+
+                    self.upload_now(device, queue, |upload| decoded.upload(upload))
+                }));
+            }
+        }
+
+        handle
+    }
+
+    // obj textures are shared with every other model loaded through this server
+    pub async fn load_model(
+        &self,
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        layout : &wgpu::BindGroupLayout,
+        path : &str,
+    ) -> Handle<Model> {
+
+        let (handle, is_new) = self.models.get_or_insert(path.to_string(), path, 1);
+
+        if is_new {
+
+            handle.finish(if is_gltf(path) {
+
+                let mut encoder = Self::mipmap_encoder(device);
+
+                let model = resource::load_gltf_with(
+                    path,
+                    layout,
+                    &mut TextureUpload {
+                        device,
+                        queue,
+                        encoder : &mut encoder,
+                        mipmaps : self.mipmaps(device),
+                    },
+                )
+                .await;
+
+                queue.submit(Some(encoder.finish()));
+
+                model
+            } else {
+
+                resource::load_model_with(path, device, queue, layout, self).await
+            });
+        }
+
+        handle
+    }
+
+    // NOTE: wgsl errors end up in the handle instead of the uncaptured error handler
+    pub async fn load_shader(
+        &self,
+        device : &wgpu::Device,
+        path : &str,
+    ) -> Handle<wgpu::ShaderModule> {
+
+        let (handle, is_new) = self.shaders.get_or_insert(path.to_string(), path, 1);
+
+        if is_new {
+
+            handle.finish(resource::load_shader(device, path).await);
+        }
+
+        handle
+    }
+
+    fn mipmaps(&self, device : &wgpu::Device) -> &MipmapGenerator {
+
+        self.mipmaps.get_or_init(|| MipmapGenerator::new(device))
+    }
+
+    fn mipmap_encoder(device : &wgpu::Device) -> wgpu::CommandEncoder {
+
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label : Some("Mipmap Encoder"),
+        })
+    }
+
+    // NOTE: foreground loads submit right away, the pipelines are still shared
+    fn upload_now<T>(
+        &self,
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        upload : impl FnOnce(&mut TextureUpload) -> T,
+    ) -> T {
+
+        let mut encoder = Self::mipmap_encoder(device);
+
+        let result = upload(&mut TextureUpload {
+            device,
+            queue,
+            encoder : &mut encoder,
+            mipmaps : self.mipmaps(device),
+        });
+
+        queue.submit(Some(encoder.finish()));
+
+        result
+    }
+
+    fn upload_context<'a>(
+        &'a self,
+        device : &'a wgpu::Device,
+        queue : &'a wgpu::Queue,
+        layout : &'a wgpu::BindGroupLayout,
+    ) -> UploadContext<'a> {
+
+        UploadContext {
+            device,
+            queue,
+            layout,
+            server : self,
+            encoder : RefCell::new(None),
+        }
+    }
+
+    fn submit(context : UploadContext) {
+
+        if let Some(encoder) = context.encoder.into_inner() {
+
+            context.queue.submit(Some(encoder.finish()));
+        }
+    }
+
+    fn workers(&self) -> &WorkerPool {
+
+        self.workers
+            .get_or_init(|| WorkerPool::with_default_threads("asset worker"))
+    }
+
+    // decode on a worker, then upload on the render thread in poll/wait
+    fn spawn_load<T, D>(
+        &self,
+        kind : &'static str,
+        handle : &Handle<T>,
+        decode : impl FnOnce() -> anyhow::Result<D> + Send + 'static,
+        upload : impl for<'a> FnOnce(D, &UploadContext<'a>) -> anyhow::Result<T> + Send + 'static,
+    ) where
+        T : Send + Sync + 'static,
+        D : Send + 'static,
+    {
+
+        self.tracked
+            .lock()
+            .unwrap()
+            .push(Tracked::new(kind, handle));
+
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+
+        let handle = handle.clone();
+
+        let sender = self.upload_sender.clone();
+
+        self.workers().spawn(move || {
+
+            // a panicking decoder fails the asset instead of leaving it pending forever
+            let decoded = std::panic::catch_unwind(std::panic::AssertUnwindSafe(decode))
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("decoder panicked")));
+
+            handle.advance();
+
+            let upload : Upload = Box::new(move |context| {
+
+                // a foreground load got there first, see load_texture
+                if handle.is_pending() {
+
+                    handle.finish(decoded.and_then(|decoded| upload(decoded, context)))
+                }
+            });
+
+            // NOTE: the server is gone, nothing left to upload to
+            let _ = sender.send(upload);
+        });
+    }
+
+    // pending until poll uploads it, see Handle::progress
+    pub fn load_texture_background(&self, path : &str, is_normal_map : bool) -> Handle<Texture> {
+
+        let (handle, is_new) =
+            self.textures
+                .get_or_insert((path.to_string(), is_normal_map), path, 2);
+
+        if is_new {
+
+            let file_name = path.to_string();
+
+            self.spawn_load(
+                "texture",
+                &handle,
+                move || {
+
+                    let data = pollster::block_on(resource::load_binary(&file_name))?;
+
+                    resource::decode_texture(&file_name, &data, is_normal_map)
+                },
+                |decoded, context| context.texture_upload(|upload| decoded.upload(upload)),
+            );
+        }
+
+        handle
+    }
+
+    // NOTE: obj models show up with placeholder textures, the real ones are swapped in
+    // once all of them are done, see poll. glTF is still decoded on the render thread
+    pub fn load_model_background(&self, path : &str) -> Handle<Model> {
+
+        let (handle, is_new) = self.models.get_or_insert(path.to_string(), path, 2);
+
+        if !is_new {
+
+            return handle;
+        }
+
+        let file_name = path.to_string();
+
+        if is_gltf(path) {
+
+            self.spawn_load(
+                "model",
+                &handle,
+                || Ok(()),
+                move |_, context| {
+
+                    context.texture_upload(|upload| {
+
+                        pollster::block_on(resource::load_gltf_with(
+                            &file_name,
+                            context.layout,
+                            upload,
+                        ))
+                    })
+                },
+            );
+
+            return handle;
+        }
+
+        let slot = Arc::downgrade(&handle.slot);
+
+        self.spawn_load(
+            "model",
+            &handle,
+            move || pollster::block_on(resource::decode_obj(&file_name)),
+            move |obj : DecodedObj, context| {
+
+                let server = context.server;
+
+                let textures = obj
+                    .materials
+                    .iter()
+                    .map(|m| {
+
+                        let mut maps : [Option<Handle<Texture>>; 4] = Default::default();
+
+                        for ((map, path), is_normal_map) in
+                            maps.iter_mut().zip(&m.maps).zip(ObjMaterial::IS_NORMAL_MAP)
+                        {
+
+                            *map = path
+                                .as_deref()
+                                .map(|path| server.load_texture_background(path, is_normal_map));
+                        }
+
+                        maps
+                    })
+                    .collect::<Vec<_>>();
+
+                let placeholders = server.placeholders(context.device, context.queue)?;
+
+                let model = obj.upload(
+                    context.device,
+                    context.layout,
+                    &loaded_maps(&textures),
+                    placeholders,
+                );
+
+                if textures.iter().flatten().flatten().any(Handle::is_pending) {
+
+                    server.waiting.lock().unwrap().push(WaitingModel {
+                        model : slot,
+                        obj,
+                        textures,
+                    });
+                }
+
+                Ok(model)
+            },
+        );
+
+        handle
+    }
+
+    fn placeholders(
+        &self,
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+    ) -> anyhow::Result<&MaterialDefaults> {
+
+        if let Some(placeholders) = self.placeholders.get() {
+
+            return Ok(placeholders);
+        }
+
+        let placeholders = MaterialDefaults::new(device, queue)?;
+
+        Ok(self.placeholders.get_or_init(|| placeholders))
+    }
+
+    // NOTE: call once per frame on the render thread, runs the finished uploads
+    // returns how many assets were uploaded
+    pub fn poll(
+        &self,
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        layout : &wgpu::BindGroupLayout,
+    ) -> usize {
+
+        let context = self.upload_context(device, queue, layout);
+
+        let mut uploaded = 0;
+
+        loop {
+
+            // the receiver is unlocked before the upload runs, uploads may queue more loads
+            let upload = self.upload_receiver.lock().unwrap().try_recv();
+
+            match upload {
+                Ok(upload) => {
+
+                    self.run_upload(upload, &context);
+
+                    uploaded += 1;
+                }
+                Err(_) => break,
+            }
+        }
+
+        self.swap_placeholders(&context);
+
+        Self::submit(context);
+
+        uploaded
+    }
+
+    // NOTE: blocks until every background load is done, e.g. before a headless render
+    pub fn wait(
+        &self,
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        layout : &wgpu::BindGroupLayout,
+    ) {
+
+        let context = self.upload_context(device, queue, layout);
+
+        while self.in_flight.load(Ordering::Acquire) > 0 {
+
+            let upload = self.upload_receiver.lock().unwrap().recv();
+
+            match upload {
+                Ok(upload) => self.run_upload(upload, &context),
+                Err(_) => break,
+            }
+        }
+
+        self.swap_placeholders(&context);
+
+        Self::submit(context);
+    }
+
+    fn run_upload(&self, upload : Upload, context : &UploadContext) {
+
+        upload(context);
+
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    fn swap_placeholders(&self, context : &UploadContext) {
+
+        let mut waiting = self.waiting.lock().unwrap();
+
+        waiting.retain(|w| {
+
+            let slot = match w.model.upgrade() {
+                Some(slot) => slot,
+                // nobody holds the model anymore
+                None => return false,
+            };
+
+            if w.textures
+                .iter()
+                .flatten()
+                .flatten()
+                .any(Handle::is_pending)
+            {
+
+                return true;
+            }
+
+            let model = Handle { slot };
+
+            let current = match model.get() {
+                Some(current) => current,
+                None => return false,
+            };
+
+            // failed textures keep their placeholder
+            let placeholders = self
+                .placeholders
+                .get()
+                .expect("created with the placeholder model");
+
+            let materials = w.obj.upload_materials(
+                context.device,
+                context.layout,
+                &loaded_maps(&w.textures),
+                placeholders,
+            );
+
+            model.finish(Ok(current.with_materials(materials)));
+
+            false
+        });
+    }
+
+    pub fn is_loading(&self) -> bool {
+
+        self.in_flight.load(Ordering::Acquire) > 0 || !self.waiting.lock().unwrap().is_empty()
+    }
+
+    // background loads that are still running, finished ones are reported once more
+    pub fn progress(&self) -> Vec<AssetProgress> {
+
+        let mut tracked = self.tracked.lock().unwrap();
+
+        let mut progress = Vec::new();
+
+        tracked.retain(|t| match (t.report)() {
+            Some((path, fraction, state)) => {
+
+                let pending = state == LoadState::Pending;
+
+                progress.push(AssetProgress {
+                    kind : t.kind,
+                    path,
+                    fraction,
+                    state,
+                });
+
+                pending
+            }
+            None => false,
+        });
+
+        progress
+    }
+
+    // assets still referenced by at least one handle
+    pub fn live_textures(&self) -> usize { self.textures.live() }
+
+    pub fn live_models(&self) -> usize { self.models.live() }
+
+    pub fn live_shaders(&self) -> usize { self.shaders.live() }
+}
+
+fn is_gltf(path : &str) -> bool { path.ends_with(".gltf") || path.ends_with(".glb") }
+
+// the textures that made it so far, None is drawn with a placeholder
+fn loaded_maps(textures : &[[Option<Handle<Texture>>; 4]]) -> Vec<[Option<Arc<Texture>>; 4]> {
+
+    textures
+        .iter()
+        .map(|maps| {
+
+            maps.each_ref()
+                .map(|map| map.as_ref().and_then(Handle::get))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+    use crate::model::Material;
+
+    #[test]
+
+    fn test_handle_states() {
+
+        let handle = Handle::<u32>::pending("a", 2);
+
+        assert_eq!(handle.state(), LoadState::Pending);
+
+        assert!(handle.get().is_none());
+
+        handle.advance();
+
+        assert_eq!(handle.progress(), 0.5);
+
+        handle.finish(Ok(7));
+
+        assert_eq!(handle.state(), LoadState::Loaded);
+
+        assert_eq!(handle.get().as_deref(), Some(&7));
+
+        assert_eq!(handle.progress(), 1.0);
+
+        let failed = Handle::<u32>::pending("b", 1);
+
+        failed.finish(Err(anyhow::anyhow!("missing")));
+
+        assert_eq!(failed.state(), LoadState::Failed("missing".to_string()));
+
+        assert!(failed.loaded_arc().is_err());
+    }
+
+    #[test]
+
+    fn test_cache_dedup_and_release() {
+
+        let cache = Cache::<String, u32>::new();
+
+        let (a, is_new) = cache.get_or_insert("a".to_string(), "a", 1);
+
+        assert!(is_new);
+
+        a.finish(Ok(1));
+
+        let (b, is_new) = cache.get_or_insert("a".to_string(), "a", 1);
+
+        assert!(!is_new);
+
+        assert!(a.ptr_eq(&b));
+
+        assert_eq!(cache.live(), 1);
+
+        // the asset goes away with its last handle, the next request loads again
+        drop(a);
+
+        drop(b);
+
+        assert_eq!(cache.live(), 0);
+
+        let (_, is_new) = cache.get_or_insert("a".to_string(), "a", 1);
+
+        assert!(is_new);
+    }
+
+    #[test]
+
+    fn test_background_progress() {
+
+        let server = AssetServer::new();
+
+        let handle = Handle::<u32>::pending("numbers", 2);
+
+        let (sender, receiver) = mpsc::channel();
+
+        server.spawn_load(
+            "number",
+            &handle,
+            move || {
+
+                receiver.recv()?;
+
+                Ok(21)
+            },
+            |decoded, _| Ok(decoded * 2),
+        );
+
+        assert!(server.is_loading());
+
+        assert_eq!(server.progress()[0].state, LoadState::Pending);
+
+        sender.send(()).unwrap();
+
+        // uploads need the gpu, so only the decode half runs here
+        let upload = server.upload_receiver.lock().unwrap().recv().unwrap();
+
+        assert_eq!(handle.progress(), 0.5);
+
+        drop(upload);
+
+        handle.finish(Ok(42));
+
+        // reported once more as loaded, then dropped
+        assert_eq!(server.progress()[0].state, LoadState::Loaded);
+
+        assert!(server.progress().is_empty());
+    }
+
+    // NOTE: the software adapter is enough, None without any
+    fn gpu() -> Option<(wgpu::Device, wgpu::Queue)> {
+
+        let instance = wgpu::Instance::default();
+
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+
+        pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                limits : wgpu::Limits::downlevel_defaults(),
+                ..Default::default()
+            },
+            None,
+        ))
+        .ok()
+    }
+
+    #[test]
+
+    fn test_foreground_after_background() {
+
+        let Some((device, queue)) = gpu() else {
+
+            eprintln!("skipped, no adapter");
+
+            return;
+        };
+
+        let server = AssetServer::new();
+
+        let background = server.load_texture_background("cube-diffuse.jpg", false);
+
+        // finished in place, without waiting for poll
+        let foreground =
+            pollster::block_on(server.load_texture(&device, &queue, "cube-diffuse.jpg", false));
+
+        assert!(foreground.ptr_eq(&background));
+
+        let texture = foreground.loaded_arc().unwrap();
+
+        assert_eq!(server.live_textures(), 1);
+
+        // the queued background upload is skipped, not uploaded a second time
+        server.wait(&device, &queue, &Material::bind_group_layout(&device));
+
+        assert!(Arc::ptr_eq(&foreground.get().unwrap(), &texture));
+
+        assert!(server
+            .load_texture_background("cube-diffuse.jpg", false)
+            .ptr_eq(&foreground));
+
+        assert_eq!(server.live_textures(), 1);
+    }
+}
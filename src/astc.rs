@@ -0,0 +1,920 @@
+// NOTE: astc ldr block decoder for the cpu fallback in texture_container, after the khronos
+// data format spec. hdr endpoints and hdr void extents come out as the error color, as they
+// do on any ldr decoder
+
+const ERROR_COLOR : [u8; 4] = [255, 0, 255, 255];
+
+// integer sequence encoding ranges, 2 to 256 levels: (3 for trits / 5 for quints / 0, bits)
+const RANGES : [(u32, u32); 21] = [
+    (0, 1),
+    (3, 0),
+    (0, 2),
+    (5, 0),
+    (3, 1),
+    (0, 3),
+    (5, 1),
+    (3, 2),
+    (0, 4),
+    (5, 2),
+    (3, 3),
+    (0, 5),
+    (5, 3),
+    (3, 4),
+    (0, 6),
+    (5, 4),
+    (3, 5),
+    (0, 7),
+    (5, 5),
+    (3, 6),
+    (0, 8),
+];
+
+// endpoints need at least 6 levels
+const MIN_COLOR_RANGE : usize = 4;
+
+fn read(value : u128, start : u32, len : u32) -> u32 {
+
+    (value.checked_shr(start).unwrap_or(0) & mask(len)) as u32
+}
+
+fn mask(len : u32) -> u128 { 1u128.checked_shl(len).map_or(u128::MAX, |bit| bit - 1) }
+
+fn ise_bits(count : u32, range : usize) -> u32 {
+
+    let (kind, bits) = RANGES[range];
+
+    bits * count
+        + match kind {
+            3 => (8 * count).div_ceil(5),
+            5 => (7 * count).div_ceil(3),
+            _ => 0,
+        }
+}
+
+fn trits(t : u32) -> [u32; 5] {
+
+    let (c, t4, t3) = if t >> 2 & 7 == 7 {
+
+        ((t >> 5 & 7) << 2 | t & 3, 2, 2)
+    } else if t >> 5 & 3 == 3 {
+
+        (t & 31, 2, t >> 7 & 1)
+    } else {
+
+        (t & 31, t >> 7 & 1, t >> 5 & 3)
+    };
+
+    let bit = |i : u32| c >> i & 1;
+
+    let (t2, t1, t0) = if c & 3 == 3 {
+
+        (2, bit(4), bit(3) << 1 | bit(2) & !bit(3) & 1)
+    } else if c >> 2 & 3 == 3 {
+
+        (2, 2, c & 3)
+    } else {
+
+        (bit(4), c >> 2 & 3, bit(1) << 1 | bit(0) & !bit(1) & 1)
+    };
+
+    [t0, t1, t2, t3, t4]
+}
+
+fn quints(q : u32) -> [u32; 3] {
+
+    let bit = |i : u32| q >> i & 1;
+
+    if q >> 1 & 3 == 3 && q >> 5 & 3 == 0 {
+
+        return [
+            4,
+            4,
+            bit(0) << 2 | (bit(4) & !bit(0) & 1) << 1 | bit(3) & !bit(0) & 1,
+        ];
+    }
+
+    let (q2, c) = if q >> 1 & 3 == 3 {
+
+        (4, (q >> 3 & 3) << 3 | (!q >> 5 & 3) << 1 | bit(0))
+    } else {
+
+        (q >> 5 & 3, q & 31)
+    };
+
+    if c & 7 == 5 {
+
+        [c >> 3 & 3, 4, q2]
+    } else {
+
+        [c & 7, c >> 3 & 3, q2]
+    }
+}
+
+// NOTE: the trit / quint bits are spread between the values of a group, bits past the end of
+// the sequence read as zero, which is how the last partial group is defined
+fn ise_decode(value : u128, count : u32, range : usize) -> Vec<u32> {
+
+    let (kind, bits) = RANGES[range];
+
+    let mut values = Vec::with_capacity(count as usize + 4);
+
+    let mut pos = 0;
+
+    let mut next = |len : u32| {
+
+        let v = read(value, pos, len);
+
+        pos += len;
+
+        v
+    };
+
+    while values.len() < count as usize {
+
+        match kind {
+            3 => {
+
+                let mut m = [0; 5];
+
+                let mut t = 0;
+
+                for (i, (shift, len)) in [(0, 2), (2, 2), (4, 1), (5, 2), (7, 1)]
+                    .into_iter()
+                    .enumerate()
+                {
+
+                    m[i] = next(bits);
+
+                    t |= next(len) << shift;
+                }
+
+                values.extend(trits(t).iter().zip(m).map(|(t, m)| t << bits | m));
+            }
+            5 => {
+
+                let mut m = [0; 3];
+
+                let mut q = 0;
+
+                for (i, (shift, len)) in [(0, 3), (3, 2), (5, 2)].into_iter().enumerate() {
+
+                    m[i] = next(bits);
+
+                    q |= next(len) << shift;
+                }
+
+                values.extend(quints(q).iter().zip(m).map(|(q, m)| q << bits | m));
+            }
+            _ => values.push(next(bits)),
+        }
+    }
+
+    values.truncate(count as usize);
+
+    values
+}
+
+fn replicate(v : u32, bits : u32, to : u32) -> u32 {
+
+    let (mut out, mut filled) = (0, 0);
+
+    while filled < to {
+
+        out = out << bits | v;
+
+        filled += bits;
+    }
+
+    out >> (filled - to)
+}
+
+// spec bit patterns, most significant first, letters are bits of the value below the trit / quint
+fn pattern(bits : &str, m : u32) -> u32 {
+
+    bits.bytes().fold(0, |acc, c| {
+
+        acc << 1
+            | match c {
+                b'0' => 0,
+                c => m >> (c - b'a') & 1,
+            }
+    })
+}
+
+fn unquantize_color(v : u32, range : usize) -> i32 {
+
+    let (kind, bits) = RANGES[range];
+
+    if kind == 0 {
+
+        return replicate(v, bits, 8) as i32;
+    }
+
+    let (d, m) = (v >> bits, v & ((1 << bits) - 1));
+
+    let (b, c) = match (kind, bits) {
+        (3, 1) => ("000000000", 204),
+        (5, 1) => ("000000000", 113),
+        (3, 2) => ("b000b0bb0", 93),
+        (5, 2) => ("b0000bb00", 54),
+        (3, 3) => ("cb000cbcb", 44),
+        (5, 3) => ("cb0000cbc", 26),
+        (3, 4) => ("dcb000dcb", 22),
+        (5, 4) => ("dcb0000dc", 13),
+        (3, 5) => ("edcb000ed", 11),
+        (5, 5) => ("edcb0000e", 6),
+        _ => ("fedcb000f", 5),
+    };
+
+    let a = if m & 1 == 1 { 0x1ff } else { 0 };
+
+    let t = (d * c + pattern(b, m)) ^ a;
+
+    (a & 0x80 | t >> 2) as i32
+}
+
+// 0 to 64
+fn unquantize_weight(v : u32, range : usize) -> u32 {
+
+    let (kind, bits) = RANGES[range];
+
+    let w = match (kind, bits) {
+        (0, _) => replicate(v, bits, 6),
+        (3, 0) => [0, 32, 63][v as usize],
+        (5, 0) => [0, 16, 32, 47, 63][v as usize],
+        _ => {
+
+            let (d, m) = (v >> bits, v & ((1 << bits) - 1));
+
+            let (b, c) = match (kind, bits) {
+                (3, 1) => ("0000000", 50),
+                (5, 1) => ("0000000", 28),
+                (3, 2) => ("b000b0b", 23),
+                (5, 2) => ("b0000b0", 13),
+                _ => ("cb000cb", 11),
+            };
+
+            let a = if m & 1 == 1 { 0x7f } else { 0 };
+
+            let t = (d * c + pattern(b, m)) ^ a;
+
+            a & 0x20 | t >> 2
+        }
+    };
+
+    if w > 32 {
+
+        w + 1
+    } else {
+
+        w
+    }
+}
+
+struct BlockMode {
+    width : u32,
+    height : u32,
+    dual_plane : bool,
+    range : usize,
+}
+
+// NOTE: the 11 bit block mode packs the weight grid size, its range and the dual plane flag
+fn block_mode(mode : u32) -> Option<BlockMode> {
+
+    let a = mode >> 5 & 3;
+
+    let mut dual_plane = mode >> 10 & 1 == 1;
+
+    let mut high = mode >> 9 & 1;
+
+    let (base, width, height) = if mode & 3 != 0 {
+
+        let b = mode >> 7 & 3;
+
+        let (width, height) = match mode >> 2 & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if mode & 0x100 != 0 => ((b & 1) + 2, a + 2),
+            _ => (a + 2, (b & 1) + 6),
+        };
+
+        ((mode & 3) << 1 | mode >> 4 & 1, width, height)
+    } else {
+
+        if mode >> 2 & 3 == 0 {
+
+            return None;
+        }
+
+        let b = mode >> 9 & 3;
+
+        let (width, height) = match mode >> 7 & 3 {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => {
+
+                dual_plane = false;
+
+                high = 0;
+
+                (a + 6, b + 6)
+            }
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        };
+
+        ((mode >> 2 & 3) << 1 | mode >> 4 & 1, width, height)
+    };
+
+    Some(BlockMode {
+        width,
+        height,
+        dual_plane,
+        range : (base - 2 + 6 * high) as usize,
+    })
+}
+
+fn hash52(mut p : u32) -> u32 {
+
+    p ^= p >> 15;
+
+    p = p.wrapping_sub(p << 17);
+
+    p = p.wrapping_add(p << 7);
+
+    p = p.wrapping_add(p << 4);
+
+    p ^= p >> 5;
+
+    p = p.wrapping_add(p << 16);
+
+    p ^= p >> 7;
+
+    p ^= p >> 3;
+
+    p ^= p << 6;
+
+    p ^= p >> 17;
+
+    p
+}
+
+// NOTE: partitions are not stored as tables, the 10 bit seed picks one of the hash patterns
+fn select_partition(seed : u32, x : u32, y : u32, partitions : u32, small_block : bool) -> usize {
+
+    let (x, y) = if small_block {
+
+        (x << 1, y << 1)
+    } else {
+
+        (x, y)
+    };
+
+    let seed = seed + (partitions - 1) * 1024;
+
+    let rnum = hash52(seed);
+
+    let mut seeds = [0u32; 8];
+
+    for (i, s) in seeds.iter_mut().enumerate() {
+
+        let nibble = rnum >> (i * 4) & 15;
+
+        *s = nibble * nibble;
+    }
+
+    let (sh1, sh2) = if seed & 1 == 1 {
+
+        (
+            if seed & 2 != 0 { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        )
+    } else {
+
+        (
+            if partitions == 3 { 6 } else { 5 },
+            if seed & 2 != 0 { 4 } else { 5 },
+        )
+    };
+
+    // z is always 0 for 2d blocks, which leaves seeds 9 to 12 out
+    let weight = |i : usize, shift : u32| {
+
+        (seeds[i] >> sh1) * x + (seeds[i + 1] >> sh2) * y + (rnum >> shift)
+    };
+
+    let a = weight(0, 14) & 63;
+
+    let b = weight(2, 10) & 63;
+
+    let c = if partitions > 2 { weight(4, 6) & 63 } else { 0 };
+
+    let d = if partitions > 3 { weight(6, 2) & 63 } else { 0 };
+
+    if a >= b && a >= c && a >= d {
+
+        0
+    } else if b >= c && b >= d {
+
+        1
+    } else if c >= d {
+
+        2
+    } else {
+
+        3
+    }
+}
+
+fn blue_contract(r : i32, g : i32, b : i32, a : i32) -> [i32; 4] {
+
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+// returns (offset, base)
+fn bit_transfer_signed(a : i32, b : i32) -> (i32, i32) {
+
+    let b = b >> 1 | a & 0x80;
+
+    let a = a >> 1 & 0x3f;
+
+    (if a & 0x20 != 0 { a - 0x40 } else { a }, b)
+}
+
+// ldr endpoint modes, None for the hdr ones
+fn endpoints(cem : u32, v : &[i32]) -> Option<([i32; 4], [i32; 4])> {
+
+    let (e0, e1) = match cem {
+        0 => ([v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]),
+        1 => {
+
+            let l0 = v[0] >> 2 | v[1] & 0xc0;
+
+            let l1 = (l0 + (v[1] & 0x3f)).min(255);
+
+            ([l0, l0, l0, 255], [l1, l1, l1, 255])
+        }
+        4 => ([v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]),
+        5 => {
+
+            let (o0, l) = bit_transfer_signed(v[1], v[0]);
+
+            let (o1, a) = bit_transfer_signed(v[3], v[2]);
+
+            ([l, l, l, a], [l + o0, l + o0, l + o0, a + o1])
+        }
+        6 => (
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                255,
+            ],
+            [v[0], v[1], v[2], 255],
+        ),
+        8 | 12 => {
+
+            let (a0, a1) = if cem == 12 { (v[6], v[7]) } else { (255, 255) };
+
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+
+                ([v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1])
+            } else {
+
+                (
+                    blue_contract(v[1], v[3], v[5], a1),
+                    blue_contract(v[0], v[2], v[4], a0),
+                )
+            }
+        }
+        9 | 13 => {
+
+            let (or, r) = bit_transfer_signed(v[1], v[0]);
+
+            let (og, g) = bit_transfer_signed(v[3], v[2]);
+
+            let (ob, b) = bit_transfer_signed(v[5], v[4]);
+
+            let (oa, a) = if cem == 13 {
+
+                bit_transfer_signed(v[7], v[6])
+            } else {
+
+                (0, 255)
+            };
+
+            if or + og + ob >= 0 {
+
+                ([r, g, b, a], [r + or, g + og, b + ob, a + oa])
+            } else {
+
+                (
+                    blue_contract(r + or, g + og, b + ob, a + oa),
+                    blue_contract(r, g, b, a),
+                )
+            }
+        }
+        10 => (
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                v[4],
+            ],
+            [v[0], v[1], v[2], v[5]],
+        ),
+        _ => return None,
+    };
+
+    Some((e0.map(|c| c.clamp(0, 255)), e1.map(|c| c.clamp(0, 255))))
+}
+
+// NOTE: the weight grid can be smaller than the block, texels take a bilinear mix of it
+fn infill(
+    weights : &[u32],
+    planes : usize,
+    plane : usize,
+    grid : &BlockMode,
+    width : u32,
+    height : u32,
+) -> Vec<u32> {
+
+    let (gw, gh) = (grid.width, grid.height);
+
+    let ds = (1024 + width / 2) / (width - 1);
+
+    let dt = (1024 + height / 2) / (height - 1);
+
+    let weight = |i : u32| {
+
+        weights
+            .get(i as usize * planes + plane)
+            .copied()
+            .unwrap_or(0)
+    };
+
+    let mut texels = Vec::with_capacity((width * height) as usize);
+
+    for t in 0..height {
+
+        for s in 0..width {
+
+            let gs = (ds * s * (gw - 1) + 32) >> 6;
+
+            let gt = (dt * t * (gh - 1) + 32) >> 6;
+
+            let (js, fs, jt, ft) = (gs >> 4, gs & 15, gt >> 4, gt & 15);
+
+            let w11 = (fs * ft + 8) >> 4;
+
+            let (w10, w01) = (ft - w11, fs - w11);
+
+            let w00 = 16 + w11 - fs - ft;
+
+            let v0 = js + jt * gw;
+
+            let mut sum = weight(v0) * w00 + 8;
+
+            // neighbours past the grid edge always have a zero factor
+            if w01 != 0 {
+
+                sum += weight(v0 + 1) * w01;
+            }
+
+            if w10 != 0 {
+
+                sum += weight(v0 + gw) * w10;
+            }
+
+            if w11 != 0 {
+
+                sum += weight(v0 + gw + 1) * w11;
+            }
+
+            texels.push(sum >> 4);
+        }
+    }
+
+    texels
+}
+
+fn decode(
+    block : &[u8],
+    width : u32,
+    height : u32,
+    srgb : bool,
+    out : &mut [[u8; 4]],
+) -> Option<()> {
+
+    let value = u128::from_le_bytes(block.try_into().ok()?);
+
+    let mode = read(value, 0, 11);
+
+    // void extent, one color for the whole block
+    if mode & 0x1ff == 0x1fc {
+
+        if mode & 0x200 != 0 {
+
+            return None;
+        }
+
+        let color = [0, 1, 2, 3].map(|i| (read(value, 64 + i * 16, 16) >> 8) as u8);
+
+        out.fill(color);
+
+        return Some(());
+    }
+
+    let grid = block_mode(mode)?;
+
+    let partitions = read(value, 11, 2) + 1;
+
+    let planes = 1 + grid.dual_plane as u32;
+
+    let weight_count = grid.width * grid.height * planes;
+
+    let weight_bits = ise_bits(weight_count, grid.range);
+
+    if grid.width > width
+        || grid.height > height
+        || weight_count > 64
+        || !(24..=96).contains(&weight_bits)
+        || partitions == 4 && grid.dual_plane
+    {
+
+        return None;
+    }
+
+    // NOTE: everything that does not fit the fixed fields is stored just below the weights
+    let mut below_weights = 128 - weight_bits;
+
+    let mut cems = [0u32; 4];
+
+    let color_start = if partitions == 1 {
+
+        cems[0] = read(value, 13, 4);
+
+        17
+    } else {
+
+        let mut cem = read(value, 23, 6);
+
+        if cem & 3 == 0 {
+
+            cems.fill(cem >> 2);
+        } else {
+
+            let extra = 3 * partitions - 4;
+
+            below_weights = below_weights.checked_sub(extra)?;
+
+            cem |= read(value, below_weights, extra) << 6;
+
+            let class = (cem & 3) - 1;
+
+            for (i, c) in cems.iter_mut().enumerate().take(partitions as usize) {
+
+                let i = i as u32;
+
+                *c = (class + (cem >> (2 + i) & 1)) << 2 | cem >> (2 + partitions + 2 * i) & 3;
+            }
+        }
+
+        29
+    };
+
+    let plane2 = if grid.dual_plane {
+
+        below_weights = below_weights.checked_sub(2)?;
+
+        Some(read(value, below_weights, 2) as usize)
+    } else {
+
+        None
+    };
+
+    let cems = &cems[..partitions as usize];
+
+    let value_count = cems.iter().map(|cem| (cem / 4 + 1) * 2).sum::<u32>();
+
+    let available = below_weights.checked_sub(color_start)?;
+
+    if value_count > 18 {
+
+        return None;
+    }
+
+    // the largest range the endpoint values fit in
+    let color_range = (MIN_COLOR_RANGE..RANGES.len())
+        .rev()
+        .find(|&range| ise_bits(value_count, range) <= available)?;
+
+    let colors = ise_decode(
+        value >> color_start & mask(available),
+        value_count,
+        color_range,
+    )
+    .into_iter()
+    .map(|v| unquantize_color(v, color_range))
+    .collect::<Vec<_>>();
+
+    let mut colors = colors.as_slice();
+
+    let mut ends = Vec::with_capacity(cems.len());
+
+    for &cem in cems {
+
+        let (values, rest) = colors.split_at(((cem / 4 + 1) * 2) as usize);
+
+        ends.push(endpoints(cem, values)?);
+
+        colors = rest;
+    }
+
+    // weights are stored bit reversed from the top of the block
+    let weights = ise_decode(
+        value.reverse_bits() & mask(weight_bits),
+        weight_count,
+        grid.range,
+    )
+    .into_iter()
+    .map(|v| unquantize_weight(v, grid.range))
+    .collect::<Vec<_>>();
+
+    let planes = planes as usize;
+
+    let texel_weights = (0..planes)
+        .map(|plane| infill(&weights, planes, plane, &grid, width, height))
+        .collect::<Vec<_>>();
+
+    let seed = read(value, 13, 10);
+
+    let small_block = width * height < 31;
+
+    // NOTE: srgb endpoints are expanded with 0x80 instead of repeating the byte
+    let expand = |c : i32| if srgb { c << 8 | 0x80 } else { c << 8 | c };
+
+    for (i, texel) in out.iter_mut().enumerate() {
+
+        let (x, y) = (i as u32 % width, i as u32 / width);
+
+        let partition = if partitions > 1 {
+
+            select_partition(seed, x, y, partitions, small_block)
+        } else {
+
+            0
+        };
+
+        let (e0, e1) = ends[partition];
+
+        for (c, channel) in texel.iter_mut().enumerate() {
+
+            let plane = if plane2 == Some(c) { 1 } else { 0 };
+
+            let w = texel_weights[plane][i] as i32;
+
+            let color = (expand(e0[c]) * (64 - w) + expand(e1[c]) * w + 32) / 64;
+
+            *channel = (color >> 8) as u8;
+        }
+    }
+
+    Some(())
+}
+
+// block is 16 bytes, out gets width * height texels in rows, the error color for blocks
+// this decoder can't or won't read
+pub fn decode_block(
+    block : &[u8],
+    width : usize,
+    height : usize,
+    srgb : bool,
+    out : &mut [[u8; 4]],
+) {
+
+    let texels = &mut out[..width * height];
+
+    if decode(block, width as u32, height as u32, srgb, texels).is_none() {
+
+        texels.fill(ERROR_COLOR);
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    // (first bit, length, value) fields of a block
+    fn block(fields : &[(u32, u32, u128)]) -> [u8; 16] {
+
+        fields
+            .iter()
+            .fold(0u128, |block, &(start, len, value)| {
+
+                block | (value & mask(len)) << start
+            })
+            .to_le_bytes()
+    }
+
+    #[test]
+
+    fn test_void_extent() {
+
+        let mut texels = [[0u8; 4]; 36];
+
+        // ldr void extent, extent coordinates all ones, 16 bit channels
+        let bytes = block(&[
+            (0, 12, 0xdfc),
+            (12, 52, u128::MAX),
+            (64, 16, 0x1234),
+            (80, 16, 0xff00),
+            (96, 16, 0x00ff),
+            (112, 16, 0x8000),
+        ]);
+
+        decode_block(&bytes, 6, 6, false, &mut texels);
+
+        assert!(texels.iter().all(|t| *t == [0x12, 0xff, 0x00, 0x80]));
+
+        // the hdr flag is not for an ldr decoder
+        decode_block(&block(&[(0, 12, 0xffc)]), 6, 6, false, &mut texels);
+
+        assert!(texels.iter().all(|t| *t == ERROR_COLOR));
+    }
+
+    #[test]
+
+    fn test_dual_plane() {
+
+        // 2 x 2 grid of 16 level weights on two planes, rgba direct endpoints in 8 bits
+        let mut fields = vec![(0, 11, 0x70e), (13, 4, 12)];
+
+        // r0 r1 g0 g1 b0 b1 a0 a1, black to white with opaque to transparent alpha
+        for (i, v) in [0, 255, 0, 255, 0, 255, 255, 0].into_iter().enumerate() {
+
+            fields.push((17 + i as u32 * 8, 8, v));
+        }
+
+        // alpha takes the second plane
+        fields.push((94, 2, 3));
+
+        // weights grow down from the top bit, bit reversed, plane 1 then plane 2 per point
+        for i in 0..8 {
+
+            let weight : u128 = if i % 2 == 0 { 8 } else { 0 };
+
+            fields.push((124 - i * 4, 4, (weight.reverse_bits() >> 124)));
+        }
+
+        let mut texels = [[0u8; 4]; 16];
+
+        decode_block(&block(&fields), 4, 4, false, &mut texels);
+
+        // weight 8 of 16 levels unquantizes to 35 / 64
+        assert!(texels.iter().all(|t| *t == [139, 139, 139, 255]));
+    }
+
+    #[test]
+
+    fn test_integer_sequences() {
+
+        // the scrambled trit order of the 6 level weights, and every trit / quint packing
+        let weights = (0..6).map(|v| unquantize_weight(v, 4)).collect::<Vec<_>>();
+
+        assert_eq!(weights, [0, 64, 12, 52, 25, 39]);
+
+        assert_eq!(
+            (0..6).map(|v| unquantize_color(v, 4)).collect::<Vec<_>>(),
+            [0, 255, 51, 204, 102, 153]
+        );
+
+        let mut seen_trits = std::collections::HashSet::new();
+
+        let mut seen_quints = std::collections::HashSet::new();
+
+        for packed in 0..256 {
+
+            seen_trits.insert(trits(packed));
+
+            if packed < 128 {
+
+                seen_quints.insert(quints(packed));
+            }
+        }
+
+        assert_eq!(seen_trits.len(), 243);
+
+        assert_eq!(seen_quints.len(), 125);
+
+        assert!(seen_trits.iter().flatten().all(|&t| t < 3));
+
+        assert!(seen_quints.iter().flatten().all(|&q| q < 5));
+    }
+}
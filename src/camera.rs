@@ -1,7 +1,29 @@
 // NOTE: https://sotrh.github.io/learn-wgpu/beginner/tutorial6-uniforms/#a-controller-for-our-camera
 
-use crate::share::OPENGL_TO_WGPU_MATRIX;
-use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+use crate::share::{OPENGL_TO_WGPU_MATRIX, REVERSED_Z_MATRIX};
+use cgmath::{InnerSpace, Rad, Vector3};
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+use winit::dpi::PhysicalPosition;
+use winit::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub enum Projection {
+    Perspective,
+    // height of the view volume in world units, the width follows the aspect
+    Orthographic { height : f32 },
+    // perspective without a far plane, zfar is ignored
+    InfinitePerspective,
+    // perspective with depth 1 at znear and 0 at zfar
+    ReversedZ,
+}
+
+impl Projection {
+    pub fn is_reversed(&self) -> bool { matches!(self, Projection::ReversedZ) }
+}
 
 pub struct Camera {
     pub eye : cgmath::Point3<f32>,
@@ -11,17 +33,56 @@ pub struct Camera {
     pub fovy : f32,
     pub znear : f32,
     pub zfar : f32,
+    pub projection : Projection,
 }
 
 impl Camera {
-    // dynamic matrix
-    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+    pub fn build_view_matrix(&self) -> cgmath::Matrix4<f32> {
+
+        cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    // NOTE: already in wgpu clip space (0..1 depth), no OPENGL_TO_WGPU_MATRIX needed
+    pub fn build_projection_matrix(&self) -> cgmath::Matrix4<f32> {
 
-        let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
+        let perspective = || {
 
-        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
+            OPENGL_TO_WGPU_MATRIX
+                * cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar)
+        };
 
-        proj * view
+        match self.projection {
+            Projection::Perspective => perspective(),
+            Projection::Orthographic { height } => {
+
+                let (half_w, half_h) = (height * 0.5 * self.aspect, height * 0.5);
+
+                OPENGL_TO_WGPU_MATRIX
+                    * cgmath::ortho(-half_w, half_w, -half_h, half_h, self.znear, self.zfar)
+            }
+            Projection::InfinitePerspective => {
+
+                // depth = 1 - znear / distance, reaches 1 at infinity
+                let f = 1.0 / (cgmath::Rad::from(cgmath::Deg(self.fovy)).0 * 0.5).tan();
+
+                #[rustfmt::skip]
+                let proj = cgmath::Matrix4::new(
+                    f / self.aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, -1.0, -1.0,
+                    0.0, 0.0, -self.znear, 0.0,
+                );
+
+                proj
+            }
+            Projection::ReversedZ => REVERSED_Z_MATRIX * perspective(),
+        }
+    }
+
+    // dynamic matrix
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+
+        self.build_projection_matrix() * self.build_view_matrix()
     }
 }
 
@@ -30,8 +91,17 @@ impl Camera {
 
 // multi dimention array
 // [ [1, 2, 3, 4], [1, 2, 3, 4], [1, 2, 3, 4], [1, 2, 3, 4] ]
+// NOTE: keep in sync with CameraUniform in assets/shaders/shader.wgsl
 pub struct CameraUniform {
     view_proj : [[f32; 4]; 4],
+    view : [[f32; 4]; 4],
+    proj : [[f32; 4]; 4],
+    // clip -> world, for rebuilding world positions from depth
+    inv_view_proj : [[f32; 4]; 4],
+    inv_view : [[f32; 4]; 4],
+    inv_proj : [[f32; 4]; 4],
+    // eye in world space, w unused, for specular highlights
+    view_position : [f32; 4],
 }
 
 impl CameraUniform {
@@ -39,44 +109,90 @@ impl CameraUniform {
 
         use cgmath::SquareMatrix;
 
+        let identity = cgmath::Matrix4::identity().into();
+
         Self {
-            view_proj : cgmath::Matrix4::identity().into(),
+            view_proj : identity,
+            view : identity,
+            proj : identity,
+            inv_view_proj : identity,
+            inv_view : identity,
+            inv_proj : identity,
+            view_position : [0.0; 4],
         }
     }
 
     pub fn update_view_proj(&mut self, camera : &Camera) {
 
-        self.view_proj = (OPENGL_TO_WGPU_MATRIX * camera.build_view_projection_matrix()).into();
+        self.view_position = camera.eye.to_homogeneous().into();
+
+        self.set_matrices(camera.build_view_matrix(), camera.build_projection_matrix());
     }
+
+    fn set_matrices(&mut self, view : cgmath::Matrix4<f32>, proj : cgmath::Matrix4<f32>) {
+
+        use cgmath::SquareMatrix;
+
+        let view_proj = proj * view;
+
+        let invert = |m : cgmath::Matrix4<f32>| m.invert().unwrap_or(cgmath::Matrix4::identity());
+
+        self.view = view.into();
+
+        self.proj = proj.into();
+
+        self.view_proj = view_proj.into();
+
+        self.inv_view = invert(view).into();
+
+        self.inv_proj = invert(proj).into();
+
+        self.inv_view_proj = invert(view_proj).into();
+    }
+
+    // NOTE: flips the depth direction of the projection after update_view_proj,
+    // for when the depth buffer and the camera projection disagree
+    pub fn reverse_z(&mut self) {
+
+        self.set_matrices(
+            self.view.into(),
+            REVERSED_Z_MATRIX * cgmath::Matrix4::from(self.proj),
+        );
+    }
+
+    pub fn view_proj(&self) -> cgmath::Matrix4<f32> { self.view_proj.into() }
+
+    pub fn inv_view_proj(&self) -> cgmath::Matrix4<f32> { self.inv_view_proj.into() }
 }
 
-pub struct CameraController {
-    speed : f32,
-    is_up_pressed : bool,
-    is_down_pressed : bool,
-    is_forward_pressed : bool,
-    is_backward_pressed : bool,
-    is_left_pressed : bool,
-    is_right_pressed : bool,
+// NOTE: controllers turn window events into camera motion
+// speeds are per second, update_camera gets the time since the last frame
+pub trait CameraController {
+    // true when the event was used by the controller
+    fn process_events(&mut self, event : &WindowEvent) -> bool;
+
+    fn update_camera(&mut self, camera : &mut Camera, dt : Duration);
 }
 
-impl CameraController {
-    pub fn new(speed : f32) -> Self {
+// stay a bit away from straight up / down, look_at breaks down there
+const MAX_PITCH : Rad<f32> = Rad(FRAC_PI_2 - 0.01);
 
-        Self {
-            speed,
-            is_up_pressed : false,
-            is_down_pressed : false,
-            is_forward_pressed : false,
-            is_backward_pressed : false,
-            is_left_pressed : false,
-            is_right_pressed : false,
-        }
-    }
+// held movement keys, WASD / arrows + Space / LShift
+#[derive(Debug, Default, Copy, Clone)]
 
-    pub fn process_events(&mut self, event : &WindowEvent) -> bool {
+struct MoveKeys {
+    forward : bool,
+    backward : bool,
+    left : bool,
+    right : bool,
+    up : bool,
+    down : bool,
+}
 
-        match event {
+impl MoveKeys {
+    fn process(&mut self, event : &WindowEvent) -> bool {
+
+        let (state, keycode) = match event {
             WindowEvent::KeyboardInput {
                 input:
                     KeyboardInput {
@@ -85,57 +201,104 @@ impl CameraController {
                         ..
                     },
                 ..
-            } => {
+            } => (state, keycode),
+            _ => return false,
+        };
 
-                let is_pressed = *state == ElementState::Pressed;
+        let is_pressed = *state == ElementState::Pressed;
 
-                match keycode {
-                    VirtualKeyCode::Space => {
+        let key = match keycode {
+            VirtualKeyCode::Space => &mut self.up,
+            VirtualKeyCode::LShift => &mut self.down,
+            VirtualKeyCode::W | VirtualKeyCode::Up => &mut self.forward,
+            VirtualKeyCode::A | VirtualKeyCode::Left => &mut self.left,
+            VirtualKeyCode::S | VirtualKeyCode::Down => &mut self.backward,
+            VirtualKeyCode::D | VirtualKeyCode::Right => &mut self.right,
+            _ => return false,
+        };
 
-                        self.is_up_pressed = is_pressed;
+        *key = is_pressed;
 
-                        true
-                    }
-                    VirtualKeyCode::LShift => {
+        true
+    }
 
-                        self.is_down_pressed = is_pressed;
+    // x = right, y = up, z = forward, each -1, 0 or 1
+    fn axis(&self) -> Vector3<f32> {
 
-                        true
-                    }
-                    VirtualKeyCode::W | VirtualKeyCode::Up => {
+        let axis =
+            |positive : bool, negative : bool| positive as i32 as f32 - negative as i32 as f32;
 
-                        self.is_forward_pressed = is_pressed;
+        Vector3::new(
+            axis(self.right, self.left),
+            axis(self.up, self.down),
+            axis(self.forward, self.backward),
+        )
+    }
+}
 
-                        true
-                    }
-                    VirtualKeyCode::A | VirtualKeyCode::Left => {
+// pixels moved since the previous cursor event
+fn cursor_delta(
+    last : &mut Option<PhysicalPosition<f64>>,
+    position : PhysicalPosition<f64>,
+) -> (f32, f32) {
 
-                        self.is_left_pressed = is_pressed;
+    let delta = match last {
+        Some(last) => ((position.x - last.x) as f32, (position.y - last.y) as f32),
+        None => (0.0, 0.0),
+    };
 
-                        true
-                    }
-                    VirtualKeyCode::S | VirtualKeyCode::Down => {
+    *last = Some(position);
 
-                        self.is_backward_pressed = is_pressed;
+    delta
+}
 
-                        true
-                    }
-                    VirtualKeyCode::D | VirtualKeyCode::Right => {
+// yaw around +y starting at +x, pitch up from the xz plane
+fn direction(yaw : Rad<f32>, pitch : Rad<f32>) -> Vector3<f32> {
 
-                        self.is_right_pressed = is_pressed;
+    Vector3::new(
+        pitch.0.cos() * yaw.0.cos(),
+        pitch.0.sin(),
+        pitch.0.cos() * yaw.0.sin(),
+    )
+}
 
-                        true
-                    }
-                    _ => false,
-                }
-            }
-            _ => false,
+fn yaw_pitch(direction : Vector3<f32>) -> (Rad<f32>, Rad<f32>) {
+
+    let direction = direction.normalize();
+
+    (
+        Rad(direction.z.atan2(direction.x)),
+        Rad(direction.y.clamp(-1.0, 1.0).asin()),
+    )
+}
+
+fn clamp_pitch(pitch : Rad<f32>) -> Rad<f32> { Rad(pitch.0.clamp(-MAX_PITCH.0, MAX_PITCH.0)) }
+
+// NOTE: the tutorial controller, W/S dolly towards the target, A/D and Space/LShift orbit it
+pub struct KeyboardController {
+    // units per second
+    pub speed : f32,
+    keys : MoveKeys,
+}
+
+impl KeyboardController {
+    pub fn new(speed : f32) -> Self {
+
+        Self {
+            speed,
+            keys : MoveKeys::default(),
         }
     }
+}
+
+impl CameraController for KeyboardController {
+    fn process_events(&mut self, event : &WindowEvent) -> bool { self.keys.process(event) }
 
-    pub fn update_camera(&self, camera : &mut Camera) {
+    fn update_camera(&mut self, camera : &mut Camera, dt : Duration) {
 
-        use cgmath::InnerSpace;
+        let step = self.speed * dt.as_secs_f32();
+
+        let axis = self.keys.axis();
 
         let forward = camera.target - camera.eye;
 
@@ -143,37 +306,392 @@ impl CameraController {
 
         let forward_mag = forward.magnitude();
 
-        // NOTE: z ++ --
         // Prevents glitching when camera gets too close to the
         // center of the scene.
-        if self.is_forward_pressed && forward_mag > self.speed {
+        if axis.z > 0.0 && forward_mag > step || axis.z < 0.0 {
+
+            camera.eye += forward_norm * step * axis.z;
+        }
+
+        // Redo radius calc in case the eye moved
+        let forward = camera.target - camera.eye;
+
+        let forward_mag = forward.magnitude();
+
+        let right = forward.normalize().cross(camera.up);
+
+        // Rescale the distance between the target and eye so
+        // that it doesn't change. The eye therefore still
+        // lies on the circle made by the target and eye.
+        let orbit = right * axis.x - camera.up * axis.y;
+
+        let moved = (forward + orbit * step).normalize();
 
-            camera.eye += forward_norm * self.speed;
+        // no orbiting over the poles
+        if moved.dot(camera.up).abs() < MAX_PITCH.0.sin() {
+
+            camera.eye = camera.target - moved * forward_mag;
+        }
+    }
+}
+
+// NOTE: first person, hold the right mouse button to look around, WASD moves on the ground plane
+pub struct FpsController {
+    // units per second
+    pub speed : f32,
+    // radians per pixel
+    pub sensitivity : f32,
+    yaw : Rad<f32>,
+    pitch : Rad<f32>,
+    keys : MoveKeys,
+    looking : bool,
+    cursor : Option<PhysicalPosition<f64>>,
+    rotate : (f32, f32),
+}
+
+impl FpsController {
+    // keeps the current view direction of the camera
+    pub fn new(camera : &Camera) -> Self {
+
+        let (yaw, pitch) = yaw_pitch(camera.target - camera.eye);
+
+        Self {
+            speed : 4.0,
+            sensitivity : 0.004,
+            yaw,
+            pitch : clamp_pitch(pitch),
+            keys : MoveKeys::default(),
+            looking : false,
+            cursor : None,
+            rotate : (0.0, 0.0),
+        }
+    }
+}
+
+impl CameraController for FpsController {
+    fn process_events(&mut self, event : &WindowEvent) -> bool {
+
+        match event {
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Right,
+                ..
+            } => {
+
+                self.looking = *state == ElementState::Pressed;
+
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+
+                let (dx, dy) = cursor_delta(&mut self.cursor, *position);
+
+                if self.looking {
+
+                    self.rotate.0 += dx;
+
+                    self.rotate.1 += dy;
+                }
+
+                self.looking
+            }
+            _ => self.keys.process(event),
         }
+    }
+
+    fn update_camera(&mut self, camera : &mut Camera, dt : Duration) {
+
+        // NOTE: mouse deltas are already per frame, only key motion scales with dt
+        self.yaw += Rad(self.rotate.0 * self.sensitivity);
+
+        self.pitch = clamp_pitch(self.pitch - Rad(self.rotate.1 * self.sensitivity));
+
+        self.rotate = (0.0, 0.0);
+
+        let forward = direction(self.yaw, Rad(0.0));
+
+        let right = forward.cross(Vector3::unit_y());
+
+        let axis = self.keys.axis();
 
-        if self.is_backward_pressed {
+        camera.eye += (forward * axis.z + right * axis.x + Vector3::unit_y() * axis.y)
+            * self.speed
+            * dt.as_secs_f32();
+
+        camera.target = camera.eye + direction(self.yaw, self.pitch);
+
+        camera.up = Vector3::unit_y();
+    }
+}
+
+// NOTE: orbit / arcball, left drag rotates, right or middle drag pans, the wheel zooms
+// A/D and Space/LShift orbit, W/S zoom from the keyboard
+pub struct OrbitController {
+    pub target : cgmath::Point3<f32>,
+    pub distance : f32,
+    pub min_distance : f32,
+    // radians per pixel
+    pub sensitivity : f32,
+    // keyboard orbit, radians per second
+    pub rotate_speed : f32,
+    // fraction of the distance per wheel line
+    pub zoom_step : f32,
+    yaw : Rad<f32>,
+    pitch : Rad<f32>,
+    keys : MoveKeys,
+    drag : Option<MouseButton>,
+    cursor : Option<PhysicalPosition<f64>>,
+    rotate : (f32, f32),
+    pan : (f32, f32),
+    zoom : f32,
+}
 
-            camera.eye -= forward_norm * self.speed;
+impl OrbitController {
+    // orbits the current camera target at the current distance
+    pub fn new(camera : &Camera) -> Self {
+
+        let offset = camera.eye - camera.target;
+
+        let (yaw, pitch) = yaw_pitch(offset);
+
+        Self {
+            target : camera.target,
+            distance : offset.magnitude(),
+            min_distance : 0.1,
+            sensitivity : 0.005,
+            rotate_speed : 1.5,
+            zoom_step : 0.1,
+            yaw,
+            pitch : clamp_pitch(pitch),
+            keys : MoveKeys::default(),
+            drag : None,
+            cursor : None,
+            rotate : (0.0, 0.0),
+            pan : (0.0, 0.0),
+            zoom : 0.0,
         }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn process_events(&mut self, event : &WindowEvent) -> bool {
 
-        let right = forward_norm.cross(camera.up);
+        match event {
+            WindowEvent::MouseInput { state, button, .. } => {
+
+                match (*state, self.drag) {
+                    (ElementState::Pressed, None) => self.drag = Some(*button),
+                    (ElementState::Released, Some(drag)) if drag == *button => self.drag = None,
+                    _ => {}
+                }
 
-        // Redo radius calc in case the up/ down is pressed.
-        // let forward = camera.target - camera.eye;
-        //
-        // let forward_mag = forward.magnitude();
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
 
-        if self.is_right_pressed {
+                let (dx, dy) = cursor_delta(&mut self.cursor, *position);
 
-            // Rescale the distance between the target and eye so
-            // that it doesn't change. The eye therefore still
-            // lies on the circle made by the target and eye.
-            camera.eye = camera.target - (forward + right * self.speed).normalize() * forward_mag;
+                match self.drag {
+                    Some(MouseButton::Left) => {
+
+                        self.rotate.0 += dx;
+
+                        self.rotate.1 += dy;
+                    }
+                    Some(_) => {
+
+                        self.pan.0 += dx;
+
+                        self.pan.1 += dy;
+                    }
+                    None => return false,
+                }
+
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+
+                self.zoom += match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    // roughly one line per 50 pixels
+                    MouseScrollDelta::PixelDelta(pixels) => pixels.y as f32 / 50.0,
+                };
+
+                true
+            }
+            _ => self.keys.process(event),
         }
+    }
+
+    fn update_camera(&mut self, camera : &mut Camera, dt : Duration) {
+
+        let dt = dt.as_secs_f32();
+
+        let axis = self.keys.axis();
+
+        self.yaw += Rad(self.rotate.0 * self.sensitivity - axis.x * self.rotate_speed * dt);
+
+        self.pitch = clamp_pitch(
+            self.pitch + Rad(self.rotate.1 * self.sensitivity + axis.y * self.rotate_speed * dt),
+        );
+
+        // wheel and W/S scale the distance, so zooming slows down close to the target
+        let zoom = self.zoom * self.zoom_step + axis.z * dt;
+
+        self.distance = (self.distance * (1.0 - zoom.clamp(-1.0, 0.9))).max(self.min_distance);
+
+        let offset = direction(self.yaw, self.pitch);
+
+        // pan in the view plane, scaled with the distance so the target follows the cursor
+        let right = (-offset).cross(Vector3::unit_y()).normalize();
+
+        let up = right.cross(-offset);
+
+        self.target += (up * self.pan.1 - right * self.pan.0) * self.sensitivity * self.distance;
+
+        self.rotate = (0.0, 0.0);
+
+        self.pan = (0.0, 0.0);
+
+        self.zoom = 0.0;
+
+        camera.target = self.target;
+
+        camera.eye = self.target + offset * self.distance;
+
+        camera.up = Vector3::unit_y();
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
 
-        if self.is_left_pressed {
+    fn camera() -> Camera {
 
-            camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
+        Camera {
+            eye : (0.0, 1.0, 2.0).into(),
+            target : (0.0, 0.0, 0.0).into(),
+            up : Vector3::unit_y(),
+            aspect : 1.0,
+            fovy : 45.0,
+            znear : 0.1,
+            zfar : 100.0,
+            projection : Projection::Perspective,
         }
     }
+
+    // world position -> wgpu ndc
+    fn project(camera : &Camera, p : cgmath::Point3<f32>) -> cgmath::Vector3<f32> {
+
+        let clip = camera.build_view_projection_matrix() * p.to_homogeneous();
+
+        clip.truncate() / clip.w
+    }
+
+    #[test]
+
+    fn test_projections() {
+
+        let mut camera = camera();
+
+        camera.eye = (0.0, 0.0, 5.0).into();
+
+        let near = cgmath::Point3::new(0.0, 0.0, 5.0 - camera.znear);
+
+        let far = cgmath::Point3::new(0.0, 0.0, 5.0 - camera.zfar);
+
+        let depth = |camera : &Camera| (project(camera, near).z, project(camera, far).z);
+
+        let (n, f) = depth(&camera);
+
+        assert!(n.abs() < 1e-4 && (f - 1.0).abs() < 1e-4);
+
+        camera.projection = Projection::ReversedZ;
+
+        let (n, f) = depth(&camera);
+
+        assert!((n - 1.0).abs() < 1e-4 && f.abs() < 1e-4);
+
+        // infinite: the far plane is still in front of depth 1
+        camera.projection = Projection::InfinitePerspective;
+
+        let (n, f) = depth(&camera);
+
+        assert!(n.abs() < 1e-4 && f > 0.99 && f < 1.0);
+
+        // orthographic: x does not shrink with distance
+        camera.projection = Projection::Orthographic { height : 4.0 };
+
+        let a = project(&camera, cgmath::Point3::new(1.0, 0.0, 0.0));
+
+        let b = project(&camera, cgmath::Point3::new(1.0, 0.0, -50.0));
+
+        assert!((a.x - 0.5).abs() < 1e-4 && (a.x - b.x).abs() < 1e-4);
+
+        // the inverse brings clip space back to the world
+        let mut uniform = CameraUniform::new();
+
+        uniform.update_view_proj(&camera);
+
+        let p = uniform.inv_view_proj() * uniform.view_proj() * cgmath::vec4(1.0, 2.0, 3.0, 1.0);
+
+        assert!((p - cgmath::vec4(1.0, 2.0, 3.0, 1.0)).magnitude() < 1e-3);
+    }
+
+    #[test]
+
+    fn test_fps_delta_time() {
+
+        let mut camera = camera();
+
+        let mut controller = FpsController::new(&camera);
+
+        controller.keys.up = true;
+
+        // two half steps move as far as one full step
+        controller.update_camera(&mut camera, Duration::from_millis(500));
+
+        controller.update_camera(&mut camera, Duration::from_millis(500));
+
+        assert!((camera.eye.y - (1.0 + controller.speed)).abs() < 1e-4);
+
+        // looking the same way as before
+        let view = (camera.target - camera.eye).normalize();
+
+        assert!((view - Vector3::new(0.0, -1.0, -2.0).normalize()).magnitude() < 1e-4);
+    }
+
+    #[test]
+
+    fn test_orbit_keeps_distance() {
+
+        let mut camera = camera();
+
+        let mut controller = OrbitController::new(&camera);
+
+        let distance = controller.distance;
+
+        controller.keys.right = true;
+
+        controller.update_camera(&mut camera, Duration::from_millis(250));
+
+        assert!(((camera.eye - camera.target).magnitude() - distance).abs() < 1e-4);
+
+        assert!((camera.eye.y - 1.0).abs() < 1e-4);
+
+        assert!(camera.eye.x.abs() > 0.1);
+
+        // pitch never reaches the poles
+        controller.keys = MoveKeys {
+            up : true,
+            ..Default::default()
+        };
+
+        controller.update_camera(&mut camera, Duration::from_secs(10));
+
+        assert!((camera.eye - camera.target).normalize().y < 1.0);
+    }
 }
@@ -0,0 +1,422 @@
+// NOTE: camera paths, record eye / target / fov keyframes and replay them
+// saved as plain text, one keyframe per line: time eye.xyz target.xyz fovy
+
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use cgmath::{EuclideanSpace, Point3};
+
+use crate::camera::Camera;
+
+const HEADER : &str = "# camera path v1";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub struct Keyframe {
+    // seconds from the start of the path
+    pub time : f32,
+    pub eye : Point3<f32>,
+    pub target : Point3<f32>,
+    pub fovy : f32,
+}
+
+impl Keyframe {
+    pub fn from_camera(time : f32, camera : &Camera) -> Self {
+
+        Self {
+            time,
+            eye : camera.eye,
+            target : camera.target,
+            fovy : camera.fovy,
+        }
+    }
+
+    pub fn apply(&self, camera : &mut Camera) {
+
+        camera.eye = self.eye;
+
+        camera.target = self.target;
+
+        camera.fovy = self.fovy;
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+
+pub enum Interpolation {
+    #[default]
+    Linear,
+    // passes through every keyframe with a smooth tangent
+    CatmullRom,
+}
+
+impl Interpolation {
+    fn name(&self) -> &'static str {
+
+        match self {
+            Interpolation::Linear => "linear",
+            Interpolation::CatmullRom => "catmull-rom",
+        }
+    }
+}
+
+// uniform Catmull-Rom between p1 and p2
+fn catmull_rom<V>(p0 : V, p1 : V, p2 : V, p3 : V, t : f32) -> V
+where
+    V : Copy + Add<Output = V> + Sub<Output = V> + Mul<f32, Output = V>,
+{
+
+    let (t2, t3) = (t * t, t * t * t);
+
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+
+pub struct CameraPath {
+    pub interpolation : Interpolation,
+    // sorted by time
+    keyframes : Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn new(interpolation : Interpolation) -> Self {
+
+        Self {
+            interpolation,
+            keyframes : Vec::new(),
+        }
+    }
+
+    // keeps the keyframes sorted, equal times go after the existing ones
+    pub fn push(&mut self, keyframe : Keyframe) {
+
+        let index = self.keyframes.partition_point(|k| k.time <= keyframe.time);
+
+        self.keyframes.insert(index, keyframe);
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] { &self.keyframes }
+
+    pub fn len(&self) -> usize { self.keyframes.len() }
+
+    pub fn is_empty(&self) -> bool { self.keyframes.is_empty() }
+
+    pub fn duration(&self) -> f32 { self.keyframes.last().map(|k| k.time).unwrap_or(0.0) }
+
+    // NOTE: clamped to the first / last keyframe, None for an empty path
+    pub fn sample(&self, time : f32) -> Option<Keyframe> {
+
+        let first = self.keyframes.first()?;
+
+        let last = self.keyframes.last()?;
+
+        if time <= first.time {
+
+            return Some(Keyframe { time, ..*first });
+        }
+
+        if time >= last.time {
+
+            return Some(Keyframe { time, ..*last });
+        }
+
+        // keyframes[i] <= time < keyframes[i + 1]
+        let i = self.keyframes.partition_point(|k| k.time <= time) - 1;
+
+        let (k1, k2) = (&self.keyframes[i], &self.keyframes[i + 1]);
+
+        let t = (time - k1.time) / (k2.time - k1.time);
+
+        let key = match self.interpolation {
+            Interpolation::Linear => Keyframe {
+                time,
+                eye : k1.eye + (k2.eye - k1.eye) * t,
+                target : k1.target + (k2.target - k1.target) * t,
+                fovy : k1.fovy + (k2.fovy - k1.fovy) * t,
+            },
+            Interpolation::CatmullRom => {
+
+                // the end points are repeated for the outer tangents
+                let k0 = &self.keyframes[i.saturating_sub(1)];
+
+                let k3 = &self.keyframes[(i + 2).min(self.keyframes.len() - 1)];
+
+                let point = |f : fn(&Keyframe) -> Point3<f32>| {
+
+                    Point3::from_vec(catmull_rom(
+                        f(k0).to_vec(),
+                        f(k1).to_vec(),
+                        f(k2).to_vec(),
+                        f(k3).to_vec(),
+                        t,
+                    ))
+                };
+
+                Keyframe {
+                    time,
+                    eye : point(|k| k.eye),
+                    target : point(|k| k.target),
+                    fovy : catmull_rom(k0.fovy, k1.fovy, k2.fovy, k3.fovy, t),
+                }
+            }
+        };
+
+        Some(key)
+    }
+
+    pub fn save(&self, path : impl AsRef<Path>) -> anyhow::Result<()> {
+
+        std::fs::write(path, self.to_string())?;
+
+        Ok(())
+    }
+
+    pub fn load(path : impl AsRef<Path>) -> anyhow::Result<Self> {
+
+        std::fs::read_to_string(path)?.parse()
+    }
+}
+
+impl fmt::Display for CameraPath {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+
+        writeln!(f, "{}", HEADER)?;
+
+        writeln!(f, "interpolation {}", self.interpolation.name())?;
+
+        for k in &self.keyframes {
+
+            writeln!(
+                f,
+                "{} {} {} {} {} {} {} {}",
+                k.time, k.eye.x, k.eye.y, k.eye.z, k.target.x, k.target.y, k.target.z, k.fovy
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for CameraPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s : &str) -> anyhow::Result<Self> {
+
+        let mut path = CameraPath::default();
+
+        for (number, line) in s.lines().enumerate() {
+
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix("interpolation ") {
+
+                path.interpolation = match name.trim() {
+                    "linear" => Interpolation::Linear,
+                    "catmull-rom" => Interpolation::CatmullRom,
+                    other => {
+
+                        anyhow::bail!("line {}: unknown interpolation {:?}", number + 1, other)
+                    }
+                };
+
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(f32::from_str)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow::anyhow!("line {}: {}", number + 1, e))?;
+
+            if values.len() != 8 {
+
+                anyhow::bail!(
+                    "line {}: expected 8 values, got {}",
+                    number + 1,
+                    values.len()
+                );
+            }
+
+            path.push(Keyframe {
+                time : values[0],
+                eye : Point3::new(values[1], values[2], values[3]),
+                target : Point3::new(values[4], values[5], values[6]),
+                fovy : values[7],
+            });
+        }
+
+        Ok(path)
+    }
+}
+
+// NOTE: samples the camera every `interval` seconds while recording
+pub struct CameraRecorder {
+    path : CameraPath,
+    interval : f32,
+    time : f32,
+}
+
+impl CameraRecorder {
+    pub fn new(interpolation : Interpolation, interval : Duration) -> Self {
+
+        Self {
+            path : CameraPath::new(interpolation),
+            interval : interval.as_secs_f32(),
+            time : 0.0,
+        }
+    }
+
+    pub fn record(&mut self, camera : &Camera, dt : Duration) {
+
+        let due = self
+            .path
+            .keyframes
+            .last()
+            .is_none_or(|last| self.time - last.time >= self.interval);
+
+        if due {
+
+            self.path.push(Keyframe::from_camera(self.time, camera));
+        }
+
+        self.time += dt.as_secs_f32();
+    }
+
+    pub fn path(&self) -> &CameraPath { &self.path }
+
+    // adds the final camera position so the path ends where the recording did
+    pub fn finish(mut self, camera : &Camera) -> CameraPath {
+
+        if self.path.duration() < self.time {
+
+            self.path.push(Keyframe::from_camera(self.time, camera));
+        }
+
+        self.path
+    }
+}
+
+// NOTE: drives the camera along a path, replaces the controller while playing
+pub struct CameraPlayback {
+    path : CameraPath,
+    time : f32,
+    pub looping : bool,
+}
+
+impl CameraPlayback {
+    pub fn new(path : CameraPath) -> Self {
+
+        Self {
+            path,
+            time : 0.0,
+            looping : false,
+        }
+    }
+
+    pub fn path(&self) -> &CameraPath { &self.path }
+
+    pub fn time(&self) -> f32 { self.time }
+
+    pub fn seek(&mut self, time : f32) { self.time = time.clamp(0.0, self.path.duration()); }
+
+    pub fn is_finished(&self) -> bool { !self.looping && self.time >= self.path.duration() }
+
+    // moves the camera to the current time, then advances by dt
+    pub fn update(&mut self, camera : &mut Camera, dt : Duration) {
+
+        if let Some(key) = self.path.sample(self.time) {
+
+            key.apply(camera);
+        }
+
+        let duration = self.path.duration();
+
+        self.time += dt.as_secs_f32();
+
+        if self.time > duration {
+
+            self.time = if self.looping && duration > 0.0 {
+
+                self.time % duration
+            } else {
+
+                duration
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    fn key(time : f32, x : f32) -> Keyframe {
+
+        Keyframe {
+            time,
+            eye : Point3::new(x, 1.0, 2.0),
+            target : Point3::new(0.0, 0.0, 0.0),
+            fovy : 45.0,
+        }
+    }
+
+    #[test]
+
+    fn test_sample() {
+
+        let mut path = CameraPath::new(Interpolation::Linear);
+
+        path.push(key(1.0, 2.0));
+
+        path.push(key(0.0, 0.0));
+
+        path.push(key(2.0, 6.0));
+
+        assert_eq!(path.keyframes()[0].time, 0.0);
+
+        assert!((path.sample(0.5).unwrap().eye.x - 1.0).abs() < 1e-5);
+
+        assert_eq!(path.sample(5.0).unwrap().eye.x, 6.0);
+
+        // catmull-rom still hits every keyframe
+        path.interpolation = Interpolation::CatmullRom;
+
+        assert!((path.sample(1.0).unwrap().eye.x - 2.0).abs() < 1e-5);
+
+        let x = path.sample(1.5).unwrap().eye.x;
+
+        assert!(x > 2.0 && x < 6.0);
+    }
+
+    #[test]
+
+    fn test_round_trip() {
+
+        let mut path = CameraPath::new(Interpolation::CatmullRom);
+
+        path.push(key(0.0, 0.25));
+
+        path.push(key(0.5, -3.5));
+
+        let parsed : CameraPath = path.to_string().parse().unwrap();
+
+        assert_eq!(parsed, path);
+
+        assert!("0 1 2".parse::<CameraPath>().is_err());
+    }
+}
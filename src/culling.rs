@@ -0,0 +1,467 @@
+// NOTE: frustum culling of instances
+// a compute pass compacts the visible instances and fills the indirect draw args,
+// the cpu path does the same test and is used when compute shaders are missing
+
+use cgmath::{InnerSpace, Vector4};
+
+use crate::camera::Camera;
+use crate::hot_reload::{self, ShaderWatcher};
+use crate::instance::InstanceBuffer;
+use crate::model::{BoundingSphere, Model};
+use crate::share::InstanceRaw;
+
+// keep in sync with @workgroup_size in assets/shaders/cull.wgsl
+const WORKGROUP_SIZE : u32 = 64;
+
+const INDIRECT_SIZE : wgpu::BufferAddress =
+    std::mem::size_of::<wgpu::util::DrawIndexedIndirect>() as wgpu::BufferAddress;
+
+// planes point inwards, a point is inside when dot(xyz, p) + w >= 0 for all of them
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub struct Frustum {
+    pub planes : [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    // NOTE: Gribb / Hartmann plane extraction, expects wgpu clip space (0..1 depth)
+    pub fn from_matrix(view_proj : cgmath::Matrix4<f32>) -> Self {
+
+        use cgmath::Matrix;
+
+        let (r0, r1, r2, r3) = (
+            view_proj.row(0),
+            view_proj.row(1),
+            view_proj.row(2),
+            view_proj.row(3),
+        );
+
+        // an infinite projection has no far plane, keep everything in front of it
+        let normalize = |p : Vector4<f32>| {
+
+            let length = p.truncate().magnitude();
+
+            if length > f32::EPSILON {
+
+                p / length
+            } else {
+
+                Vector4::new(0.0, 0.0, 0.0, 1.0)
+            }
+        };
+
+        Self {
+            planes : [
+                normalize(r3 + r0),
+                normalize(r3 - r0),
+                normalize(r3 + r1),
+                normalize(r3 - r1),
+                normalize(r2),
+                normalize(r3 - r2),
+            ],
+        }
+    }
+
+    // reversed z does not move the planes, only which one is near
+    pub fn from_camera(camera : &Camera) -> Self {
+
+        Self::from_matrix(camera.build_view_projection_matrix())
+    }
+
+    pub fn intersects_sphere(&self, sphere : &BoundingSphere) -> bool {
+
+        let center = sphere.center;
+
+        self.planes.iter().all(|plane| {
+
+            plane.x * center.x + plane.y * center.y + plane.z * center.z + plane.w >= -sphere.radius
+        })
+    }
+
+    // the same test as cull.wgsl, kept for the cpu fallback and tests
+    pub fn intersects_instance(&self, instance : &InstanceRaw, sphere : &BoundingSphere) -> bool {
+
+        self.intersects_sphere(&sphere.transform(instance.model_matrix()))
+    }
+
+    pub fn cull(&self, instances : &[InstanceRaw], sphere : &BoundingSphere) -> Vec<InstanceRaw> {
+
+        instances
+            .iter()
+            .filter(|instance| self.intersects_instance(instance, sphere))
+            .copied()
+            .collect()
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+
+struct CullParams {
+    planes : [[f32; 4]; 6],
+    sphere : [f32; 4],
+    count : u32,
+    _padding : [u32; 3],
+}
+
+// per model output of the culling pass, rebuilt when the source buffer grows
+pub struct CullTarget {
+    capacity : usize,
+    params_buffer : wgpu::Buffer,
+    visible_buffer : wgpu::Buffer,
+    count_buffer : wgpu::Buffer,
+    indirect_buffer : wgpu::Buffer,
+    bind_group : wgpu::BindGroup,
+}
+
+impl CullTarget {
+    // compacted instances, bind at vertex slot 1
+    pub fn visible_buffer(&self) -> &wgpu::Buffer { &self.visible_buffer }
+
+    // one DrawIndexedIndirect per mesh of the model
+    pub fn indirect_buffer(&self) -> &wgpu::Buffer { &self.indirect_buffer }
+
+    pub fn indirect_offset(mesh : usize) -> wgpu::BufferAddress {
+
+        mesh as wgpu::BufferAddress * INDIRECT_SIZE
+    }
+
+    // the source buffer was reallocated since this target was created
+    pub fn is_stale(&self, instances : &InstanceBuffer) -> bool {
+
+        self.capacity != instances.capacity()
+    }
+}
+
+pub struct GpuCuller {
+    pipeline : wgpu::ComputePipeline,
+    pipeline_layout : wgpu::PipelineLayout,
+    bind_group_layout : wgpu::BindGroupLayout,
+}
+
+impl GpuCuller {
+    // downlevel (webgl) devices have no compute shaders or storage buffers
+    pub fn is_supported(device : &wgpu::Device) -> bool {
+
+        let limits = device.limits();
+
+        limits.max_storage_buffers_per_shader_stage >= 3
+            && limits.max_compute_invocations_per_workgroup >= WORKGROUP_SIZE
+    }
+
+    pub fn new(device : &wgpu::Device) -> Self {
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility : wgpu::ShaderStages::COMPUTE,
+            ty : wgpu::BindingType::Buffer {
+                ty : wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset : false,
+                min_binding_size : None,
+            },
+            count : None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries : &[
+                wgpu::BindGroupLayoutEntry {
+                    binding : 0,
+                    visibility : wgpu::ShaderStages::COMPUTE,
+                    ty : wgpu::BindingType::Buffer {
+                        ty : wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset : false,
+                        min_binding_size : None,
+                    },
+                    count : None,
+                },
+                storage(1, true),
+                storage(2, false),
+                storage(3, false),
+            ],
+            label : Some("cull_bind_group_layout"),
+        });
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../assets/shaders/cull.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label : Some("Cull Pipeline Layout"),
+            bind_group_layouts : &[&bind_group_layout],
+            push_constant_ranges : &[],
+        });
+
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &shader);
+
+        Self {
+            pipeline,
+            pipeline_layout,
+            bind_group_layout,
+        }
+    }
+
+    fn create_pipeline(
+        device : &wgpu::Device,
+        layout : &wgpu::PipelineLayout,
+        shader : &wgpu::ShaderModule,
+    ) -> wgpu::ComputePipeline {
+
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label : Some("Cull Pipeline"),
+            layout : Some(layout),
+            module : shader,
+            entry_point : "cs_main",
+        })
+    }
+
+    // NOTE: `changed` comes from ShaderWatcher::poll
+    pub fn reload_shaders(
+        &mut self,
+        device : &wgpu::Device,
+        watcher : &mut ShaderWatcher,
+        changed : &[String],
+    ) {
+
+        for name in changed.iter().filter(|name| *name == "cull.wgsl") {
+
+            let reloaded = watcher.read(name).and_then(|source| {
+
+                hot_reload::compile(device, name, &source, |shader| {
+
+                    Self::create_pipeline(device, &self.pipeline_layout, shader)
+                })
+            });
+
+            let result = reloaded.map(|(_, pipeline)| self.pipeline = pipeline);
+
+            watcher.report(name, result);
+        }
+    }
+
+    pub fn create_target(
+        &self,
+        device : &wgpu::Device,
+        model : &Model,
+        instances : &InstanceBuffer,
+    ) -> CullTarget {
+
+        let capacity = instances.capacity();
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Cull Params"),
+            size : std::mem::size_of::<CullParams>() as wgpu::BufferAddress,
+            usage : wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        });
+
+        let visible_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Cull Visible Instances"),
+            size : (capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            usage : wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            mapped_at_creation : false,
+        });
+
+        let count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Cull Visible Count"),
+            size : std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            usage : wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        });
+
+        let indirect_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Cull Indirect Args"),
+            size : INDIRECT_SIZE * model.meshes.len().max(1) as wgpu::BufferAddress,
+            usage : wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout : &self.bind_group_layout,
+            entries : &[
+                wgpu::BindGroupEntry {
+                    binding : 0,
+                    resource : params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding : 1,
+                    resource : instances.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding : 2,
+                    resource : visible_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding : 3,
+                    resource : count_buffer.as_entire_binding(),
+                },
+            ],
+            label : Some("cull_bind_group"),
+        });
+
+        CullTarget {
+            capacity,
+            params_buffer,
+            visible_buffer,
+            count_buffer,
+            indirect_buffer,
+            bind_group,
+        }
+    }
+
+    // NOTE: the instance buffer must be synced first, the target must not be stale
+    pub fn encode(
+        &self,
+        queue : &wgpu::Queue,
+        encoder : &mut wgpu::CommandEncoder,
+        frustum : &Frustum,
+        model : &Model,
+        instances : &InstanceBuffer,
+        target : &CullTarget,
+    ) {
+
+        let sphere = model
+            .bounds()
+            .map(|bounds| bounds.bounding_sphere())
+            .unwrap_or(BoundingSphere {
+                center : cgmath::Point3::new(0.0, 0.0, 0.0),
+                radius : 0.0,
+            });
+
+        let params = CullParams {
+            planes : frustum.planes.map(|plane| plane.into()),
+            sphere : [
+                sphere.center.x,
+                sphere.center.y,
+                sphere.center.z,
+                sphere.radius,
+            ],
+            count : instances.len() as u32,
+            _padding : [0; 3],
+        };
+
+        queue.write_buffer(&target.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        queue.write_buffer(&target.count_buffer, 0, bytemuck::cast_slice(&[0u32]));
+
+        // instance_count is filled in by the copy below
+        for (i, mesh) in model.meshes.iter().enumerate() {
+
+            let args = wgpu::util::DrawIndexedIndirect {
+                vertex_count : mesh.num_elements,
+                instance_count : 0,
+                base_index : 0,
+                vertex_offset : 0,
+                base_instance : 0,
+            };
+
+            queue.write_buffer(
+                &target.indirect_buffer,
+                CullTarget::indirect_offset(i),
+                args.as_bytes(),
+            );
+        }
+
+        {
+
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label : Some("Cull Pass"),
+            });
+
+            cpass.set_pipeline(&self.pipeline);
+
+            cpass.set_bind_group(0, &target.bind_group, &[]);
+
+            cpass.dispatch_workgroups((instances.len() as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+
+        // instance_count sits right after vertex_count
+        for i in 0..model.meshes.len() {
+
+            encoder.copy_buffer_to_buffer(
+                &target.count_buffer,
+                0,
+                &target.indirect_buffer,
+                CullTarget::indirect_offset(i) + std::mem::size_of::<u32>() as wgpu::BufferAddress,
+                std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    use cgmath::{Matrix4, Point3, Vector3};
+
+    fn camera() -> Camera {
+
+        Camera {
+            eye : Point3::new(0.0, 0.0, 5.0),
+            target : Point3::new(0.0, 0.0, 0.0),
+            up : Vector3::unit_y(),
+            aspect : 1.0,
+            fovy : 90.0,
+            znear : 0.1,
+            zfar : 100.0,
+            projection : crate::camera::Projection::Perspective,
+        }
+    }
+
+    fn at(x : f32, y : f32, z : f32) -> InstanceRaw {
+
+        InstanceRaw::from_matrix(Matrix4::from_translation(Vector3::new(x, y, z)))
+    }
+
+    #[test]
+
+    fn test_frustum_sphere() {
+
+        let frustum = Frustum::from_camera(&camera());
+
+        let sphere = |x, y, z, radius| BoundingSphere {
+            center : Point3::new(x, y, z),
+            radius,
+        };
+
+        assert!(frustum.intersects_sphere(&sphere(0.0, 0.0, 0.0, 1.0)));
+
+        // behind the camera and past the far plane
+        assert!(!frustum.intersects_sphere(&sphere(0.0, 0.0, 10.0, 1.0)));
+
+        assert!(!frustum.intersects_sphere(&sphere(0.0, 0.0, -200.0, 1.0)));
+
+        // 90 degree fov, the side planes are 45 degrees off the view axis
+        assert!(!frustum.intersects_sphere(&sphere(10.0, 0.0, 0.0, 1.0)));
+
+        assert!(frustum.intersects_sphere(&sphere(10.0, 0.0, 0.0, 8.0)));
+    }
+
+    #[test]
+
+    fn test_cull_instances() {
+
+        let frustum = Frustum::from_camera(&camera());
+
+        let sphere = BoundingSphere {
+            center : Point3::new(0.0, 0.0, 0.0),
+            radius : 1.0,
+        };
+
+        let instances = [at(0.0, 0.0, 0.0), at(0.0, 50.0, 0.0), at(0.0, 0.0, -20.0)];
+
+        let visible = frustum.cull(&instances, &sphere);
+
+        assert_eq!(visible.len(), 2);
+
+        // scale grows the sphere back into view
+        let scaled = InstanceRaw::from_matrix(
+            Matrix4::from_translation(Vector3::new(8.0, 0.0, 0.0)) * Matrix4::from_scale(4.0),
+        );
+
+        assert!(frustum.intersects_instance(&scaled, &sphere));
+    }
+}
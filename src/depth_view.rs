@@ -0,0 +1,281 @@
+// NOTE: renders the depth buffer as a grayscale imgui image
+// [doc] https://sotrh.github.io/learn-wgpu/beginner/tutorial8-depth/
+
+use crate::camera::Camera;
+use crate::hot_reload::{self, ShaderWatcher};
+use crate::texture::{self, DepthMode};
+use wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+
+struct DepthParams {
+    znear : f32,
+    zfar : f32,
+    reversed : u32,
+    _padding : u32,
+}
+
+pub struct DepthView {
+    pub texture_id : imgui::TextureId,
+    pub size : [u32; 2],
+    pipeline : wgpu::RenderPipeline,
+    pipeline_layout : wgpu::PipelineLayout,
+    multisampled : bool,
+    bind_group_layout : wgpu::BindGroupLayout,
+    bind_group : wgpu::BindGroup,
+    params_buffer : wgpu::Buffer,
+}
+
+impl DepthView {
+    const FORMAT : wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    pub fn new(
+        device : &wgpu::Device,
+        renderer : &mut imgui_wgpu::Renderer,
+        depth_texture : &texture::Texture,
+        size : [u32; 2],
+    ) -> Self {
+
+        // NOTE: msaa depth needs its own binding type and entry point, recreate on change
+        let multisampled = depth_texture.texture.sample_count() > 1;
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries : &[
+                wgpu::BindGroupLayoutEntry {
+                    binding : 0,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Texture {
+                        multisampled,
+                        view_dimension : wgpu::TextureViewDimension::D2,
+                        sample_type : wgpu::TextureSampleType::Depth,
+                    },
+                    count : None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding : 1,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Buffer {
+                        ty : wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset : false,
+                        min_binding_size : None,
+                    },
+                    count : None,
+                },
+            ],
+            label : Some("depth_view_bind_group_layout"),
+        });
+
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label : Some("Depth View Params"),
+            contents : bytemuck::cast_slice(&[DepthParams {
+                znear : 0.1,
+                zfar : 100.0,
+                reversed : 0,
+                _padding : 0,
+            }]),
+            usage : wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, depth_texture, &params_buffer);
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../assets/shaders/depth_view.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label : Some("Depth View Pipeline Layout"),
+            bind_group_layouts : &[&bind_group_layout],
+            push_constant_ranges : &[],
+        });
+
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &shader, multisampled);
+
+        let texture_id = renderer
+            .textures
+            .insert(Self::create_target(device, renderer, size));
+
+        Self {
+            texture_id,
+            size,
+            pipeline,
+            pipeline_layout,
+            multisampled,
+            bind_group_layout,
+            bind_group,
+            params_buffer,
+        }
+    }
+
+    fn create_pipeline(
+        device : &wgpu::Device,
+        layout : &wgpu::PipelineLayout,
+        shader : &wgpu::ShaderModule,
+        multisampled : bool,
+    ) -> wgpu::RenderPipeline {
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label : Some("Depth View Pipeline"),
+            layout : Some(layout),
+            vertex : wgpu::VertexState {
+                module : shader,
+                entry_point : "vs_main",
+                buffers : &[],
+            },
+            fragment : Some(wgpu::FragmentState {
+                module : shader,
+                entry_point : if multisampled { "fs_main_ms" } else { "fs_main" },
+                targets : &[Some(Self::FORMAT.into())],
+            }),
+            primitive : wgpu::PrimitiveState::default(),
+            depth_stencil : None,
+            multisample : wgpu::MultisampleState::default(),
+            multiview : None,
+        })
+    }
+
+    // NOTE: `changed` comes from ShaderWatcher::poll
+    pub fn reload_shaders(
+        &mut self,
+        device : &wgpu::Device,
+        watcher : &mut ShaderWatcher,
+        changed : &[String],
+    ) {
+
+        for name in changed.iter().filter(|name| *name == "depth_view.wgsl") {
+
+            let reloaded = watcher.read(name).and_then(|source| {
+
+                hot_reload::compile(device, name, &source, |shader| {
+
+                    Self::create_pipeline(device, &self.pipeline_layout, shader, self.multisampled)
+                })
+            });
+
+            let result = reloaded.map(|(_, pipeline)| self.pipeline = pipeline);
+
+            watcher.report(name, result);
+        }
+    }
+
+    fn create_target(
+        device : &wgpu::Device,
+        renderer : &imgui_wgpu::Renderer,
+        size : [u32; 2],
+    ) -> imgui_wgpu::Texture {
+
+        let texture_config = imgui_wgpu::TextureConfig {
+            size : wgpu::Extent3d {
+                width : size[0],
+                height : size[1],
+                ..Default::default()
+            },
+            label : Some("depth_view_texture"),
+            format : Some(Self::FORMAT),
+            usage : wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            ..Default::default()
+        };
+
+        imgui_wgpu::Texture::new(device, renderer, texture_config)
+    }
+
+    fn create_bind_group(
+        device : &wgpu::Device,
+        layout : &wgpu::BindGroupLayout,
+        depth_texture : &texture::Texture,
+        params_buffer : &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+
+        // NOTE: stencil formats must be viewed through the depth aspect only
+        let depth_view = depth_texture
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor {
+                aspect : wgpu::TextureAspect::DepthOnly,
+                ..Default::default()
+            });
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries : &[
+                wgpu::BindGroupEntry {
+                    binding : 0,
+                    resource : wgpu::BindingResource::TextureView(&depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding : 1,
+                    resource : params_buffer.as_entire_binding(),
+                },
+            ],
+            label : Some("depth_view_bind_group"),
+        })
+    }
+
+    // NOTE: call whenever the depth texture is recreated (resize, depth mode)
+    pub fn resize(
+        &mut self,
+        device : &wgpu::Device,
+        renderer : &mut imgui_wgpu::Renderer,
+        depth_texture : &texture::Texture,
+        size : [u32; 2],
+    ) {
+
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            depth_texture,
+            &self.params_buffer,
+        );
+
+        if self.size != size {
+
+            self.size = size;
+
+            renderer
+                .textures
+                .replace(self.texture_id, Self::create_target(device, renderer, size));
+        }
+    }
+
+    pub fn encode(
+        &self,
+        queue : &wgpu::Queue,
+        encoder : &mut wgpu::CommandEncoder,
+        renderer : &imgui_wgpu::Renderer,
+        camera : &Camera,
+        mode : DepthMode,
+    ) {
+
+        let params = DepthParams {
+            znear : camera.znear,
+            zfar : camera.zfar,
+            reversed : mode.is_reversed() as u32,
+            _padding : 0,
+        };
+
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        let target = match renderer.textures.get(self.texture_id) {
+            Some(target) => target,
+            None => return,
+        };
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label : Some("Depth View Pass"),
+            color_attachments : &[Some(wgpu::RenderPassColorAttachment {
+                view : target.view(),
+                resolve_target : None,
+                ops : wgpu::Operations {
+                    load : wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store : true,
+                },
+            })],
+            depth_stencil_attachment : None,
+        });
+
+        rpass.set_pipeline(&self.pipeline);
+
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+
+        rpass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,417 @@
+// NOTE: the scene renders into a float target, a fullscreen pass maps it to the surface
+// [doc] https://sotrh.github.io/learn-wgpu/intermediate/tutorial13-hdr/
+
+use crate::hot_reload::{self, ShaderWatcher};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+
+struct ToneMapParams {
+    exposure : f32,
+    curve : u32,
+    encode_srgb : u32,
+    _padding : u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+
+pub enum ToneMapOperator {
+    // values above 1 are simply cut off
+    Clamp,
+    Reinhard,
+    #[default]
+    Aces,
+}
+
+impl ToneMapOperator {
+    pub const ALL : [ToneMapOperator; 3] = [
+        ToneMapOperator::Clamp,
+        ToneMapOperator::Reinhard,
+        ToneMapOperator::Aces,
+    ];
+
+    pub fn name(&self) -> &'static str {
+
+        match self {
+            ToneMapOperator::Clamp => "Clamp",
+            ToneMapOperator::Reinhard => "Reinhard",
+            ToneMapOperator::Aces => "ACES",
+        }
+    }
+
+    // same curves as tonemap.wgsl, for one linear channel
+    pub fn apply(&self, x : f32) -> f32 {
+
+        let x = x.max(0.0);
+
+        match self {
+            ToneMapOperator::Clamp => x.min(1.0),
+            ToneMapOperator::Reinhard => x / (x + 1.0),
+            ToneMapOperator::Aces => {
+                (x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub struct ToneMapping {
+    pub operator : ToneMapOperator,
+    // linear scale before the curve, 1 = unchanged
+    pub exposure : f32,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+
+        Self {
+            operator : ToneMapOperator::default(),
+            exposure : 1.0,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn map(&self, x : f32) -> f32 { self.operator.apply(x * self.exposure) }
+}
+
+pub struct HdrPipeline {
+    texture : wgpu::Texture,
+    view : wgpu::TextureView,
+    // multisampled color target resolved into `view`, None at 1 sample
+    msaa_view : Option<wgpu::TextureView>,
+    sample_count : u32,
+    output_format : wgpu::TextureFormat,
+    pipeline : wgpu::RenderPipeline,
+    pipeline_layout : wgpu::PipelineLayout,
+    bind_group_layout : wgpu::BindGroupLayout,
+    bind_group : wgpu::BindGroup,
+    params_buffer : wgpu::Buffer,
+}
+
+impl HdrPipeline {
+    // NOTE: what every scene pipeline renders into
+    pub const FORMAT : wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    pub fn new(
+        device : &wgpu::Device,
+        width : u32,
+        height : u32,
+        output_format : wgpu::TextureFormat,
+    ) -> Self {
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries : &[
+                wgpu::BindGroupLayoutEntry {
+                    binding : 0,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Texture {
+                        multisampled : false,
+                        view_dimension : wgpu::TextureViewDimension::D2,
+                        sample_type : wgpu::TextureSampleType::Float { filterable : false },
+                    },
+                    count : None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding : 1,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Buffer {
+                        ty : wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset : false,
+                        min_binding_size : None,
+                    },
+                    count : None,
+                },
+            ],
+            label : Some("hdr_bind_group_layout"),
+        });
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Tone Map Params"),
+            size : std::mem::size_of::<ToneMapParams>() as wgpu::BufferAddress,
+            usage : wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        });
+
+        let (texture, view) = Self::create_target(device, width, height, 1);
+
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &view, &params_buffer);
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../assets/shaders/tonemap.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label : Some("Tone Map Pipeline Layout"),
+            bind_group_layouts : &[&bind_group_layout],
+            push_constant_ranges : &[],
+        });
+
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &shader, output_format);
+
+        Self {
+            texture,
+            view,
+            msaa_view : None,
+            sample_count : 1,
+            output_format,
+            pipeline,
+            pipeline_layout,
+            bind_group_layout,
+            bind_group,
+            params_buffer,
+        }
+    }
+
+    fn create_pipeline(
+        device : &wgpu::Device,
+        layout : &wgpu::PipelineLayout,
+        shader : &wgpu::ShaderModule,
+        output_format : wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label : Some("Tone Map Pipeline"),
+            layout : Some(layout),
+            vertex : wgpu::VertexState {
+                module : shader,
+                entry_point : "vs_main",
+                buffers : &[],
+            },
+            fragment : Some(wgpu::FragmentState {
+                module : shader,
+                entry_point : "fs_main",
+                targets : &[Some(output_format.into())],
+            }),
+            primitive : wgpu::PrimitiveState::default(),
+            depth_stencil : None,
+            multisample : wgpu::MultisampleState::default(),
+            multiview : None,
+        })
+    }
+
+    // NOTE: `changed` comes from ShaderWatcher::poll
+    pub fn reload_shaders(
+        &mut self,
+        device : &wgpu::Device,
+        watcher : &mut ShaderWatcher,
+        changed : &[String],
+    ) {
+
+        for name in changed.iter().filter(|name| *name == "tonemap.wgsl") {
+
+            let reloaded = watcher.read(name).and_then(|source| {
+
+                hot_reload::compile(device, name, &source, |shader| {
+
+                    Self::create_pipeline(device, &self.pipeline_layout, shader, self.output_format)
+                })
+            });
+
+            let result = reloaded.map(|(_, pipeline)| self.pipeline = pipeline);
+
+            watcher.report(name, result);
+        }
+    }
+
+    fn create_target(
+        device : &wgpu::Device,
+        width : u32,
+        height : u32,
+        sample_count : u32,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+
+        // only the resolved target is read by the tone map pass
+        let usage = if sample_count > 1 {
+
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        } else {
+
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label : Some(if sample_count > 1 {
+
+                "hdr_msaa_target"
+            } else {
+
+                "hdr_target"
+            }),
+            size : wgpu::Extent3d {
+                width : width.max(1),
+                height : height.max(1),
+                depth_or_array_layers : 1,
+            },
+            mip_level_count : 1,
+            sample_count,
+            dimension : wgpu::TextureDimension::D2,
+            format : Self::FORMAT,
+            usage,
+            view_formats : &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        (texture, view)
+    }
+
+    fn create_bind_group(
+        device : &wgpu::Device,
+        layout : &wgpu::BindGroupLayout,
+        view : &wgpu::TextureView,
+        params_buffer : &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries : &[
+                wgpu::BindGroupEntry {
+                    binding : 0,
+                    resource : wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding : 1,
+                    resource : params_buffer.as_entire_binding(),
+                },
+            ],
+            label : Some("hdr_bind_group"),
+        })
+    }
+
+    // NOTE: called from State::resize, the target always matches the surface
+    pub fn resize(&mut self, device : &wgpu::Device, width : u32, height : u32) {
+
+        (self.texture, self.view) = Self::create_target(device, width, height, 1);
+
+        self.msaa_view = Self::create_msaa_view(device, width, height, self.sample_count);
+
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.view,
+            &self.params_buffer,
+        );
+    }
+
+    fn create_msaa_view(
+        device : &wgpu::Device,
+        width : u32,
+        height : u32,
+        sample_count : u32,
+    ) -> Option<wgpu::TextureView> {
+
+        (sample_count > 1).then(|| Self::create_target(device, width, height, sample_count).1)
+    }
+
+    // NOTE: the caller validates the count against the adapter, see State::set_sample_count
+    pub fn set_sample_count(&mut self, device : &wgpu::Device, sample_count : u32) {
+
+        self.sample_count = sample_count;
+
+        self.msaa_view = Self::create_msaa_view(
+            device,
+            self.texture.width(),
+            self.texture.height(),
+            sample_count,
+        );
+    }
+
+    pub fn sample_count(&self) -> u32 { self.sample_count }
+
+    // what the scene pass draws into, resolving into `view` when multisampled
+    pub fn color_attachment(
+        &self,
+        load : wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'_> {
+
+        let ops = wgpu::Operations { load, store : true };
+
+        match &self.msaa_view {
+            Some(msaa_view) => wgpu::RenderPassColorAttachment {
+                view : msaa_view,
+                resolve_target : Some(&self.view),
+                ops,
+            },
+            None => wgpu::RenderPassColorAttachment {
+                view : &self.view,
+                resolve_target : None,
+                ops,
+            },
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView { &self.view }
+
+    pub fn texture(&self) -> &wgpu::Texture { &self.texture }
+
+    pub fn process(
+        &self,
+        queue : &wgpu::Queue,
+        encoder : &mut wgpu::CommandEncoder,
+        output : &wgpu::TextureView,
+        tone_mapping : ToneMapping,
+    ) {
+
+        let params = ToneMapParams {
+            exposure : tone_mapping.exposure,
+            curve : tone_mapping.operator as u32,
+            // srgb surfaces encode on write, the others get it in the shader
+            encode_srgb : !self.output_format.describe().srgb as u32,
+            _padding : 0,
+        };
+
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label : Some("Tone Map Pass"),
+            color_attachments : &[Some(wgpu::RenderPassColorAttachment {
+                view : output,
+                resolve_target : None,
+                ops : wgpu::Operations {
+                    load : wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store : true,
+                },
+            })],
+            depth_stencil_attachment : None,
+        });
+
+        rpass.set_pipeline(&self.pipeline);
+
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    #[test]
+
+    fn test_tone_map_curves() {
+
+        for operator in ToneMapOperator::ALL {
+
+            assert_eq!(operator.apply(0.0), 0.0);
+
+            assert!(operator.apply(100.0) <= 1.0);
+
+            assert!(operator.apply(0.5) < operator.apply(0.6));
+        }
+
+        assert_eq!(ToneMapOperator::Reinhard.apply(1.0), 0.5);
+
+        assert_eq!(ToneMapOperator::Clamp.apply(4.0), 1.0);
+
+        let tone_mapping = ToneMapping {
+            operator : ToneMapOperator::Reinhard,
+            exposure : 2.0,
+        };
+
+        assert_eq!(tone_mapping.map(0.5), 0.5);
+    }
+}
@@ -0,0 +1,236 @@
+// NOTE: development mode shader reloading
+// the shader directory is polled for changed .wgsl files, owners of a pipeline recompile
+// the ones they use with `compile` and keep the last good pipeline when it fails
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+pub struct ShaderWatcher {
+    dir : PathBuf,
+    modified : HashMap<String, SystemTime>,
+    interval : Duration,
+    last_check : Instant,
+    // last error per shader, cleared by the next good compile
+    errors : BTreeMap<String, String>,
+}
+
+impl ShaderWatcher {
+    // the files as they are now count as seen, only later changes are reported
+    pub fn new(dir : impl Into<PathBuf>) -> Self {
+
+        let dir = dir.into();
+
+        let modified = scan(&dir);
+
+        Self {
+            dir,
+            modified,
+            interval : Duration::from_millis(500),
+            last_check : Instant::now(),
+            errors : BTreeMap::new(),
+        }
+    }
+
+    // assets/shaders in the source tree, where the include_wgsl! copies come from
+    pub fn source_dir() -> PathBuf { Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/shaders") }
+
+    // None outside of debug builds or when the source tree is gone
+    pub fn development() -> Option<Self> {
+
+        let dir = Self::source_dir();
+
+        (cfg!(debug_assertions) && dir.is_dir()).then(|| Self::new(dir))
+    }
+
+    pub fn with_interval(mut self, interval : Duration) -> Self {
+
+        self.interval = interval;
+
+        self
+    }
+
+    pub fn dir(&self) -> &Path { &self.dir }
+
+    // file names of the shaders written since the last poll, at most once per interval
+    pub fn poll(&mut self) -> Vec<String> {
+
+        if self.last_check.elapsed() < self.interval {
+
+            return Vec::new();
+        }
+
+        self.last_check = Instant::now();
+
+        let modified = scan(&self.dir);
+
+        let mut changed = modified
+            .iter()
+            .filter(|(name, time)| self.modified.get(*name) != Some(time))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        changed.sort();
+
+        self.modified = modified;
+
+        changed
+    }
+
+    pub fn read(&self, name : &str) -> anyhow::Result<String> {
+
+        let path = self.dir.join(name);
+
+        std::fs::read_to_string(&path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    // NOTE: keeps the error around for the imgui window, a good compile clears it
+    pub fn report(&mut self, name : &str, result : anyhow::Result<()>) {
+
+        match result {
+            Ok(()) => {
+
+                log::info!("reloaded {}", name);
+
+                self.errors.remove(name);
+            }
+            Err(e) => {
+
+                log::error!("{:#}", e);
+
+                self.errors.insert(name.to_string(), format!("{:#}", e));
+            }
+        }
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = (&str, &str)> {
+
+        self.errors
+            .iter()
+            .map(|(name, error)| (name.as_str(), error.as_str()))
+    }
+
+    // only shown while a shader is broken
+    pub fn ui(&self, ui : &imgui::Ui) {
+
+        if self.errors.is_empty() {
+
+            return;
+        }
+
+        ui.window("Shader Errors")
+            .size([520.0, 260.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+
+                ui.text("last good pipelines are kept until these compile");
+
+                for (name, error) in self.errors() {
+
+                    ui.separator();
+
+                    ui.text_colored([1.0, 0.4, 0.4, 1.0], name);
+
+                    ui.text_wrapped(error);
+                }
+            });
+    }
+}
+
+fn scan(dir : &Path) -> HashMap<String, SystemTime> {
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return HashMap::new(),
+    };
+
+    entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "wgsl"))
+        .filter_map(|entry| {
+
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+
+            Some((entry.file_name().to_string_lossy().into_owned(), modified))
+        })
+        .collect()
+}
+
+// NOTE: module + whatever is built from it inside one validation scope, so naga errors
+// and pipeline/layout mismatches come back here instead of the uncaptured error handler
+pub fn compile<T>(
+    device : &wgpu::Device,
+    name : &str,
+    source : &str,
+    build : impl FnOnce(&wgpu::ShaderModule) -> T,
+) -> anyhow::Result<(wgpu::ShaderModule, T)> {
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label : Some(name),
+        source : wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    let built = build(&module);
+
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(error) => Err(anyhow::anyhow!("{}: {}", name, error)),
+        None => Ok((module, built)),
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    #[test]
+
+    fn test_shader_watcher() {
+
+        let dir = std::env::temp_dir().join(format!("shader_watcher_{}", std::process::id()));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::write(dir.join("a.wgsl"), "// a").unwrap();
+
+        std::fs::write(dir.join("notes.txt"), "not a shader").unwrap();
+
+        let mut watcher = ShaderWatcher::new(&dir).with_interval(Duration::ZERO);
+
+        // existing files are not reported
+        assert!(watcher.poll().is_empty());
+
+        // NOTE: set the time explicitly, some file systems only keep whole seconds
+        let file = std::fs::File::options()
+            .write(true)
+            .open(dir.join("a.wgsl"))
+            .unwrap();
+
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+
+        std::fs::write(dir.join("b.wgsl"), "// b").unwrap();
+
+        std::fs::write(dir.join("notes.txt"), "still not a shader").unwrap();
+
+        assert_eq!(watcher.poll(), ["a.wgsl", "b.wgsl"]);
+
+        assert!(watcher.poll().is_empty());
+
+        assert_eq!(watcher.read("b.wgsl").unwrap(), "// b");
+
+        assert!(watcher.read("missing.wgsl").is_err());
+
+        watcher.report("a.wgsl", Err(anyhow::anyhow!("broken")));
+
+        assert_eq!(watcher.errors().collect::<Vec<_>>(), [("a.wgsl", "broken")]);
+
+        watcher.report("a.wgsl", Ok(()));
+
+        assert_eq!(watcher.errors().count(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
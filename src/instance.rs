@@ -0,0 +1,241 @@
+// NOTE: growable instance buffer, cpu copy + gpu buffer kept in sync
+// capacity doubles when it runs out, otherwise only the dirty range is written
+
+use std::ops::Range;
+
+use crate::share::InstanceRaw;
+
+// smallest allocation, avoids reallocating for the first few pushes
+const MIN_CAPACITY : usize = 16;
+
+// amortized growth, at least double the old capacity
+fn grow_capacity(capacity : usize, required : usize) -> usize {
+
+    required.max(capacity * 2).max(MIN_CAPACITY)
+}
+
+// union of every range touched since the last sync
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+
+struct DirtyRange(Option<Range<usize>>);
+
+impl DirtyRange {
+    fn mark(&mut self, range : Range<usize>) {
+
+        if range.is_empty() {
+
+            return;
+        }
+
+        self.0 = Some(match self.0.take() {
+            Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+            None => range,
+        });
+    }
+
+    fn take(&mut self) -> Option<Range<usize>> { self.0.take() }
+}
+
+pub struct InstanceBuffer {
+    label : String,
+    buffer : wgpu::Buffer,
+    capacity : usize,
+    instances : Vec<InstanceRaw>,
+    dirty : DirtyRange,
+}
+
+impl InstanceBuffer {
+    pub fn new(device : &wgpu::Device, label : &str, capacity : usize) -> Self {
+
+        let capacity = capacity.max(MIN_CAPACITY);
+
+        Self {
+            label : label.to_string(),
+            buffer : Self::create_buffer(device, label, capacity),
+            capacity,
+            instances : Vec::with_capacity(capacity),
+            dirty : DirtyRange::default(),
+        }
+    }
+
+    // NOTE: still needs a sync before drawing
+    pub fn from_instances(
+        device : &wgpu::Device,
+        label : &str,
+        instances : &[InstanceRaw],
+    ) -> Self {
+
+        let mut buffer = Self::new(device, label, instances.len());
+
+        buffer.set(instances);
+
+        buffer
+    }
+
+    fn create_buffer(device : &wgpu::Device, label : &str, capacity : usize) -> wgpu::Buffer {
+
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some(label),
+            size : (capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            // NOTE: storage, the culling pass reads it as the source instances
+            usage : wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        })
+    }
+
+    pub fn push(&mut self, instance : InstanceRaw) -> usize {
+
+        self.instances.push(instance);
+
+        let index = self.instances.len() - 1;
+
+        self.dirty.mark(index..index + 1);
+
+        index
+    }
+
+    pub fn update(&mut self, index : usize, instance : InstanceRaw) -> bool {
+
+        match self.instances.get_mut(index) {
+            Some(slot) => {
+
+                *slot = instance;
+
+                self.dirty.mark(index..index + 1);
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    // the last instance takes the removed slot, indices past it are not stable
+    pub fn swap_remove(&mut self, index : usize) -> Option<InstanceRaw> {
+
+        if index >= self.instances.len() {
+
+            return None;
+        }
+
+        let removed = self.instances.swap_remove(index);
+
+        if index < self.instances.len() {
+
+            self.dirty.mark(index..index + 1);
+        }
+
+        Some(removed)
+    }
+
+    // replaces everything, only the instances that differ are marked dirty
+    pub fn set(&mut self, instances : &[InstanceRaw]) {
+
+        let same =
+            |a : &InstanceRaw, b : &InstanceRaw| bytemuck::bytes_of(a) == bytemuck::bytes_of(b);
+
+        let overlap = self.instances.len().min(instances.len());
+
+        let first = (0..overlap).find(|i| !same(&self.instances[*i], &instances[*i]));
+
+        let last = (0..overlap)
+            .rev()
+            .find(|i| !same(&self.instances[*i], &instances[*i]));
+
+        if let (Some(first), Some(last)) = (first, last) {
+
+            self.dirty.mark(first..last + 1);
+        }
+
+        // appended instances are new data, removed ones just shrink the draw range
+        self.dirty.mark(overlap..instances.len());
+
+        self.instances.clear();
+
+        self.instances.extend_from_slice(instances);
+    }
+
+    pub fn clear(&mut self) { self.instances.clear(); }
+
+    pub fn instances(&self) -> &[InstanceRaw] { &self.instances }
+
+    pub fn len(&self) -> usize { self.instances.len() }
+
+    pub fn is_empty(&self) -> bool { self.instances.is_empty() }
+
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    pub fn buffer(&self) -> &wgpu::Buffer { &self.buffer }
+
+    // instance range for draw calls
+    pub fn range(&self) -> Range<u32> { 0..self.instances.len() as u32 }
+
+    // NOTE: call before encoding draws, returns true when the buffer was reallocated
+    pub fn sync(&mut self, device : &wgpu::Device, queue : &wgpu::Queue) -> bool {
+
+        if self.instances.len() > self.capacity {
+
+            self.capacity = grow_capacity(self.capacity, self.instances.len());
+
+            self.buffer = Self::create_buffer(device, &self.label, self.capacity);
+
+            self.dirty.take();
+
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.instances));
+
+            return true;
+        }
+
+        if let Some(range) = self.dirty.take() {
+
+            let end = range.end.min(self.instances.len());
+
+            if range.start < end {
+
+                queue.write_buffer(
+                    &self.buffer,
+                    (range.start * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+                    bytemuck::cast_slice(&self.instances[range.start..end]),
+                );
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    #[test]
+
+    fn test_grow_capacity() {
+
+        assert_eq!(grow_capacity(0, 1), MIN_CAPACITY);
+
+        assert_eq!(grow_capacity(16, 17), 32);
+
+        assert_eq!(grow_capacity(16, 100), 100);
+    }
+
+    #[test]
+
+    fn test_dirty_range() {
+
+        let mut dirty = DirtyRange::default();
+
+        dirty.mark(4..5);
+
+        dirty.mark(0..0);
+
+        dirty.mark(10..12);
+
+        assert_eq!(dirty.take(), Some(4..12));
+
+        assert_eq!(dirty.take(), None);
+    }
+}
@@ -1,11 +1,28 @@
+pub mod asset;
+pub mod astc;
 pub mod camera;
+pub mod camera_path;
+pub mod culling;
+pub mod depth_view;
 pub mod framework;
 pub mod gpu;
+pub mod hot_reload;
+pub mod hdr;
 pub mod imgui_layer;
+pub mod instance;
+pub mod light;
 pub mod model;
+pub mod picking;
+pub mod post;
 pub mod resource;
+pub mod scene;
+pub mod shadow;
+pub mod skybox;
 pub mod share;
 pub mod state;
 pub mod swapchain;
 pub mod texture;
+pub mod texture_container;
+pub mod vfs;
 pub mod window;
+pub mod worker;
@@ -0,0 +1,341 @@
+// NOTE: https://sotrh.github.io/learn-wgpu/intermediate/tutorial10-lighting/
+// point, directional and spot lights, packed into one uniform buffer at @group(2)
+
+use bytemuck::Zeroable;
+use cgmath::InnerSpace;
+use wgpu::util::DeviceExt;
+
+// keep in sync with MAX_LIGHTS in assets/shaders/shader.wgsl
+pub const MAX_LIGHTS : usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub enum LightKind {
+    Point,
+    Directional,
+    // cone between the inner (full intensity) and outer (zero) angles
+    Spot {
+        inner : cgmath::Deg<f32>,
+        outer : cgmath::Deg<f32>,
+    },
+}
+
+impl LightKind {
+    // matches the LIGHT_* constants in the shader
+    fn id(&self) -> u32 {
+
+        match self {
+            LightKind::Point => 0,
+            LightKind::Directional => 1,
+            LightKind::Spot { .. } => 2,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+
+pub struct Light {
+    pub kind : LightKind,
+    // ignored by directional lights
+    pub position : cgmath::Point3<f32>,
+    // the way the light travels, ignored by point lights
+    pub direction : cgmath::Vector3<f32>,
+    pub color : [f32; 3],
+    pub intensity : f32,
+    // distance at which point and spot lights fade out
+    pub range : f32,
+}
+
+impl Light {
+    pub fn point(position : cgmath::Point3<f32>, color : [f32; 3], intensity : f32) -> Self {
+
+        Self {
+            kind : LightKind::Point,
+            position,
+            direction : -cgmath::Vector3::unit_y(),
+            color,
+            intensity,
+            range : 20.0,
+        }
+    }
+
+    pub fn directional(
+        direction : cgmath::Vector3<f32>,
+        color : [f32; 3],
+        intensity : f32,
+    ) -> Self {
+
+        Self {
+            kind : LightKind::Directional,
+            position : cgmath::Point3::new(0.0, 0.0, 0.0),
+            direction,
+            color,
+            intensity,
+            range : 0.0,
+        }
+    }
+
+    pub fn spot(
+        position : cgmath::Point3<f32>,
+        direction : cgmath::Vector3<f32>,
+        inner : cgmath::Deg<f32>,
+        outer : cgmath::Deg<f32>,
+        color : [f32; 3],
+        intensity : f32,
+    ) -> Self {
+
+        Self {
+            kind : LightKind::Spot { inner, outer },
+            position,
+            direction,
+            color,
+            intensity,
+            range : 20.0,
+        }
+    }
+
+    pub fn to_raw(&self) -> LightRaw {
+
+        use cgmath::Angle;
+
+        let (inner_cos, outer_cos) = match self.kind {
+            LightKind::Spot { inner, outer } => (inner.cos(), outer.cos()),
+            _ => (0.0, 0.0),
+        };
+
+        LightRaw {
+            position : self.position.into(),
+            kind : self.kind.id(),
+            direction : self.direction.normalize().into(),
+            range : self.range,
+            color : self.color,
+            intensity : self.intensity,
+            inner_cos,
+            outer_cos,
+            _padding : [0; 2],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+
+pub struct LightRaw {
+    position : [f32; 3],
+    kind : u32,
+    direction : [f32; 3],
+    range : f32,
+    color : [f32; 3],
+    intensity : f32,
+    inner_cos : f32,
+    outer_cos : f32,
+    _padding : [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+
+pub struct LightsUniform {
+    ambient : [f32; 3],
+    count : u32,
+    lights : [LightRaw; MAX_LIGHTS],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+
+pub struct LightId(u64);
+
+// NOTE: lights -> uniform -> buffer -> fragment shader
+pub struct Lights {
+    ambient : [f32; 3],
+    lights : Vec<(LightId, Light)>,
+    next_id : u64,
+    dirty : bool,
+    pub buffer : wgpu::Buffer,
+    pub bind_group : wgpu::BindGroup,
+}
+
+impl Lights {
+    // @group(2) @binding(0) lights
+    pub fn bind_group_layout(device : &wgpu::Device) -> wgpu::BindGroupLayout {
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries : &[wgpu::BindGroupLayoutEntry {
+                binding : 0,
+                visibility : wgpu::ShaderStages::FRAGMENT,
+                ty : wgpu::BindingType::Buffer {
+                    ty : wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset : false,
+                    min_binding_size : None,
+                },
+                count : None,
+            }],
+            label : Some("light_bind_group_layout"),
+        })
+    }
+
+    pub fn new(device : &wgpu::Device, layout : &wgpu::BindGroupLayout) -> Self {
+
+        let ambient = [0.1, 0.1, 0.1];
+
+        let uniform = Self::uniform_from(ambient, &[]);
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label : Some("Light Buffer"),
+            contents : bytemuck::cast_slice(&[uniform]),
+            usage : wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries : &[wgpu::BindGroupEntry {
+                binding : 0,
+                resource : buffer.as_entire_binding(),
+            }],
+            label : Some("light_bind_group"),
+        });
+
+        Self {
+            ambient,
+            lights : Vec::new(),
+            next_id : 0,
+            dirty : false,
+            buffer,
+            bind_group,
+        }
+    }
+
+    fn uniform_from(ambient : [f32; 3], lights : &[(LightId, Light)]) -> LightsUniform {
+
+        let mut raw = [LightRaw::zeroed(); MAX_LIGHTS];
+
+        for (slot, (_, light)) in raw.iter_mut().zip(lights) {
+
+            *slot = light.to_raw();
+        }
+
+        LightsUniform {
+            ambient,
+            count : lights.len() as u32,
+            lights : raw,
+        }
+    }
+
+    pub fn uniform(&self) -> LightsUniform { Self::uniform_from(self.ambient, &self.lights) }
+
+    // None once MAX_LIGHTS are in use
+    pub fn add(&mut self, light : Light) -> Option<LightId> {
+
+        if self.lights.len() >= MAX_LIGHTS {
+
+            return None;
+        }
+
+        let id = LightId(self.next_id);
+
+        self.next_id += 1;
+
+        self.lights.push((id, light));
+
+        self.dirty = true;
+
+        Some(id)
+    }
+
+    pub fn remove(&mut self, id : LightId) -> Option<Light> {
+
+        let index = self
+            .lights
+            .iter()
+            .position(|(light_id, _)| *light_id == id)?;
+
+        self.dirty = true;
+
+        Some(self.lights.remove(index).1)
+    }
+
+    pub fn get(&self, id : LightId) -> Option<&Light> {
+
+        self.lights
+            .iter()
+            .find(|(light_id, _)| *light_id == id)
+            .map(|(_, light)| light)
+    }
+
+    // marks the buffer for upload, the caller is expected to change something
+    pub fn get_mut(&mut self, id : LightId) -> Option<&mut Light> {
+
+        let light = self
+            .lights
+            .iter_mut()
+            .find(|(light_id, _)| *light_id == id)
+            .map(|(_, light)| light)?;
+
+        self.dirty = true;
+
+        Some(light)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(LightId, Light)> { self.lights.iter() }
+
+    pub fn len(&self) -> usize { self.lights.len() }
+
+    pub fn is_empty(&self) -> bool { self.lights.is_empty() }
+
+    pub fn ambient(&self) -> [f32; 3] { self.ambient }
+
+    pub fn set_ambient(&mut self, ambient : [f32; 3]) {
+
+        self.ambient = ambient;
+
+        self.dirty = true;
+    }
+
+    // upload only when something changed since the last write
+    pub fn write_buffer(&mut self, queue : &wgpu::Queue) {
+
+        if !self.dirty {
+
+            return;
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform()]));
+
+        self.dirty = false;
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    #[test]
+
+    fn test_uniform_layout() {
+
+        // must match the std140 layout of `Lights` in shader.wgsl
+        assert_eq!(std::mem::size_of::<LightRaw>(), 64);
+
+        assert_eq!(std::mem::size_of::<LightsUniform>(), 16 + 64 * MAX_LIGHTS);
+
+        let spot = Light::spot(
+            (0.0, 2.0, 0.0).into(),
+            (0.0, -2.0, 0.0).into(),
+            cgmath::Deg(0.0),
+            cgmath::Deg(60.0),
+            [1.0, 1.0, 1.0],
+            1.0,
+        )
+        .to_raw();
+
+        assert_eq!(spot.kind, 2);
+
+        assert_eq!(spot.direction, [0.0, -1.0, 0.0]);
+
+        assert_eq!(spot.inner_cos, 1.0);
+
+        assert!((spot.outer_cos - 0.5).abs() < 1e-6);
+    }
+}
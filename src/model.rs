@@ -1,8 +1,12 @@
 // model.rs
 
+use crate::culling::CullTarget;
+use crate::instance::InstanceBuffer;
 use crate::texture;
 use bytemuck;
 use std::ops::Range;
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
 
 pub trait Vertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
@@ -46,24 +50,554 @@ impl ModelVertex {
     }
 }
 
+// NOTE: ModelVertex + tangent space, what the mesh vertex buffers hold
+// [doc] https://sotrh.github.io/learn-wgpu/intermediate/tutorial11-normals/
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+
+pub struct TangentVertex {
+    pub position : [f32; 3],
+    pub tex_coords : [f32; 2],
+    pub normal : [f32; 3],
+    pub tangent : [f32; 3],
+    pub bitangent : [f32; 3],
+}
+
+impl TangentVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+
+        const ATTRIBS : [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+            0 => Float32x3,
+            1 => Float32x2,
+            2 => Float32x3,
+            3 => Float32x3,
+            4 => Float32x3,
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride : std::mem::size_of::<TangentVertex>() as wgpu::BufferAddress,
+            step_mode : wgpu::VertexStepMode::Vertex,
+            attributes : &ATTRIBS,
+        }
+    }
+}
+
+// NOTE: per triangle tangent/bitangent from the uv deltas, averaged over the
+// shared vertices, then orthogonalized against the vertex normal
+pub fn compute_tangents(vertices : &[ModelVertex], indices : &[u32]) -> Vec<TangentVertex> {
+
+    use cgmath::{InnerSpace, Vector2, Vector3};
+
+    let mut tangents = vec![Vector3::new(0.0f32, 0.0, 0.0); vertices.len()];
+
+    let mut bitangents = vec![Vector3::new(0.0f32, 0.0, 0.0); vertices.len()];
+
+    for c in indices.chunks_exact(3) {
+
+        let [v0, v1, v2] = [c[0], c[1], c[2]].map(|i| vertices[i as usize]);
+
+        let pos0 : Vector3<f32> = v0.position.into();
+
+        let pos1 : Vector3<f32> = v1.position.into();
+
+        let pos2 : Vector3<f32> = v2.position.into();
+
+        let uv0 : Vector2<f32> = v0.tex_coords.into();
+
+        let uv1 : Vector2<f32> = v1.tex_coords.into();
+
+        let uv2 : Vector2<f32> = v2.tex_coords.into();
+
+        // Calculate the edges of the triangle
+        let delta_pos1 = pos1 - pos0;
+
+        let delta_pos2 = pos2 - pos0;
+
+        // This will give us a direction to calculate the
+        // tangent and bitangent
+        let delta_uv1 = uv1 - uv0;
+
+        let delta_uv2 = uv2 - uv0;
+
+        let det = delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x;
+
+        // degenerate uvs carry no tangent information
+        if det.abs() < f32::EPSILON {
+
+            continue;
+        }
+
+        let r = 1.0 / det;
+
+        // NOTE: flip the bitangent, wgpu texture coordinates have v pointing down
+        let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
+
+        let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
+
+        for i in c {
+
+            tangents[*i as usize] += tangent;
+
+            bitangents[*i as usize] += bitangent;
+        }
+    }
+
+    vertices
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+
+            let normal = Vector3::from(v.normal);
+
+            // Gram-Schmidt, keep the tangent perpendicular to the normal
+            let mut tangent = tangents[i] - normal * normal.dot(tangents[i]);
+
+            if tangent.magnitude2() < f32::EPSILON {
+
+                // no uv information, any perpendicular direction will do
+                let axis = if normal.x.abs() < 0.9 {
+
+                    Vector3::unit_x()
+                } else {
+
+                    Vector3::unit_y()
+                };
+
+                tangent = normal.cross(axis).cross(normal);
+            }
+
+            let tangent = tangent.normalize();
+
+            let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0.0 {
+
+                -1.0
+            } else {
+
+                1.0
+            };
+
+            let bitangent = normal.cross(tangent) * handedness;
+
+            TangentVertex {
+                position : v.position,
+                tex_coords : v.tex_coords,
+                normal : v.normal,
+                tangent : tangent.into(),
+                bitangent : bitangent.into(),
+            }
+        })
+        .collect()
+}
+
 // model.rs
 pub struct Model {
     pub meshes : Vec<Mesh>,
     pub materials : Vec<Material>,
+    // mesh indices grouped by material, see DrawModel::draw_model
+    pub draw_order : Vec<usize>,
+}
+
+impl Model {
+    // every mesh.material has to index materials, see DecodedObj::new for obj parts without one
+    pub fn new(meshes : Vec<Mesh>, materials : Vec<Material>) -> Self {
+
+        debug_assert!(
+            meshes.iter().all(|mesh| mesh.material < materials.len()),
+            "mesh without a material"
+        );
+
+        let draw_order = material_order(meshes.iter().map(|mesh| mesh.material));
+
+        Self {
+            meshes,
+            materials,
+            draw_order,
+        }
+    }
+
+    // same gpu meshes, new materials, e.g. once the real textures are loaded
+    pub fn with_materials(&self, materials : Vec<Material>) -> Self {
+
+        Self::new(self.meshes.clone(), materials)
+    }
+
+    pub fn mesh(&self, name : &str) -> Option<&Mesh> {
+
+        self.meshes.iter().find(|mesh| mesh.name == name)
+    }
+
+    // union of the mesh bounds, None for a model without meshes
+    pub fn bounds(&self) -> Option<Aabb> {
+
+        self.meshes
+            .iter()
+            .map(|mesh| mesh.bounds)
+            .reduce(|a, b| a.union(&b))
+    }
+}
+
+// stable sort of the mesh indices by material, so each material is bound once
+pub fn material_order(materials : impl IntoIterator<Item = usize>) -> Vec<usize> {
+
+    let mut order = materials.into_iter().enumerate().collect::<Vec<_>>();
+
+    order.sort_by_key(|&(_, material)| material);
+
+    order.into_iter().map(|(mesh, _)| mesh).collect()
 }
 
 pub struct Material {
     pub name : String,
-    pub diffuse_texture : texture::Texture,
+    // NOTE: shared, see AssetServer::load_model
+    pub diffuse_texture : Arc<texture::Texture>,
+    pub normal_texture : Option<Arc<texture::Texture>>,
+    pub specular_texture : Option<Arc<texture::Texture>>,
+    pub emissive_texture : Option<Arc<texture::Texture>>,
+    pub uniform : MaterialUniform,
+    pub uniform_buffer : wgpu::Buffer,
     pub bind_group : wgpu::BindGroup,
 }
 
+// NOTE: Ka Kd Ks Ns Ke d from the mtl file, std140 friendly:
+// every vec3 is followed by a scalar
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+
+pub struct MaterialUniform {
+    pub ambient : [f32; 3],
+    _padding0 : u32,
+    pub diffuse : [f32; 3],
+    _padding1 : u32,
+    pub specular : [f32; 3],
+    pub shininess : f32,
+    pub emissive : [f32; 3],
+    pub opacity : f32,
+    // layer of an array diffuse texture, 0 for a plain one
+    pub layer : u32,
+    _padding2 : [u32; 3],
+}
+
+impl Default for MaterialUniform {
+    fn default() -> Self {
+
+        Self {
+            ambient : [1.0; 3],
+            _padding0 : 0,
+            diffuse : [1.0; 3],
+            _padding1 : 0,
+            specular : [0.5; 3],
+            shininess : 32.0,
+            emissive : [0.0; 3],
+            opacity : 1.0,
+            layer : 0,
+            _padding2 : [0; 3],
+        }
+    }
+}
+
+// optional maps, absent ones are bound to MaterialDefaults
+#[derive(Default)]
+
+pub struct MaterialMaps {
+    pub normal : Option<Arc<texture::Texture>>,
+    pub specular : Option<Arc<texture::Texture>>,
+    pub emissive : Option<Arc<texture::Texture>>,
+}
+
+// NOTE: 1x1 stand-ins for missing maps, shared by every material of a load
+// also the placeholders of background loads, see AssetServer::poll
+pub struct MaterialDefaults {
+    pub white : Arc<texture::Texture>,
+    pub flat_normal : Arc<texture::Texture>,
+    pub black : Arc<texture::Texture>,
+}
+
+impl MaterialDefaults {
+    pub fn new(device : &wgpu::Device, queue : &wgpu::Queue) -> anyhow::Result<Self> {
+
+        let flat_normal = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            image::Rgba([128, 128, 255, 255]),
+        ));
+
+        Ok(Self {
+            white : Arc::new(texture::Texture::from_color(
+                device, queue, [255; 4], "white",
+            )?),
+            // tangent space +z, stored linear
+            flat_normal : Arc::new(texture::Texture::from_image_with_format(
+                device,
+                queue,
+                &flat_normal,
+                Some("flat_normal"),
+                wgpu::TextureFormat::Rgba8Unorm,
+            )?),
+            black : Arc::new(texture::Texture::from_color(
+                device,
+                queue,
+                [0, 0, 0, 255],
+                "black",
+            )?),
+        })
+    }
+}
+
+impl Material {
+    // @group(0)
+    // 0/1 diffuse, 2/3 normal, 4/5 specular, 6/7 emissive, 8 MaterialUniform
+    // NOTE: the diffuse texture is a 2d array indexed by MaterialUniform::layer
+    pub fn bind_group_layout(device : &wgpu::Device) -> wgpu::BindGroupLayout {
+
+        let texture_entries = (0..4).flat_map(|i| {
+
+            let view_dimension = if i == 0 {
+
+                wgpu::TextureViewDimension::D2Array
+            } else {
+
+                wgpu::TextureViewDimension::D2
+            };
+
+            [
+                wgpu::BindGroupLayoutEntry {
+                    binding : i * 2,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Texture {
+                        multisampled : false,
+                        view_dimension,
+                        sample_type : wgpu::TextureSampleType::Float { filterable : true },
+                    },
+                    count : None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding : i * 2 + 1,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count : None,
+                },
+            ]
+        });
+
+        let uniform_entry = wgpu::BindGroupLayoutEntry {
+            binding : 8,
+            visibility : wgpu::ShaderStages::FRAGMENT,
+            ty : wgpu::BindingType::Buffer {
+                ty : wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset : false,
+                min_binding_size : None,
+            },
+            count : None,
+        };
+
+        let entries = texture_entries
+            .chain(std::iter::once(uniform_entry))
+            .collect::<Vec<_>>();
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries : &entries,
+            label : Some("material_bind_group_layout"),
+        })
+    }
+
+    pub fn new(
+        device : &wgpu::Device,
+        layout : &wgpu::BindGroupLayout,
+        name : String,
+        diffuse_texture : impl Into<Arc<texture::Texture>>,
+        maps : MaterialMaps,
+        uniform : MaterialUniform,
+        defaults : &MaterialDefaults,
+    ) -> Self {
+
+        let diffuse_texture = diffuse_texture.into();
+
+        debug_assert!(
+            uniform.layer < diffuse_texture.texture.depth_or_array_layers(),
+            "{:?}: layer {} is past the diffuse texture",
+            name,
+            uniform.layer
+        );
+
+        // a plain texture is bound as an array of its one layer
+        let diffuse_view = (diffuse_texture.view_dimension != wgpu::TextureViewDimension::D2Array)
+            .then(|| {
+
+                diffuse_texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor {
+                        dimension : Some(wgpu::TextureViewDimension::D2Array),
+                        ..Default::default()
+                    })
+            });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label : Some(&format!("{:?} Material Buffer", name)),
+            contents : bytemuck::cast_slice(&[uniform]),
+            usage : wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let textures = [
+            &*diffuse_texture,
+            maps.normal.as_deref().unwrap_or(&defaults.flat_normal),
+            maps.specular.as_deref().unwrap_or(&defaults.white),
+            maps.emissive.as_deref().unwrap_or(&defaults.black),
+        ];
+
+        let mut entries = Vec::new();
+
+        for (i, texture) in textures.iter().enumerate() {
+
+            let view = match (i, &diffuse_view) {
+                (0, Some(view)) => view,
+                _ => &texture.view,
+            };
+
+            entries.push(wgpu::BindGroupEntry {
+                binding : i as u32 * 2,
+                resource : wgpu::BindingResource::TextureView(view),
+            });
+
+            entries.push(wgpu::BindGroupEntry {
+                binding : i as u32 * 2 + 1,
+                resource : wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+        }
+
+        entries.push(wgpu::BindGroupEntry {
+            binding : 8,
+            resource : uniform_buffer.as_entire_binding(),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries : &entries,
+            label : Some(&name),
+        });
+
+        Self {
+            name,
+            diffuse_texture,
+            normal_texture : maps.normal,
+            specular_texture : maps.specular,
+            emissive_texture : maps.emissive,
+            uniform,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    // NOTE: after editing self.uniform at runtime
+    pub fn write_uniform(&self, queue : &wgpu::Queue) {
+
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+}
+
+// NOTE: buffers are shared, clones draw the same gpu data
+#[derive(Clone)]
+
 pub struct Mesh {
     pub name : String,
-    pub vertex_buffer : wgpu::Buffer,
-    pub index_buffer : wgpu::Buffer,
+    pub vertex_buffer : Arc<wgpu::Buffer>,
+    pub index_buffer : Arc<wgpu::Buffer>,
     pub num_elements : u32,
     pub material : usize,
+    // object space bounds of the vertex positions
+    pub bounds : Aabb,
+    // cpu copy of the triangles, for picking
+    pub positions : Vec<[f32; 3]>,
+    pub indices : Vec<u32>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub struct Aabb {
+    pub min : cgmath::Point3<f32>,
+    pub max : cgmath::Point3<f32>,
+}
+
+impl Aabb {
+    // an empty point set gives a zero sized box at the origin
+    pub fn from_points(points : impl IntoIterator<Item = [f32; 3]>) -> Self {
+
+        let mut points = points.into_iter();
+
+        let first = match points.next() {
+            Some(first) => cgmath::Point3::from(first),
+            None => return Self::from_point(cgmath::Point3::new(0.0, 0.0, 0.0)),
+        };
+
+        points.fold(Self::from_point(first), |aabb, p| {
+
+            aabb.union(&Self::from_point(p.into()))
+        })
+    }
+
+    fn from_point(p : cgmath::Point3<f32>) -> Self { Self { min : p, max : p } }
+
+    pub fn union(&self, other : &Self) -> Self {
+
+        Self {
+            min : cgmath::Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max : cgmath::Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn center(&self) -> cgmath::Point3<f32> {
+
+        cgmath::EuclideanSpace::midpoint(self.min, self.max)
+    }
+
+    // sphere around the box, loose but cheap to transform
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+
+        use cgmath::MetricSpace;
+
+        BoundingSphere {
+            center : self.center(),
+            radius : self.center().distance(self.max),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub struct BoundingSphere {
+    pub center : cgmath::Point3<f32>,
+    pub radius : f32,
+}
+
+impl BoundingSphere {
+    // non uniform scale grows the radius by the largest axis
+    pub fn transform(&self, matrix : cgmath::Matrix4<f32>) -> Self {
+
+        use cgmath::{InnerSpace, Transform};
+
+        let scale = matrix
+            .x
+            .truncate()
+            .magnitude()
+            .max(matrix.y.truncate().magnitude())
+            .max(matrix.z.truncate().magnitude());
+
+        Self {
+            center : matrix.transform_point(self.center),
+            radius : self.radius * scale,
+        }
+    }
 }
 
 // model.rs
@@ -107,6 +641,81 @@ pub trait DrawModel<'a> {
         instances : Range<u32>,
         camera_bind_group : &'a wgpu::BindGroup,
     );
+
+    // binds the instance buffer at slot 1 and draws all of its instances
+    fn draw_mesh_instance_buffer(
+        &mut self,
+        mesh : &'a Mesh,
+        material : &'a Material,
+        instances : &'a InstanceBuffer,
+        camera_bind_group : &'a wgpu::BindGroup,
+    );
+
+    // instance count comes from the gpu, the instance buffer must already be bound
+    fn draw_mesh_indirect(
+        &mut self,
+        mesh : &'a Mesh,
+        material : &'a Material,
+        indirect_buffer : &'a wgpu::Buffer,
+        indirect_offset : wgpu::BufferAddress,
+        camera_bind_group : &'a wgpu::BindGroup,
+    );
+
+    // every mesh of the model, in draw_order
+    fn draw_model(&mut self, model : &'a Model, camera_bind_group : &'a wgpu::BindGroup);
+
+    fn draw_model_instanced(
+        &mut self,
+        model : &'a Model,
+        instances : Range<u32>,
+        camera_bind_group : &'a wgpu::BindGroup,
+    );
+
+    fn draw_model_instance_buffer(
+        &mut self,
+        model : &'a Model,
+        instances : &'a InstanceBuffer,
+        camera_bind_group : &'a wgpu::BindGroup,
+    );
+
+    // one indirect draw per mesh, laid out as in CullTarget
+    fn draw_model_indirect(
+        &mut self,
+        model : &'a Model,
+        indirect_buffer : &'a wgpu::Buffer,
+        camera_bind_group : &'a wgpu::BindGroup,
+    );
+}
+
+// NOTE: binds camera once and a material only when it changes, draws go in draw_order
+fn draw_model_meshes<'a>(
+    rpass : &mut wgpu::RenderPass<'a>,
+    model : &'a Model,
+    camera_bind_group : &'a wgpu::BindGroup,
+    mut draw : impl FnMut(&mut wgpu::RenderPass<'a>, usize, &'a Mesh),
+) {
+
+    rpass.set_bind_group(1, camera_bind_group, &[]);
+
+    let mut bound_material = None;
+
+    for &m in &model.draw_order {
+
+        let mesh = &model.meshes[m];
+
+        if bound_material != Some(mesh.material) {
+
+            rpass.set_bind_group(0, &model.materials[mesh.material].bind_group, &[]);
+
+            bound_material = Some(mesh.material);
+        }
+
+        rpass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+
+        rpass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        draw(rpass, m, mesh);
+    }
 }
 
 impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
@@ -147,4 +756,127 @@ where
 
         self.draw_indexed(0..mesh.num_elements, 0, instances);
     }
+
+    fn draw_mesh_instance_buffer(
+        &mut self,
+        mesh : &'b Mesh,
+        material : &'b Material,
+        instances : &'b InstanceBuffer,
+        camera_bind_group : &'b wgpu::BindGroup,
+    ) {
+
+        self.set_vertex_buffer(1, instances.buffer().slice(..));
+
+        self.draw_mesh_instanced(mesh, material, instances.range(), camera_bind_group);
+    }
+
+    fn draw_mesh_indirect(
+        &mut self,
+        mesh : &'b Mesh,
+        material : &'b Material,
+        indirect_buffer : &'b wgpu::Buffer,
+        indirect_offset : wgpu::BufferAddress,
+        camera_bind_group : &'b wgpu::BindGroup,
+    ) {
+
+        self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+
+        self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        self.set_bind_group(0, &material.bind_group, &[]);
+
+        self.set_bind_group(1, camera_bind_group, &[]);
+
+        self.draw_indexed_indirect(indirect_buffer, indirect_offset);
+    }
+
+    fn draw_model(&mut self, model : &'b Model, camera_bind_group : &'b wgpu::BindGroup) {
+
+        self.draw_model_instanced(model, 0..1, camera_bind_group);
+    }
+
+    fn draw_model_instanced(
+        &mut self,
+        model : &'b Model,
+        instances : Range<u32>,
+        camera_bind_group : &'b wgpu::BindGroup,
+    ) {
+
+        draw_model_meshes(self, model, camera_bind_group, |rpass, _, mesh| {
+
+            rpass.draw_indexed(0..mesh.num_elements, 0, instances.clone());
+        });
+    }
+
+    fn draw_model_instance_buffer(
+        &mut self,
+        model : &'b Model,
+        instances : &'b InstanceBuffer,
+        camera_bind_group : &'b wgpu::BindGroup,
+    ) {
+
+        self.set_vertex_buffer(1, instances.buffer().slice(..));
+
+        self.draw_model_instanced(model, instances.range(), camera_bind_group);
+    }
+
+    fn draw_model_indirect(
+        &mut self,
+        model : &'b Model,
+        indirect_buffer : &'b wgpu::Buffer,
+        camera_bind_group : &'b wgpu::BindGroup,
+    ) {
+
+        draw_model_meshes(self, model, camera_bind_group, |rpass, m, _| {
+
+            rpass.draw_indexed_indirect(indirect_buffer, CullTarget::indirect_offset(m));
+        });
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    fn vertex(position : [f32; 3], tex_coords : [f32; 2]) -> ModelVertex {
+
+        ModelVertex {
+            position,
+            tex_coords,
+            normal : [0.0, 0.0, 1.0],
+        }
+    }
+
+    #[test]
+
+    fn test_material_order() {
+
+        assert_eq!(material_order([2, 0, 1, 0, 2]), vec![1, 3, 2, 0, 4]);
+
+        assert!(material_order([]).is_empty());
+    }
+
+    #[test]
+
+    fn test_compute_tangents() {
+
+        // quad in the xy plane, u along +x, v growing downwards (-y)
+        let vertices = [
+            vertex([0.0, 0.0, 0.0], [0.0, 1.0]),
+            vertex([1.0, 0.0, 0.0], [1.0, 1.0]),
+            vertex([1.0, 1.0, 0.0], [1.0, 0.0]),
+            vertex([0.0, 1.0, 0.0], [0.0, 0.0]),
+        ];
+
+        let tangent_vertices = compute_tangents(&vertices, &[0, 1, 2, 2, 3, 0]);
+
+        for v in tangent_vertices {
+
+            assert_eq!(v.tangent, [1.0, 0.0, 0.0]);
+
+            assert_eq!(v.bitangent, [0.0, 1.0, 0.0]);
+        }
+    }
 }
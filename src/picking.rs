@@ -0,0 +1,836 @@
+// NOTE: picking, what is under the cursor
+// cpu: cursor ray -> instance bounding spheres -> mesh bounds -> triangles
+// gpu: id buffer pass, exact per pixel but waits for a readback
+
+use std::num::NonZeroU32;
+
+use cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+
+use crate::asset::Handle;
+use crate::camera::Camera;
+use crate::hot_reload::{self, ShaderWatcher};
+use crate::instance::InstanceBuffer;
+use crate::model::{Aabb, BoundingSphere, Mesh, Model, TangentVertex};
+use crate::share::{InstanceRaw, REVERSED_Z_MATRIX};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub struct Ray {
+    pub origin : Point3<f32>,
+    pub direction : Vector3<f32>,
+}
+
+impl Ray {
+    // NOTE: cursor in physical pixels from the top left, size = surface size
+    // the ray starts on the near plane, None when the camera matrix is degenerate
+    pub fn from_cursor(camera : &Camera, cursor : [f32; 2], size : [u32; 2]) -> Option<Self> {
+
+        let inverse = camera.build_view_projection_matrix().invert()?;
+
+        let x = cursor[0] / size[0] as f32 * 2.0 - 1.0;
+
+        let y = 1.0 - cursor[1] / size[1] as f32 * 2.0;
+
+        // depth 1 is the far plane (or infinity), 0.5 is always a finite point in front
+        let near_depth = if camera.projection.is_reversed() {
+
+            1.0
+        } else {
+
+            0.0
+        };
+
+        let unproject = |z : f32| inverse.transform_point(Point3::new(x, y, z));
+
+        let origin = unproject(near_depth);
+
+        let direction = (unproject(0.5) - origin).normalize();
+
+        Some(Self { origin, direction })
+    }
+
+    pub fn at(&self, t : f32) -> Point3<f32> { self.origin + self.direction * t }
+
+    // the direction is not renormalized, so t stays the same in both spaces
+    pub fn transform(&self, matrix : Matrix4<f32>) -> Self {
+
+        Self {
+            origin : matrix.transform_point(self.origin),
+            direction : matrix.transform_vector(self.direction),
+        }
+    }
+
+    // entry distance, 0 when the origin is inside
+    pub fn intersect_sphere(&self, sphere : &BoundingSphere) -> Option<f32> {
+
+        let oc = self.origin - sphere.center;
+
+        let a = self.direction.magnitude2();
+
+        let b = oc.dot(self.direction);
+
+        let c = oc.magnitude2() - sphere.radius * sphere.radius;
+
+        let discriminant = b * b - a * c;
+
+        if discriminant < 0.0 {
+
+            return None;
+        }
+
+        let sqrt = discriminant.sqrt();
+
+        let (t0, t1) = ((-b - sqrt) / a, (-b + sqrt) / a);
+
+        (t1 >= 0.0).then(|| t0.max(0.0))
+    }
+
+    // slab test, entry distance, 0 when the origin is inside
+    pub fn intersect_aabb(&self, aabb : &Aabb) -> Option<f32> {
+
+        let mut t_min = 0.0f32;
+
+        let mut t_max = f32::INFINITY;
+
+        for axis in 0..3 {
+
+            let inv = 1.0 / self.direction[axis];
+
+            let t0 = (aabb.min[axis] - self.origin[axis]) * inv;
+
+            let t1 = (aabb.max[axis] - self.origin[axis]) * inv;
+
+            // NaN from 0 * inf (origin on a slab with a parallel ray) keeps the old bounds
+            t_min = t_min.max(t0.min(t1));
+
+            t_max = t_max.min(t0.max(t1));
+        }
+
+        (t_min <= t_max).then_some(t_min)
+    }
+
+    // NOTE: Moller-Trumbore, both faces count
+    pub fn intersect_triangle(
+        &self,
+        a : Point3<f32>,
+        b : Point3<f32>,
+        c : Point3<f32>,
+    ) -> Option<f32> {
+
+        let (e1, e2) = (b - a, c - a);
+
+        let p = self.direction.cross(e2);
+
+        let det = e1.dot(p);
+
+        if det.abs() < f32::EPSILON {
+
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+
+        let s = self.origin - a;
+
+        let u = s.dot(p) * inv_det;
+
+        if !(0.0..=1.0).contains(&u) {
+
+            return None;
+        }
+
+        let q = s.cross(e1);
+
+        let v = self.direction.dot(q) * inv_det;
+
+        if v < 0.0 || u + v > 1.0 {
+
+            return None;
+        }
+
+        let t = e2.dot(q) * inv_det;
+
+        (t >= 0.0).then_some(t)
+    }
+
+    // closest triangle of an indexed triangle list
+    pub fn intersect_triangles(&self, positions : &[[f32; 3]], indices : &[u32]) -> Option<f32> {
+
+        indices
+            .chunks_exact(3)
+            .filter_map(|tri| {
+
+                let p = |i : u32| Point3::from(positions[i as usize]);
+
+                self.intersect_triangle(p(tri[0]), p(tri[1]), p(tri[2]))
+            })
+            .min_by(|a, b| a.total_cmp(b))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub struct Hit {
+    // index into State::models
+    pub model : usize,
+    pub mesh : usize,
+    // index into the instance buffer of the model, see Scene::instance_node
+    pub instance : usize,
+    // from the ray origin, in world units
+    pub distance : f32,
+    pub position : Point3<f32>,
+}
+
+// NOTE: closest hit, `instances[i]` are the instances of `models[i]`
+pub fn pick(ray : &Ray, models : &[Handle<Model>], instances : &[&[InstanceRaw]]) -> Option<Hit> {
+
+    let mut best : Option<Hit> = None;
+
+    for (m, (model, instances)) in models.iter().zip(instances).enumerate() {
+
+        // pending models can't be hit yet
+        let model = match model.get() {
+            Some(model) => model,
+            None => continue,
+        };
+
+        let sphere = match model.bounds() {
+            Some(bounds) => bounds.bounding_sphere(),
+            None => continue,
+        };
+
+        for (i, instance) in instances.iter().enumerate() {
+
+            let closest = best.map_or(f32::INFINITY, |hit| hit.distance);
+
+            let matrix = instance.model_matrix();
+
+            match ray.intersect_sphere(&sphere.transform(matrix)) {
+                Some(t) if t < closest => {}
+                _ => continue,
+            }
+
+            // object space ray, cheaper than moving every triangle
+            let local = match matrix.invert() {
+                Some(inverse) => ray.transform(inverse),
+                None => continue,
+            };
+
+            for (k, mesh) in model.meshes.iter().enumerate() {
+
+                let closest = best.map_or(f32::INFINITY, |hit| hit.distance);
+
+                match local.intersect_aabb(&mesh.bounds) {
+                    Some(t) if t < closest => {}
+                    _ => continue,
+                }
+
+                if let Some(t) = local.intersect_triangles(&mesh.positions, &mesh.indices) {
+
+                    if t < closest {
+
+                        best = Some(Hit {
+                            model : m,
+                            mesh : k,
+                            instance : i,
+                            distance : t,
+                            position : ray.at(t),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    best
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+
+struct PickIds {
+    model : u32,
+    mesh : u32,
+}
+
+// NOTE: gpu picking, renders model / mesh / instance ids and world positions
+// into offscreen targets and reads back the pixel under the cursor
+pub struct IdPicker {
+    size : [u32; 2],
+    pipeline : wgpu::RenderPipeline,
+    pipeline_layout : wgpu::PipelineLayout,
+    camera_buffer : wgpu::Buffer,
+    camera_bind_group : wgpu::BindGroup,
+    ids_layout : wgpu::BindGroupLayout,
+    ids_stride : wgpu::BufferAddress,
+    ids_capacity : usize,
+    ids_buffer : wgpu::Buffer,
+    ids_bind_group : wgpu::BindGroup,
+    targets : PickTargets,
+    readback : wgpu::Buffer,
+}
+
+struct PickTargets {
+    id : wgpu::Texture,
+    position : wgpu::Texture,
+    depth : wgpu::TextureView,
+}
+
+impl IdPicker {
+    const ID_FORMAT : wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Uint;
+
+    const POSITION_FORMAT : wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+    const DEPTH_FORMAT : wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    // one row per target, rows must be 256 byte aligned
+    const ROW : wgpu::BufferAddress = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress;
+
+    pub fn new(device : &wgpu::Device, size : [u32; 2]) -> Self {
+
+        let uniform_entry = |has_dynamic_offset| wgpu::BindGroupLayoutEntry {
+            binding : 0,
+            visibility : wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty : wgpu::BindingType::Buffer {
+                ty : wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset,
+                min_binding_size : None,
+            },
+            count : None,
+        };
+
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries : &[uniform_entry(false)],
+            label : Some("picking_camera_bind_group_layout"),
+        });
+
+        let ids_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries : &[uniform_entry(true)],
+            label : Some("picking_ids_bind_group_layout"),
+        });
+
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Picking Camera Buffer"),
+            size : std::mem::size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress,
+            usage : wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        });
+
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout : &camera_layout,
+            entries : &[wgpu::BindGroupEntry {
+                binding : 0,
+                resource : camera_buffer.as_entire_binding(),
+            }],
+            label : Some("picking_camera_bind_group"),
+        });
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+
+        let ids_stride =
+            (std::mem::size_of::<PickIds>() as wgpu::BufferAddress).div_ceil(alignment) * alignment;
+
+        let (ids_buffer, ids_bind_group) = Self::create_ids(device, &ids_layout, ids_stride, 1);
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../assets/shaders/picking.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label : Some("Picking Pipeline Layout"),
+            bind_group_layouts : &[&camera_layout, &ids_layout],
+            push_constant_ranges : &[],
+        });
+
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &shader);
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Picking Readback Buffer"),
+            size : Self::ROW * 2,
+            usage : wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        });
+
+        Self {
+            size,
+            pipeline,
+            pipeline_layout,
+            camera_buffer,
+            camera_bind_group,
+            ids_layout,
+            ids_stride,
+            ids_capacity : 1,
+            ids_buffer,
+            ids_bind_group,
+            targets : Self::create_targets(device, size),
+            readback,
+        }
+    }
+
+    fn create_pipeline(
+        device : &wgpu::Device,
+        layout : &wgpu::PipelineLayout,
+        shader : &wgpu::ShaderModule,
+    ) -> wgpu::RenderPipeline {
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label : Some("Picking Pipeline"),
+            layout : Some(layout),
+            vertex : wgpu::VertexState {
+                module : shader,
+                entry_point : "vs_main",
+                buffers : &[TangentVertex::desc(), InstanceRaw::desc()],
+            },
+            fragment : Some(wgpu::FragmentState {
+                module : shader,
+                entry_point : "fs_main",
+                targets : &[
+                    Some(Self::ID_FORMAT.into()),
+                    Some(Self::POSITION_FORMAT.into()),
+                ],
+            }),
+            primitive : wgpu::PrimitiveState {
+                topology : wgpu::PrimitiveTopology::TriangleList,
+                // NOTE: no culling, the cpu path hits both faces too
+                cull_mode : None,
+                ..Default::default()
+            },
+            depth_stencil : Some(wgpu::DepthStencilState {
+                format : Self::DEPTH_FORMAT,
+                depth_write_enabled : true,
+                depth_compare : wgpu::CompareFunction::Less,
+                stencil : wgpu::StencilState::default(),
+                bias : wgpu::DepthBiasState::default(),
+            }),
+            multisample : wgpu::MultisampleState::default(),
+            multiview : None,
+        })
+    }
+
+    // NOTE: `changed` comes from ShaderWatcher::poll
+    pub fn reload_shaders(
+        &mut self,
+        device : &wgpu::Device,
+        watcher : &mut ShaderWatcher,
+        changed : &[String],
+    ) {
+
+        for name in changed.iter().filter(|name| *name == "picking.wgsl") {
+
+            let reloaded = watcher.read(name).and_then(|source| {
+
+                hot_reload::compile(device, name, &source, |shader| {
+
+                    Self::create_pipeline(device, &self.pipeline_layout, shader)
+                })
+            });
+
+            let result = reloaded.map(|(_, pipeline)| self.pipeline = pipeline);
+
+            watcher.report(name, result);
+        }
+    }
+
+    fn create_ids(
+        device : &wgpu::Device,
+        layout : &wgpu::BindGroupLayout,
+        stride : wgpu::BufferAddress,
+        capacity : usize,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Picking Ids Buffer"),
+            size : stride * capacity as wgpu::BufferAddress,
+            usage : wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries : &[wgpu::BindGroupEntry {
+                binding : 0,
+                resource : wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer : &buffer,
+                    offset : 0,
+                    size : wgpu::BufferSize::new(std::mem::size_of::<PickIds>() as u64),
+                }),
+            }],
+            label : Some("picking_ids_bind_group"),
+        });
+
+        (buffer, bind_group)
+    }
+
+    fn create_targets(device : &wgpu::Device, size : [u32; 2]) -> PickTargets {
+
+        let texture = |label, format, usage| {
+
+            device.create_texture(&wgpu::TextureDescriptor {
+                label : Some(label),
+                size : wgpu::Extent3d {
+                    width : size[0].max(1),
+                    height : size[1].max(1),
+                    depth_or_array_layers : 1,
+                },
+                mip_level_count : 1,
+                sample_count : 1,
+                dimension : wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats : &[],
+            })
+        };
+
+        let target = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC;
+
+        PickTargets {
+            id : texture("Picking Id Texture", Self::ID_FORMAT, target),
+            position : texture("Picking Position Texture", Self::POSITION_FORMAT, target),
+            depth : texture(
+                "Picking Depth Texture",
+                Self::DEPTH_FORMAT,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default()),
+        }
+    }
+
+    // NOTE: follow the surface size, the cursor is in its pixels
+    pub fn resize(&mut self, device : &wgpu::Device, size : [u32; 2]) {
+
+        if self.size != size {
+
+            self.size = size;
+
+            self.targets = Self::create_targets(device, size);
+        }
+    }
+
+    // NOTE: blocks until the pixel is read back, `instances[i]` belong to `models[i]`
+    pub fn pick(
+        &mut self,
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        camera : &Camera,
+        models : &[Handle<Model>],
+        instances : &[InstanceBuffer],
+        cursor : [f32; 2],
+    ) -> anyhow::Result<Option<Hit>> {
+
+        let size = self.size;
+
+        let (x, y) = (cursor[0].floor(), cursor[1].floor());
+
+        if x < 0.0 || y < 0.0 || x >= size[0] as f32 || y >= size[1] as f32 {
+
+            return Ok(None);
+        }
+
+        // the id pass always uses a regular depth buffer, undo a reversed projection
+        let mut view_proj = camera.build_view_projection_matrix();
+
+        if camera.projection.is_reversed() {
+
+            view_proj = REVERSED_Z_MATRIX * view_proj;
+        }
+
+        let view_proj : [[f32; 4]; 4] = view_proj.into();
+
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[view_proj]));
+
+        // NOTE: hold on to the models while their meshes are drawn
+        let models = models.iter().map(Handle::get).collect::<Vec<_>>();
+
+        // one id entry per drawn mesh
+        let draws : Vec<(usize, usize, &Mesh)> = models
+            .iter()
+            .zip(instances)
+            .enumerate()
+            .filter(|(_, (_, instances))| !instances.is_empty())
+            .filter_map(|(m, (model, _))| Some((m, model.as_deref()?)))
+            .flat_map(|(m, model)| {
+
+                model
+                    .meshes
+                    .iter()
+                    .enumerate()
+                    .map(move |(k, mesh)| (m, k, mesh))
+            })
+            .collect();
+
+        if draws.len() > self.ids_capacity {
+
+            self.ids_capacity = draws.len().next_power_of_two();
+
+            (self.ids_buffer, self.ids_bind_group) =
+                Self::create_ids(device, &self.ids_layout, self.ids_stride, self.ids_capacity);
+        }
+
+        for (d, (m, k, _)) in draws.iter().enumerate() {
+
+            let ids = PickIds {
+                model : *m as u32,
+                mesh : *k as u32,
+            };
+
+            queue.write_buffer(
+                &self.ids_buffer,
+                d as wgpu::BufferAddress * self.ids_stride,
+                bytemuck::bytes_of(&ids),
+            );
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label : Some("Picking Encoder"),
+        });
+
+        {
+
+            let id_view = self
+                .targets
+                .id
+                .create_view(&wgpu::TextureViewDescriptor::default());
+
+            let position_view = self
+                .targets
+                .position
+                .create_view(&wgpu::TextureViewDescriptor::default());
+
+            let clear = |view| {
+
+                Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target : None,
+                    ops : wgpu::Operations {
+                        load : wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store : true,
+                    },
+                })
+            };
+
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label : Some("Picking Pass"),
+                color_attachments : &[clear(&id_view), clear(&position_view)],
+                depth_stencil_attachment : Some(wgpu::RenderPassDepthStencilAttachment {
+                    view : &self.targets.depth,
+                    depth_ops : Some(wgpu::Operations {
+                        load : wgpu::LoadOp::Clear(1.0),
+                        store : false,
+                    }),
+                    stencil_ops : None,
+                }),
+            });
+
+            rpass.set_pipeline(&self.pipeline);
+
+            rpass.set_bind_group(0, &self.camera_bind_group, &[]);
+
+            for (d, (m, _, mesh)) in draws.iter().enumerate() {
+
+                let instances = &instances[*m];
+
+                rpass.set_bind_group(
+                    1,
+                    &self.ids_bind_group,
+                    &[(d as wgpu::BufferAddress * self.ids_stride) as u32],
+                );
+
+                rpass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+
+                rpass.set_vertex_buffer(1, instances.buffer().slice(..));
+
+                rpass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+                rpass.draw_indexed(0..mesh.num_elements, 0, instances.range());
+            }
+        }
+
+        for (row, texture) in [&self.targets.id, &self.targets.position]
+            .into_iter()
+            .enumerate()
+        {
+
+            encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level : 0,
+                    origin : wgpu::Origin3d {
+                        x : x as u32,
+                        y : y as u32,
+                        z : 0,
+                    },
+                    aspect : wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer : &self.readback,
+                    layout : wgpu::ImageDataLayout {
+                        offset : row as wgpu::BufferAddress * Self::ROW,
+                        bytes_per_row : NonZeroU32::new(Self::ROW as u32),
+                        rows_per_image : None,
+                    },
+                },
+                wgpu::Extent3d {
+                    width : 1,
+                    height : 1,
+                    depth_or_array_layers : 1,
+                },
+            );
+        }
+
+        let submission_index = queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = self.readback.slice(..);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| {
+
+            sender.send(v).ok();
+        });
+
+        device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission_index));
+
+        receiver.recv()??;
+
+        let (id, position) = {
+
+            let data = buffer_slice.get_mapped_range();
+
+            let id : [u32; 4] = bytemuck::pod_read_unaligned(&data[..16]);
+
+            let position : [f32; 4] =
+                bytemuck::pod_read_unaligned(&data[Self::ROW as usize..Self::ROW as usize + 16]);
+
+            (id, position)
+        };
+
+        self.readback.unmap();
+
+        // model 0 means the background
+        if id[0] == 0 {
+
+            return Ok(None);
+        }
+
+        let position = Point3::new(position[0], position[1], position[2]);
+
+        let distance = Ray::from_cursor(camera, cursor, size)
+            .map_or(0.0, |ray| (position - ray.origin).magnitude());
+
+        Ok(Some(Hit {
+            model : id[0] as usize - 1,
+            mesh : id[1] as usize,
+            instance : id[2] as usize,
+            distance,
+            position,
+        }))
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    use crate::camera::Projection;
+
+    use cgmath::EuclideanSpace;
+
+    fn camera() -> Camera {
+
+        Camera {
+            eye : (0.0, 0.0, 5.0).into(),
+            target : (0.0, 0.0, 0.0).into(),
+            up : Vector3::unit_y(),
+            aspect : 1.0,
+            fovy : 90.0,
+            znear : 0.1,
+            zfar : 100.0,
+            projection : Projection::Perspective,
+        }
+    }
+
+    #[test]
+
+    fn test_cursor_ray() {
+
+        let mut camera = camera();
+
+        for projection in [
+            Projection::Perspective,
+            Projection::ReversedZ,
+            Projection::InfinitePerspective,
+        ] {
+
+            camera.projection = projection;
+
+            let ray = Ray::from_cursor(&camera, [50.0, 50.0], [100, 100]).unwrap();
+
+            assert!((ray.direction - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-4);
+
+            assert!((ray.origin.z - (5.0 - camera.znear)).abs() < 1e-3);
+        }
+
+        // 90 degree fov, the right edge is 45 degrees off the view axis
+        camera.projection = Projection::Perspective;
+
+        let ray = Ray::from_cursor(&camera, [100.0, 50.0], [100, 100]).unwrap();
+
+        assert!((ray.direction.x - ray.direction.z.abs()).abs() < 1e-4);
+    }
+
+    #[test]
+
+    fn test_intersections() {
+
+        let ray = Ray {
+            origin : Point3::new(0.0, 0.0, 5.0),
+            direction : Vector3::new(0.0, 0.0, -1.0),
+        };
+
+        let sphere = BoundingSphere {
+            center : Point3::origin(),
+            radius : 1.0,
+        };
+
+        assert_eq!(ray.intersect_sphere(&sphere), Some(4.0));
+
+        let aabb = Aabb {
+            min : Point3::new(-1.0, -1.0, -1.0),
+            max : Point3::new(1.0, 1.0, 1.0),
+        };
+
+        assert_eq!(ray.intersect_aabb(&aabb), Some(4.0));
+
+        // two triangles of a quad at z = 0 and one behind it
+        let positions = [
+            [-1.0, -1.0, 0.0],
+            [1.0, -1.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [-1.0, 1.0, 0.0],
+            [-1.0, -1.0, -2.0],
+            [1.0, -1.0, -2.0],
+            [0.0, 1.0, -2.0],
+        ];
+
+        let indices = [4, 5, 6, 0, 1, 2, 0, 2, 3];
+
+        assert_eq!(ray.intersect_triangles(&positions, &indices), Some(5.0));
+
+        // moved into the space of an instance scaled by 2, t stays the same
+        let scale = Matrix4::from_scale(2.0);
+
+        let local = ray.transform(scale.invert().unwrap());
+
+        assert_eq!(local.intersect_sphere(&sphere), Some(3.0));
+
+        let miss = Ray {
+            origin : Point3::new(3.0, 0.0, 5.0),
+            ..ray
+        };
+
+        assert!(miss.intersect_triangles(&positions, &indices).is_none());
+
+        assert!(miss.intersect_aabb(&aabb).is_none());
+    }
+}
@@ -0,0 +1,620 @@
+// NOTE: post processing after tone mapping, effects ping-pong between two targets
+// the tone map pass writes the first one, the last enabled effect writes the surface
+
+use crate::hot_reload::{self, ShaderWatcher};
+
+// dynamic uniform offsets must be aligned to this
+const UNIFORM_ALIGN : wgpu::BufferAddress = 256;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+
+struct PostParams {
+    a : [f32; 4],
+    b : [f32; 4],
+    texel_size : [f32; 2],
+    _padding : [f32; 2],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub enum Effect {
+    // bright parts bleed into their neighbourhood, radius in texels
+    Bloom {
+        threshold : f32,
+        intensity : f32,
+        radius : f32,
+    },
+    // edge anti-aliasing, span in texels
+    Fxaa {
+        span_max : f32,
+        reduce_mul : f32,
+        reduce_min : f32,
+    },
+    // darkened corners, radius / softness in units of the half diagonal
+    Vignette {
+        strength : f32,
+        radius : f32,
+        softness : f32,
+    },
+    ColorGrading {
+        contrast : f32,
+        saturation : f32,
+        brightness : f32,
+        tint : [f32; 3],
+    },
+    Grayscale {
+        amount : f32,
+    },
+}
+
+impl Effect {
+    // one of each, in the order they are applied by default
+    pub fn defaults() -> Vec<Effect> {
+
+        vec![
+            Effect::Bloom {
+                threshold : 0.8,
+                intensity : 0.6,
+                radius : 4.0,
+            },
+            Effect::ColorGrading {
+                contrast : 1.0,
+                saturation : 1.0,
+                brightness : 0.0,
+                tint : [1.0, 1.0, 1.0],
+            },
+            Effect::Grayscale { amount : 1.0 },
+            Effect::Vignette {
+                strength : 0.5,
+                radius : 1.0,
+                softness : 0.6,
+            },
+            Effect::Fxaa {
+                span_max : 8.0,
+                reduce_mul : 1.0 / 8.0,
+                reduce_min : 1.0 / 128.0,
+            },
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+
+        match self {
+            Effect::Bloom { .. } => "Bloom",
+            Effect::Fxaa { .. } => "FXAA",
+            Effect::Vignette { .. } => "Vignette",
+            Effect::ColorGrading { .. } => "Color Grading",
+            Effect::Grayscale { .. } => "Grayscale",
+        }
+    }
+
+    // index into the pipelines, same order as ENTRY_POINTS
+    fn index(&self) -> usize {
+
+        match self {
+            Effect::Bloom { .. } => 0,
+            Effect::Fxaa { .. } => 1,
+            Effect::Vignette { .. } => 2,
+            Effect::ColorGrading { .. } => 3,
+            Effect::Grayscale { .. } => 4,
+        }
+    }
+
+    const ENTRY_POINTS : [&'static str; 5] = [
+        "fs_bloom",
+        "fs_fxaa",
+        "fs_vignette",
+        "fs_color_grading",
+        "fs_grayscale",
+    ];
+
+    // NOTE: packed as in post.wgsl, every effect documents its a / b layout there
+    fn params(&self) -> ([f32; 4], [f32; 4]) {
+
+        match *self {
+            Effect::Bloom {
+                threshold,
+                intensity,
+                radius,
+            } => ([threshold, intensity, radius, 0.0], [0.0; 4]),
+            Effect::Fxaa {
+                span_max,
+                reduce_mul,
+                reduce_min,
+            } => ([span_max, reduce_mul, reduce_min, 0.0], [0.0; 4]),
+            Effect::Vignette {
+                strength,
+                radius,
+                softness,
+            } => ([strength, radius, softness, 0.0], [0.0; 4]),
+            Effect::ColorGrading {
+                contrast,
+                saturation,
+                brightness,
+                tint,
+            } => (
+                [contrast, saturation, brightness, 0.0],
+                [tint[0], tint[1], tint[2], 0.0],
+            ),
+            Effect::Grayscale { amount } => ([amount, 0.0, 0.0, 0.0], [0.0; 4]),
+        }
+    }
+
+    // NOTE: imgui widgets for the parameters, returns true when something changed
+    pub fn edit(&mut self, ui : &imgui::Ui) -> bool {
+
+        let id = ui.push_id(self.name());
+
+        let changed = match self {
+            Effect::Bloom {
+                threshold,
+                intensity,
+                radius,
+            } => {
+                ui.slider("Threshold", 0.0, 1.0, threshold)
+                    | ui.slider("Intensity", 0.0, 2.0, intensity)
+                    | ui.slider("Radius", 1.0, 16.0, radius)
+            }
+            Effect::Fxaa { span_max, .. } => ui.slider("Span", 1.0, 16.0, span_max),
+            Effect::Vignette {
+                strength,
+                radius,
+                softness,
+            } => {
+                ui.slider("Strength", 0.0, 1.0, strength)
+                    | ui.slider("Radius", 0.2, 1.5, radius)
+                    | ui.slider("Softness", 0.05, 1.0, softness)
+            }
+            Effect::ColorGrading {
+                contrast,
+                saturation,
+                brightness,
+                tint,
+            } => {
+                ui.slider("Contrast", 0.0, 2.0, contrast)
+                    | ui.slider("Saturation", 0.0, 2.0, saturation)
+                    | ui.slider("Brightness", -0.5, 0.5, brightness)
+                    | ui.color_edit3("Tint", tint)
+            }
+            Effect::Grayscale { amount } => ui.slider("Amount", 0.0, 1.0, amount),
+        };
+
+        id.pop();
+
+        changed
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub struct PostEffect {
+    pub effect : Effect,
+    pub enabled : bool,
+}
+
+struct PingPong {
+    views : [wgpu::TextureView; 2],
+    // bind_groups[i] reads views[i]
+    bind_groups : [wgpu::BindGroup; 2],
+}
+
+pub struct PostStack {
+    // NOTE: applied in order, disabled ones are skipped
+    pub effects : Vec<PostEffect>,
+    size : [u32; 2],
+    format : wgpu::TextureFormat,
+    pipelines : Vec<wgpu::RenderPipeline>,
+    pipeline_layout : wgpu::PipelineLayout,
+    bind_group_layout : wgpu::BindGroupLayout,
+    sampler : wgpu::Sampler,
+    uniform_buffer : wgpu::Buffer,
+    // effects the uniform buffer has room for
+    capacity : usize,
+    targets : PingPong,
+}
+
+impl PostStack {
+    pub fn new(
+        device : &wgpu::Device,
+        width : u32,
+        height : u32,
+        format : wgpu::TextureFormat,
+        effects : Vec<Effect>,
+    ) -> Self {
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries : &[
+                wgpu::BindGroupLayoutEntry {
+                    binding : 0,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Texture {
+                        multisampled : false,
+                        view_dimension : wgpu::TextureViewDimension::D2,
+                        sample_type : wgpu::TextureSampleType::Float { filterable : true },
+                    },
+                    count : None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding : 1,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count : None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding : 2,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Buffer {
+                        ty : wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset : true,
+                        min_binding_size : wgpu::BufferSize::new(
+                            std::mem::size_of::<PostParams>() as wgpu::BufferAddress
+                        ),
+                    },
+                    count : None,
+                },
+            ],
+            label : Some("post_bind_group_layout"),
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label : Some("post_sampler"),
+            address_mode_u : wgpu::AddressMode::ClampToEdge,
+            address_mode_v : wgpu::AddressMode::ClampToEdge,
+            address_mode_w : wgpu::AddressMode::ClampToEdge,
+            mag_filter : wgpu::FilterMode::Linear,
+            min_filter : wgpu::FilterMode::Linear,
+            mipmap_filter : wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../assets/shaders/post.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label : Some("Post Pipeline Layout"),
+            bind_group_layouts : &[&bind_group_layout],
+            push_constant_ranges : &[],
+        });
+
+        let pipelines = Self::create_pipelines(device, &pipeline_layout, &shader, format);
+
+        let capacity = effects.len().max(1);
+
+        let uniform_buffer = Self::create_uniform_buffer(device, capacity);
+
+        let size = [width.max(1), height.max(1)];
+
+        let targets = Self::create_targets(
+            device,
+            size,
+            format,
+            &bind_group_layout,
+            &sampler,
+            &uniform_buffer,
+        );
+
+        Self {
+            effects : effects
+                .into_iter()
+                .map(|effect| PostEffect {
+                    effect,
+                    enabled : false,
+                })
+                .collect(),
+            size,
+            format,
+            pipelines,
+            pipeline_layout,
+            bind_group_layout,
+            sampler,
+            uniform_buffer,
+            capacity,
+            targets,
+        }
+    }
+
+    // one per effect kind, in ENTRY_POINTS order
+    fn create_pipelines(
+        device : &wgpu::Device,
+        layout : &wgpu::PipelineLayout,
+        shader : &wgpu::ShaderModule,
+        format : wgpu::TextureFormat,
+    ) -> Vec<wgpu::RenderPipeline> {
+
+        Effect::ENTRY_POINTS
+            .iter()
+            .map(|entry_point| {
+
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label : Some(entry_point),
+                    layout : Some(layout),
+                    vertex : wgpu::VertexState {
+                        module : shader,
+                        entry_point : "vs_main",
+                        buffers : &[],
+                    },
+                    fragment : Some(wgpu::FragmentState {
+                        module : shader,
+                        entry_point,
+                        targets : &[Some(format.into())],
+                    }),
+                    primitive : wgpu::PrimitiveState::default(),
+                    depth_stencil : None,
+                    multisample : wgpu::MultisampleState::default(),
+                    multiview : None,
+                })
+            })
+            .collect()
+    }
+
+    // NOTE: `changed` comes from ShaderWatcher::poll, every effect is rebuilt or none
+    pub fn reload_shaders(
+        &mut self,
+        device : &wgpu::Device,
+        watcher : &mut ShaderWatcher,
+        changed : &[String],
+    ) {
+
+        for name in changed.iter().filter(|name| *name == "post.wgsl") {
+
+            let reloaded = watcher.read(name).and_then(|source| {
+
+                hot_reload::compile(device, name, &source, |shader| {
+
+                    Self::create_pipelines(device, &self.pipeline_layout, shader, self.format)
+                })
+            });
+
+            let result = reloaded.map(|(_, pipelines)| self.pipelines = pipelines);
+
+            watcher.report(name, result);
+        }
+    }
+
+    fn create_uniform_buffer(device : &wgpu::Device, capacity : usize) -> wgpu::Buffer {
+
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Post Params"),
+            size : capacity as wgpu::BufferAddress * UNIFORM_ALIGN,
+            usage : wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        })
+    }
+
+    fn create_targets(
+        device : &wgpu::Device,
+        size : [u32; 2],
+        format : wgpu::TextureFormat,
+        layout : &wgpu::BindGroupLayout,
+        sampler : &wgpu::Sampler,
+        uniform_buffer : &wgpu::Buffer,
+    ) -> PingPong {
+
+        let view = |label| {
+
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label : Some(label),
+                    size : wgpu::Extent3d {
+                        width : size[0],
+                        height : size[1],
+                        depth_or_array_layers : 1,
+                    },
+                    mip_level_count : 1,
+                    sample_count : 1,
+                    dimension : wgpu::TextureDimension::D2,
+                    format,
+                    usage : wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats : &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+
+        let views = [view("post_ping"), view("post_pong")];
+
+        let bind_group = |view : &wgpu::TextureView| {
+
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries : &[
+                    wgpu::BindGroupEntry {
+                        binding : 0,
+                        resource : wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding : 1,
+                        resource : wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding : 2,
+                        resource : wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer : uniform_buffer,
+                            offset : 0,
+                            size : wgpu::BufferSize::new(
+                                std::mem::size_of::<PostParams>() as wgpu::BufferAddress
+                            ),
+                        }),
+                    },
+                ],
+                label : Some("post_bind_group"),
+            })
+        };
+
+        let bind_groups = [bind_group(&views[0]), bind_group(&views[1])];
+
+        PingPong { views, bind_groups }
+    }
+
+    fn recreate_targets(&mut self, device : &wgpu::Device) {
+
+        self.targets = Self::create_targets(
+            device,
+            self.size,
+            self.format,
+            &self.bind_group_layout,
+            &self.sampler,
+            &self.uniform_buffer,
+        );
+    }
+
+    // NOTE: called from State::resize together with the surface
+    pub fn resize(&mut self, device : &wgpu::Device, width : u32, height : u32) {
+
+        self.size = [width.max(1), height.max(1)];
+
+        self.recreate_targets(device);
+    }
+
+    pub fn push(&mut self, effect : Effect) {
+
+        self.effects.push(PostEffect {
+            effect,
+            enabled : true,
+        });
+    }
+
+    pub fn is_active(&self) -> bool { self.effects.iter().any(|e| e.enabled) }
+
+    // where the scene should be written before `encode`, None when nothing is enabled
+    pub fn input_view(&self) -> Option<&wgpu::TextureView> {
+
+        self.is_active().then_some(&self.targets.views[0])
+    }
+
+    // NOTE: runs every enabled effect, the last one writes into `output`
+    pub fn encode(
+        &mut self,
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        encoder : &mut wgpu::CommandEncoder,
+        output : &wgpu::TextureView,
+    ) {
+
+        let enabled = self
+            .effects
+            .iter()
+            .filter(|e| e.enabled)
+            .map(|e| e.effect)
+            .collect::<Vec<_>>();
+
+        if enabled.len() > self.capacity {
+
+            self.capacity = enabled.len();
+
+            self.uniform_buffer = Self::create_uniform_buffer(device, self.capacity);
+
+            self.recreate_targets(device);
+        }
+
+        let texel_size = [1.0 / self.size[0] as f32, 1.0 / self.size[1] as f32];
+
+        for (i, effect) in enabled.iter().enumerate() {
+
+            let (a, b) = effect.params();
+
+            queue.write_buffer(
+                &self.uniform_buffer,
+                i as wgpu::BufferAddress * UNIFORM_ALIGN,
+                bytemuck::cast_slice(&[PostParams {
+                    a,
+                    b,
+                    texel_size,
+                    _padding : [0.0; 2],
+                }]),
+            );
+        }
+
+        for (i, effect) in enabled.iter().enumerate() {
+
+            let target = if i + 1 == enabled.len() {
+
+                output
+            } else {
+
+                &self.targets.views[(i + 1) % 2]
+            };
+
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label : Some(effect.name()),
+                color_attachments : &[Some(wgpu::RenderPassColorAttachment {
+                    view : target,
+                    resolve_target : None,
+                    ops : wgpu::Operations {
+                        load : wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store : true,
+                    },
+                })],
+                depth_stencil_attachment : None,
+            });
+
+            rpass.set_pipeline(&self.pipelines[effect.index()]);
+
+            rpass.set_bind_group(
+                0,
+                &self.targets.bind_groups[i % 2],
+                &[(i as wgpu::BufferAddress * UNIFORM_ALIGN) as u32],
+            );
+
+            rpass.draw(0..3, 0..1);
+        }
+    }
+
+    // checkbox + parameters per effect, meant to be called inside an imgui window
+    pub fn ui(&mut self, ui : &imgui::Ui) {
+
+        for (i, post) in self.effects.iter_mut().enumerate() {
+
+            let id = ui.push_id_usize(i);
+
+            ui.checkbox(post.effect.name(), &mut post.enabled);
+
+            if post.enabled {
+
+                ui.indent();
+
+                post.effect.edit(ui);
+
+                ui.unindent();
+            }
+
+            id.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    #[test]
+
+    fn test_effect_params() {
+
+        let effects = Effect::defaults();
+
+        // one pipeline per effect kind, every kind in the defaults
+        let mut indices = effects.iter().map(|e| e.index()).collect::<Vec<_>>();
+
+        indices.sort();
+
+        assert_eq!(indices, vec![0, 1, 2, 3, 4]);
+
+        let grading = Effect::ColorGrading {
+            contrast : 1.2,
+            saturation : 0.5,
+            brightness : 0.1,
+            tint : [1.0, 0.9, 0.8],
+        };
+
+        assert_eq!(
+            grading.params(),
+            ([1.2, 0.5, 0.1, 0.0], [1.0, 0.9, 0.8, 0.0])
+        );
+
+        assert!(std::mem::size_of::<PostParams>() as wgpu::BufferAddress <= UNIFORM_ALIGN);
+    }
+}
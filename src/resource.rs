@@ -1,6 +1,13 @@
+use crate::asset::AssetServer;
 use crate::model;
 use crate::texture;
+use crate::texture_container::TextureData;
+use crate::vfs::resolve_relative;
+use cgmath::{InnerSpace, Matrix, SquareMatrix};
 use std::io::{BufReader, Cursor};
+use std::sync::{Arc, OnceLock, RwLock};
+
+pub use crate::vfs::{Mount, Vfs};
 
 use cfg_if::cfg_if;
 
@@ -26,6 +33,14 @@ fn format_url(file_name : &str) -> reqwest::Url {
     base.join(file_name).unwrap()
 }
 
+// NOTE: the res/ mounts of Vfs::with_default_mounts until something else is mounted
+static VFS : OnceLock<RwLock<Vfs>> = OnceLock::new();
+
+pub fn vfs() -> &'static RwLock<Vfs> { VFS.get_or_init(|| RwLock::new(Vfs::with_default_mounts())) }
+
+// e.g. mount("", Mount::archive_file("assets.zip")?)
+pub fn mount(prefix : &str, mount : Mount) { vfs().write().unwrap().mount(prefix, mount) }
+
 pub async fn load_string(file_name : &str) -> anyhow::Result<String> {
 
     cfg_if! {
@@ -36,10 +51,7 @@ pub async fn load_string(file_name : &str) -> anyhow::Result<String> {
                 .text()
                 .await?;
         } else {
-            let path = std::path::Path::new(env!("OUT_DIR"))
-                .join("res")
-                .join(file_name);
-            let txt = std::fs::read_to_string(path)?;
+            let txt = vfs().read().unwrap().read_string(file_name)?;
         }
     }
 
@@ -57,10 +69,7 @@ pub async fn load_binary(file_name : &str) -> anyhow::Result<Vec<u8>> {
                 .await?
                 .to_vec();
         } else {
-            let path = std::path::Path::new(env!("OUT_DIR"))
-                .join("res")
-                .join(file_name);
-            let data = std::fs::read(path)?;
+            let data = vfs().read().unwrap().read(file_name)?;
         }
     }
 
@@ -69,21 +78,265 @@ pub async fn load_binary(file_name : &str) -> anyhow::Result<Vec<u8>> {
 
 pub async fn load_texture(
     file_name : &str,
+    is_normal_map : bool,
     device : &wgpu::Device,
     queue : &wgpu::Queue,
 ) -> anyhow::Result<texture::Texture> {
 
     let data = load_binary(file_name).await?;
 
-    texture::Texture::from_bytes_with_label(device, queue, &data, file_name)
+    let decoded = decode_texture(file_name, &data, is_normal_map)?;
+
+    // NOTE: one-off upload, loaders with many textures share one generator and encoder
+    let mipmaps = texture::MipmapGenerator::new(device);
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label : Some("Mipmap Encoder"),
+    });
+
+    let texture = decoded.upload(&mut texture::TextureUpload {
+        device,
+        queue,
+        encoder : &mut encoder,
+        mipmaps : &mipmaps,
+    });
+
+    queue.submit(Some(encoder.finish()));
+
+    texture
 }
 
-pub async fn load_model(
+// NOTE: cpu half of a texture load, safe to build on a worker thread
+pub enum DecodedTexture {
+    Image {
+        label : String,
+        image : image::DynamicImage,
+        is_normal_map : bool,
+    },
+    Container {
+        label : String,
+        data : TextureData,
+    },
+}
+
+impl DecodedTexture {
+    pub fn upload(&self, upload : &mut texture::TextureUpload) -> anyhow::Result<texture::Texture> {
+
+        match self {
+            DecodedTexture::Image {
+                label,
+                image,
+                is_normal_map,
+            } => model_texture(upload, image, label, *is_normal_map),
+            DecodedTexture::Container { label, data } => {
+                texture::Texture::from_texture_data(upload.device, upload.queue, data, Some(label))
+            }
+        }
+    }
+}
+
+pub fn decode_texture(
     file_name : &str,
-    device : &wgpu::Device,
-    queue : &wgpu::Queue,
-    layout : &wgpu::BindGroupLayout,
-) -> anyhow::Result<model::Model> {
+    data : &[u8],
+    is_normal_map : bool,
+) -> anyhow::Result<DecodedTexture> {
+
+    // NOTE: containers keep their own mips, normal maps stay linear
+    let extension = std::path::Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+
+    let label = file_name.to_string();
+
+    Ok(match extension.as_deref() {
+        Some("dds") => DecodedTexture::Container {
+            label,
+            data : TextureData::from_dds(data)?.with_srgb(!is_normal_map),
+        },
+        Some("ktx2") => DecodedTexture::Container {
+            label,
+            data : TextureData::from_ktx2(data)?.with_srgb(!is_normal_map),
+        },
+        _ => DecodedTexture::Image {
+            label,
+            image : image::load_from_memory(data)?,
+            is_normal_map,
+        },
+    })
+}
+
+// NOTE: material textures get a full mip chain, instanced models are mostly seen from afar
+fn model_texture(
+    upload : &mut texture::TextureUpload,
+    img : &image::DynamicImage,
+    label : &str,
+    is_normal_map : bool,
+) -> anyhow::Result<texture::Texture> {
+
+    let format = if is_normal_map {
+
+        wgpu::TextureFormat::Rgba8Unorm
+    } else {
+
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    };
+
+    texture::Texture::from_image_with_options(
+        upload,
+        img,
+        Some(label),
+        format,
+        texture::TextureOptions::mipmapped(),
+    )
+}
+
+// Ka Kd Ks Ns d are parsed by tobj, Ke ends up in unknown_param
+fn mtl_uniform(m : &tobj::Material) -> model::MaterialUniform {
+
+    let mut uniform = model::MaterialUniform::default();
+
+    uniform.ambient = m.ambient;
+
+    uniform.diffuse = m.diffuse;
+
+    uniform.specular = m.specular;
+
+    uniform.shininess = m.shininess;
+
+    uniform.opacity = m.dissolve;
+
+    if let Some(ke) = m.unknown_param.get("Ke") {
+
+        let values = ke
+            .split_whitespace()
+            .filter_map(|v| v.parse::<f32>().ok())
+            .collect::<Vec<_>>();
+
+        if let [r, g, b] = values[..] {
+
+            uniform.emissive = [r, g, b];
+        }
+    }
+
+    uniform
+}
+
+// one obj part with tangents, ready for upload_mesh
+pub struct ObjMesh {
+    pub name : String,
+    pub vertices : Vec<model::TangentVertex>,
+    pub indices : Vec<u32>,
+    pub material : usize,
+}
+
+pub struct ObjMaterial {
+    pub name : String,
+    pub uniform : model::MaterialUniform,
+    // diffuse, normal, specular, emissive, resolved against the model, None when unset
+    pub maps : [Option<String>; 4],
+}
+
+impl ObjMaterial {
+    // only the normal map is sampled linear
+    pub const IS_NORMAL_MAP : [bool; 4] = [false, true, false, false];
+}
+
+// NOTE: cpu half of load_model, obj/mtl parsing and tangents, no gpu access
+pub struct DecodedObj {
+    pub meshes : Vec<ObjMesh>,
+    pub materials : Vec<ObjMaterial>,
+}
+
+impl DecodedObj {
+    // NOTE: parts without a material (an obj without mtllib, or a usemtl naming nothing) share
+    // one appended default material, white and without maps so MaterialDefaults stand in
+    pub fn new(mut meshes : Vec<ObjMesh>, mut materials : Vec<ObjMaterial>) -> Self {
+
+        let default = materials.len();
+
+        let mut uses_default = false;
+
+        for mesh in meshes.iter_mut().filter(|mesh| mesh.material >= default) {
+
+            mesh.material = default;
+
+            uses_default = true;
+        }
+
+        if uses_default {
+
+            materials.push(ObjMaterial {
+                name : "default".to_string(),
+                uniform : model::MaterialUniform::default(),
+                maps : Default::default(),
+            });
+        }
+
+        Self { meshes, materials }
+    }
+
+    // number of texture loads the materials will start
+    pub fn texture_paths(&self) -> impl Iterator<Item = (&str, bool)> {
+
+        self.materials.iter().flat_map(|m| {
+
+            m.maps
+                .iter()
+                .zip(ObjMaterial::IS_NORMAL_MAP)
+                .filter_map(|(path, is_normal_map)| Some((path.as_deref()?, is_normal_map)))
+        })
+    }
+
+    // `textures[i]` holds the maps of `materials[i]`, None falls back to MaterialDefaults
+    pub fn upload(
+        &self,
+        device : &wgpu::Device,
+        layout : &wgpu::BindGroupLayout,
+        textures : &[[Option<Arc<texture::Texture>>; 4]],
+        defaults : &model::MaterialDefaults,
+    ) -> model::Model {
+
+        let meshes = self.meshes.iter().map(|m| upload_mesh(device, m)).collect();
+
+        model::Model::new(
+            meshes,
+            self.upload_materials(device, layout, textures, defaults),
+        )
+    }
+
+    pub fn upload_materials(
+        &self,
+        device : &wgpu::Device,
+        layout : &wgpu::BindGroupLayout,
+        textures : &[[Option<Arc<texture::Texture>>; 4]],
+        defaults : &model::MaterialDefaults,
+    ) -> Vec<model::Material> {
+
+        self.materials
+            .iter()
+            .zip(textures)
+            .map(|(m, [diffuse, normal, specular, emissive])| {
+
+                model::Material::new(
+                    device,
+                    layout,
+                    m.name.clone(),
+                    diffuse.clone().unwrap_or_else(|| defaults.white.clone()),
+                    model::MaterialMaps {
+                        normal : normal.clone(),
+                        specular : specular.clone(),
+                        emissive : emissive.clone(),
+                    },
+                    m.uniform,
+                    defaults,
+                )
+            })
+            .collect()
+    }
+}
+
+pub async fn decode_obj(file_name : &str) -> anyhow::Result<DecodedObj> {
 
     let obj_text = load_string(file_name).await?;
 
@@ -98,90 +351,956 @@ pub async fn load_model(
             single_index : true,
             ..Default::default()
         },
-        |p| {
+        |p| async move {
 
-            async move {
+            // decoded on a worker thread, a missing mtl fails the load instead of panicking
+            let mat_text = match load_string(&resolve_relative(file_name, &p)).await {
+                Ok(text) => text,
+                Err(e) => {
 
-                let mat_text = load_string(&p).await.unwrap();
+                    log::warn!("{}: {:#}", p, e);
 
-                tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
-            }
+                    return Err(tobj::LoadError::OpenFileFailed);
+                }
+            };
+
+            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
         },
     )
     .await?;
 
-    let mut materials = Vec::new();
-
-    for m in obj_materials? {
+    // NOTE: tobj leaves unset maps as empty strings, set ones are relative to the model
+    let resolve = |path : &str| (!path.is_empty()).then(|| resolve_relative(file_name, path));
 
-        let diffuse_texture = load_texture(&m.diffuse_texture, device, queue).await?;
+    let materials = obj_materials?
+        .into_iter()
+        .map(|m| {
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout,
-            entries : &[
-                wgpu::BindGroupEntry {
-                    binding : 0,
-                    resource : wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding : 1,
-                    resource : wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label : None,
-        });
+            let emissive = m
+                .unknown_param
+                .get("map_Ke")
+                .map(String::as_str)
+                .unwrap_or("");
 
-        materials.push(model::Material {
-            name : m.name,
-            diffuse_texture,
-            bind_group,
+            ObjMaterial {
+                uniform : mtl_uniform(&m),
+                maps : [
+                    resolve(&m.diffuse_texture),
+                    resolve(&m.normal_texture),
+                    resolve(&m.specular_texture),
+                    resolve(emissive),
+                ],
+                name : m.name,
+            }
         })
-    }
+        .collect::<Vec<_>>();
 
     let meshes = models
         .into_iter()
         .map(|m| {
 
             let vertices = (0..m.mesh.positions.len() / 3)
-                .map(|i| {
-
-                    model::ModelVertex {
-                        position : [
-                            m.mesh.positions[i * 3],
-                            m.mesh.positions[i * 3 + 1],
-                            m.mesh.positions[i * 3 + 2],
-                        ],
-                        tex_coords : [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
-                        normal : [
-                            m.mesh.normals[i * 3],
-                            m.mesh.normals[i * 3 + 1],
-                            m.mesh.normals[i * 3 + 2],
-                        ],
-                    }
+                .map(|i| model::ModelVertex {
+                    position : [
+                        m.mesh.positions[i * 3],
+                        m.mesh.positions[i * 3 + 1],
+                        m.mesh.positions[i * 3 + 2],
+                    ],
+                    tex_coords : [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
+                    normal : [
+                        m.mesh.normals[i * 3],
+                        m.mesh.normals[i * 3 + 1],
+                        m.mesh.normals[i * 3 + 2],
+                    ],
                 })
                 .collect::<Vec<_>>();
 
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label : Some(&format!("{:?} Vertex Buffer", file_name)),
-                contents : bytemuck::cast_slice(&vertices),
-                usage : wgpu::BufferUsages::VERTEX,
+            // NOTE: `o`/`g` names from the obj, unnamed parts fall back to the file
+            let name = if m.name.is_empty() || m.name == "unnamed_object" {
+
+                file_name.to_string()
+            } else {
+
+                m.name
+            };
+
+            ObjMesh {
+                name,
+                vertices : model::compute_tangents(&vertices, &m.mesh.indices),
+                indices : m.mesh.indices,
+                material : m.mesh.material_id.unwrap_or(materials.len()),
+            }
+        })
+        .collect();
+
+    Ok(DecodedObj::new(meshes, materials))
+}
+
+pub async fn load_model(
+    file_name : &str,
+    device : &wgpu::Device,
+    queue : &wgpu::Queue,
+    layout : &wgpu::BindGroupLayout,
+) -> anyhow::Result<model::Model> {
+
+    load_model_with(file_name, device, queue, layout, &AssetServer::new()).await
+}
+
+// NOTE: textures come from `assets`, so models loaded through one server share them
+pub async fn load_model_with(
+    file_name : &str,
+    device : &wgpu::Device,
+    queue : &wgpu::Queue,
+    layout : &wgpu::BindGroupLayout,
+    assets : &AssetServer,
+) -> anyhow::Result<model::Model> {
+
+    let obj = decode_obj(file_name).await?;
+
+    let mut textures = Vec::new();
+
+    for m in &obj.materials {
+
+        let mut maps : [Option<Arc<texture::Texture>>; 4] = Default::default();
+
+        for ((map, path), is_normal_map) in
+            maps.iter_mut().zip(&m.maps).zip(ObjMaterial::IS_NORMAL_MAP)
+        {
+
+            if let Some(path) = path {
+
+                let handle = assets
+                    .load_texture(device, queue, path, is_normal_map)
+                    .await;
+
+                *map = Some(handle.loaded_arc()?);
+            }
+        }
+
+        textures.push(maps);
+    }
+
+    let defaults = model::MaterialDefaults::new(device, queue)?;
+
+    Ok(obj.upload(device, layout, &textures, &defaults))
+}
+
+fn create_mesh(
+    device : &wgpu::Device,
+    name : &str,
+    vertices : &[model::ModelVertex],
+    indices : &[u32],
+    material : usize,
+) -> model::Mesh {
+
+    upload_mesh(
+        device,
+        &ObjMesh {
+            name : name.to_string(),
+            vertices : model::compute_tangents(vertices, indices),
+            indices : indices.to_vec(),
+            material,
+        },
+    )
+}
+
+pub fn upload_mesh(device : &wgpu::Device, mesh : &ObjMesh) -> model::Mesh {
+
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label : Some(&format!("{:?} Vertex Buffer", mesh.name)),
+        contents : bytemuck::cast_slice(&mesh.vertices),
+        usage : wgpu::BufferUsages::VERTEX,
+    });
+
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label : Some(&format!("{:?} Index Buffer", mesh.name)),
+        contents : bytemuck::cast_slice(&mesh.indices),
+        usage : wgpu::BufferUsages::INDEX,
+    });
+
+    model::Mesh {
+        name : mesh.name.clone(),
+        vertex_buffer : Arc::new(vertex_buffer),
+        index_buffer : Arc::new(index_buffer),
+        num_elements : mesh.indices.len() as u32,
+        material : mesh.material,
+        bounds : model::Aabb::from_points(mesh.vertices.iter().map(|v| v.position)),
+        positions : mesh.vertices.iter().map(|v| v.position).collect(),
+        indices : mesh.indices.clone(),
+    }
+}
+
+// NOTE: validation errors are returned instead of going to the uncaptured error handler
+pub async fn load_shader(
+    device : &wgpu::Device,
+    file_name : &str,
+) -> anyhow::Result<wgpu::ShaderModule> {
+
+    let source = load_string(file_name).await?;
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label : Some(file_name),
+        source : wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    match device.pop_error_scope().await {
+        Some(error) => Err(anyhow::anyhow!("{}: {}", file_name, error)),
+        None => Ok(module),
+    }
+}
+
+// NOTE: glTF 2.0
+// [doc] https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html
+
+#[derive(Debug)]
+
+pub enum GltfError {
+    MissingBinaryChunk,
+    UnsupportedUri(String),
+    UnsupportedMode {
+        mesh : String,
+        mode : gltf::mesh::Mode,
+    },
+    MissingPositions {
+        mesh : String,
+    },
+    MissingNormals {
+        mesh : String,
+    },
+    MissingTexCoords {
+        mesh : String,
+    },
+    // a bufferView past the end of its buffer, or a short .bin
+    BufferViewOutOfRange {
+        view : usize,
+    },
+    // NORMAL or TEXCOORD_0 with a different count than POSITION
+    AttributeCountMismatch {
+        mesh : String,
+        attribute : &'static str,
+        count : usize,
+        positions : usize,
+    },
+    IndexOutOfRange {
+        mesh : String,
+        index : u32,
+        vertex_count : usize,
+    },
+}
+
+impl std::fmt::Display for GltfError {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+        match self {
+            GltfError::MissingBinaryChunk => write!(f, "glb binary chunk is missing"),
+            GltfError::UnsupportedUri(uri) => write!(f, "unsupported uri: {}", uri),
+            GltfError::UnsupportedMode { mesh, mode } => {
+
+                write!(f, "mesh {:?}: unsupported primitive mode {:?}", mesh, mode)
+            }
+            GltfError::MissingPositions { mesh } => write!(f, "mesh {:?} has no POSITION", mesh),
+            GltfError::MissingNormals { mesh } => write!(f, "mesh {:?} has no NORMAL", mesh),
+            GltfError::MissingTexCoords { mesh } => {
+
+                write!(f, "mesh {:?} has no TEXCOORD_0", mesh)
+            }
+            GltfError::BufferViewOutOfRange { view } => {
+
+                write!(f, "bufferView {} is out of range of its buffer", view)
+            }
+            GltfError::AttributeCountMismatch {
+                mesh,
+                attribute,
+                count,
+                positions,
+            } => {
+
+                write!(
+                    f,
+                    "mesh {:?}: {} has {} entries, POSITION has {}",
+                    mesh, attribute, count, positions
+                )
+            }
+            GltfError::IndexOutOfRange {
+                mesh,
+                index,
+                vertex_count,
+            } => {
+
+                write!(
+                    f,
+                    "mesh {:?}: index {} is out of range of {} vertices",
+                    mesh, index, vertex_count
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+// one glTF primitive, already transformed into model space
+struct GltfPrimitive {
+    name : String,
+    vertices : Vec<model::ModelVertex>,
+    indices : Vec<u32>,
+    material : Option<usize>,
+}
+
+// data:[<mediatype>][;base64],<data>
+fn decode_data_uri(uri : &str) -> Option<anyhow::Result<Vec<u8>>> {
+
+    use base64::Engine;
+
+    let data = uri.strip_prefix("data:")?;
+
+    let decoded = match data.split_once(',') {
+        Some((header, payload)) if header.ends_with(";base64") => {
+            base64::engine::general_purpose::STANDARD
+                .decode(payload)
+                .map_err(anyhow::Error::from)
+        }
+        _ => Err(GltfError::UnsupportedUri(uri.to_string()).into()),
+    };
+
+    Some(decoded)
+}
+
+async fn load_gltf_uri(file_name : &str, uri : &str) -> anyhow::Result<Vec<u8>> {
+
+    match decode_data_uri(uri) {
+        Some(data) => data,
+        None => load_binary(&resolve_relative(file_name, uri)).await,
+    }
+}
+
+async fn load_gltf_buffers(gltf : &gltf::Gltf, file_name : &str) -> anyhow::Result<Vec<Vec<u8>>> {
+
+    let mut buffers = Vec::new();
+
+    for buffer in gltf.buffers() {
+
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => gltf.blob.clone().ok_or(GltfError::MissingBinaryChunk)?,
+            gltf::buffer::Source::Uri(uri) => load_gltf_uri(file_name, uri).await?,
+        };
+
+        buffers.push(data);
+    }
+
+    Ok(buffers)
+}
+
+async fn load_gltf_image(
+    file_name : &str,
+    buffers : &[Vec<u8>],
+    image : gltf::Image<'_>,
+) -> anyhow::Result<image::DynamicImage> {
+
+    let img = match image.source() {
+        gltf::image::Source::View { view, .. } => {
+
+            let start = view.offset();
+
+            let bytes = start
+                .checked_add(view.length())
+                .and_then(|end| buffers.get(view.buffer().index())?.get(start..end))
+                .ok_or(GltfError::BufferViewOutOfRange {
+                    view : view.index(),
+                })?;
+
+            image::load_from_memory(bytes)?
+        }
+        gltf::image::Source::Uri { uri, .. } => {
+            image::load_from_memory(&load_gltf_uri(file_name, uri).await?)?
+        }
+    };
+
+    Ok(img)
+}
+
+// NOTE: walk the scene graph, bake node transforms into the vertices
+fn read_gltf_primitives(
+    document : &gltf::Document,
+    buffers : &[Vec<u8>],
+) -> Result<Vec<GltfPrimitive>, GltfError> {
+
+    let roots = match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => scene.nodes().collect::<Vec<_>>(),
+        None => {
+
+            // no scene, every node nobody claims as a child is a root
+            let children = document
+                .nodes()
+                .flat_map(|node| node.children().map(|child| child.index()))
+                .collect::<std::collections::HashSet<_>>();
+
+            document
+                .nodes()
+                .filter(|node| !children.contains(&node.index()))
+                .collect()
+        }
+    };
+
+    let mut stack = roots
+        .into_iter()
+        .map(|node| (node, cgmath::Matrix4::identity()))
+        .collect::<Vec<_>>();
+
+    let mut primitives = Vec::new();
+
+    while let Some((node, parent)) = stack.pop() {
+
+        let world = parent * cgmath::Matrix4::from(node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+
+            read_gltf_mesh(&mesh, world, buffers, &mut primitives)?;
+        }
+
+        for child in node.children() {
+
+            stack.push((child, world));
+        }
+    }
+
+    Ok(primitives)
+}
+
+fn read_gltf_mesh(
+    mesh : &gltf::Mesh,
+    world : cgmath::Matrix4<f32>,
+    buffers : &[Vec<u8>],
+    primitives : &mut Vec<GltfPrimitive>,
+) -> Result<(), GltfError> {
+
+    let name = mesh
+        .name()
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("mesh{}", mesh.index()));
+
+    let linear =
+        cgmath::Matrix3::from_cols(world.x.truncate(), world.y.truncate(), world.z.truncate());
+
+    // normals need the inverse transpose to survive non uniform scale
+    let normal_matrix = linear.invert().map(|m| m.transpose()).unwrap_or(linear);
+
+    // mirrored transforms flip the triangle winding
+    let flip_winding = linear.determinant() < 0.0;
+
+    for primitive in mesh.primitives() {
+
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+
+            return Err(GltfError::UnsupportedMode {
+                mesh : name,
+                mode : primitive.mode(),
             });
+        }
+
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+
+        let positions = reader
+            .read_positions()
+            .ok_or_else(|| GltfError::MissingPositions {
+                mesh : name.clone(),
+            })?
+            .collect::<Vec<_>>();
+
+        let normals = reader
+            .read_normals()
+            .ok_or_else(|| GltfError::MissingNormals {
+                mesh : name.clone(),
+            })?
+            .collect::<Vec<_>>();
+
+        let tex_coords = reader
+            .read_tex_coords(0)
+            .ok_or_else(|| GltfError::MissingTexCoords {
+                mesh : name.clone(),
+            })?
+            .into_f32()
+            .collect::<Vec<_>>();
+
+        // NOTE: zip would silently drop the extra vertices
+        for (attribute, count) in [("NORMAL", normals.len()), ("TEXCOORD_0", tex_coords.len())] {
+
+            if count != positions.len() {
+
+                return Err(GltfError::AttributeCountMismatch {
+                    mesh : name.clone(),
+                    attribute,
+                    count,
+                    positions : positions.len(),
+                });
+            }
+        }
 
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label : Some(&format!("{:?} Index Buffer", file_name)),
-                contents : bytemuck::cast_slice(&m.mesh.indices),
-                usage : wgpu::BufferUsages::INDEX,
+        let vertices = positions
+            .into_iter()
+            .zip(normals)
+            .zip(tex_coords)
+            .map(|((position, normal), tex_coords)| {
+
+                let position = world * cgmath::Vector3::from(position).extend(1.0);
+
+                let normal = (normal_matrix * cgmath::Vector3::from(normal)).normalize();
+
+                model::ModelVertex {
+                    position : position.truncate().into(),
+                    tex_coords,
+                    normal : normal.into(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+            None => (0..vertices.len() as u32).collect(),
+        };
+
+        // compute_tangents indexes the vertices with these
+        if let Some(&index) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+
+            return Err(GltfError::IndexOutOfRange {
+                mesh : name.clone(),
+                index,
+                vertex_count : vertices.len(),
             });
+        }
+
+        if flip_winding {
+
+            indices
+                .chunks_exact_mut(3)
+                .for_each(|triangle| triangle.swap(1, 2));
+        }
+
+        primitives.push(GltfPrimitive {
+            name : name.clone(),
+            vertices,
+            indices,
+            material : primitive.material().index(),
+        });
+    }
 
-            model::Mesh {
-                name : file_name.to_string(),
-                vertex_buffer,
-                index_buffer,
-                num_elements : m.mesh.indices.len() as u32,
-                material : m.mesh.material_id.unwrap_or(0),
+    Ok(())
+}
+
+// NOTE: .gltf (json + external/embedded buffers) or .glb (binary container)
+// into the same Model/Mesh/Material as load_model, base color textures only.
+// Malformed meshes fail with a GltfError, see anyhow::Error::downcast_ref
+pub async fn load_gltf(
+    file_name : &str,
+    device : &wgpu::Device,
+    queue : &wgpu::Queue,
+    layout : &wgpu::BindGroupLayout,
+) -> anyhow::Result<model::Model> {
+
+    let mipmaps = texture::MipmapGenerator::new(device);
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label : Some("Mipmap Encoder"),
+    });
+
+    let mut upload = texture::TextureUpload {
+        device,
+        queue,
+        encoder : &mut encoder,
+        mipmaps : &mipmaps,
+    };
+
+    let model = load_gltf_with(file_name, layout, &mut upload).await;
+
+    queue.submit(Some(encoder.finish()));
+
+    model
+}
+
+// NOTE: the mip blits of every material texture go into `upload.encoder`
+pub async fn load_gltf_with(
+    file_name : &str,
+    layout : &wgpu::BindGroupLayout,
+    upload : &mut texture::TextureUpload<'_>,
+) -> anyhow::Result<model::Model> {
+
+    let (device, queue) = (upload.device, upload.queue);
+
+    let bytes = load_binary(file_name).await?;
+
+    let gltf = gltf::Gltf::from_slice(&bytes)?;
+
+    let buffers = load_gltf_buffers(&gltf, file_name).await?;
+
+    let primitives = read_gltf_primitives(&gltf.document, &buffers)?;
+
+    let mut materials = Vec::new();
+
+    let defaults = model::MaterialDefaults::new(device, queue)?;
+
+    for material in gltf.materials() {
+
+        let pbr = material.pbr_metallic_roughness();
+
+        let name = material
+            .name()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("material{}", materials.len()));
+
+        let [r, g, b, a] = pbr.base_color_factor();
+
+        let diffuse_texture = match pbr.base_color_texture() {
+            Some(info) => {
+
+                let img = load_gltf_image(file_name, &buffers, info.texture().source()).await?;
+
+                model_texture(upload, &img, &name, false)?
+            }
+            None => texture::Texture::from_color(device, queue, [255; 4], &name)?,
+        };
+
+        let normal = match material.normal_texture() {
+            Some(info) => {
+
+                let img = load_gltf_image(file_name, &buffers, info.texture().source()).await?;
+
+                Some(Arc::new(model_texture(upload, &img, &name, true)?))
+            }
+            None => None,
+        };
+
+        let emissive = match material.emissive_texture() {
+            Some(info) => {
+
+                let img = load_gltf_image(file_name, &buffers, info.texture().source()).await?;
+
+                Some(Arc::new(model_texture(upload, &img, &name, false)?))
             }
+            None => None,
+        };
+
+        // NOTE: metallic-roughness has no specular color, approximate the
+        // highlight size from the roughness
+        let mut uniform = model::MaterialUniform::default();
+
+        uniform.diffuse = [r, g, b];
+
+        uniform.opacity = a;
+
+        uniform.emissive = material.emissive_factor();
+
+        uniform.shininess = (1.0 - pbr.roughness_factor()).max(0.01) * 128.0;
+
+        let maps = model::MaterialMaps {
+            normal,
+            specular : None,
+            emissive,
+        };
+
+        materials.push(model::Material::new(
+            device,
+            layout,
+            name,
+            diffuse_texture,
+            maps,
+            uniform,
+            &defaults,
+        ));
+    }
+
+    // NOTE: primitives without a material use the glTF default, plain white
+    let default_material = materials.len();
+
+    if primitives.iter().any(|p| p.material.is_none()) {
+
+        let diffuse_texture = texture::Texture::from_color(device, queue, [255; 4], "default")?;
+
+        materials.push(model::Material::new(
+            device,
+            layout,
+            "default".to_string(),
+            diffuse_texture,
+            model::MaterialMaps::default(),
+            model::MaterialUniform::default(),
+            &defaults,
+        ));
+    }
+
+    let meshes = primitives
+        .iter()
+        .map(|p| {
+
+            create_mesh(
+                device,
+                &p.name,
+                &p.vertices,
+                &p.indices,
+                p.material.unwrap_or(default_material),
+            )
         })
         .collect::<Vec<_>>();
 
-    Ok(model::Model { meshes, materials })
+    Ok(model::Model::new(meshes, materials))
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    // one triangle, positions / normals / uvs packed in a single embedded buffer
+    // `primitive` is the body of the one primitive, accessors 0-2 are the triangle,
+    // 3 is a NORMAL with only 2 entries, 4 are u16 indices 0 1 5
+    fn triangle_gltf(primitive : &str) -> (gltf::Gltf, Vec<Vec<u8>>) {
+
+        use base64::Engine;
+
+        let positions : [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+
+        let normals : [f32; 9] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+
+        let tex_coords : [f32; 6] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+
+        let mut data = Vec::new();
+
+        data.extend_from_slice(bytemuck::cast_slice(&positions));
+
+        data.extend_from_slice(bytemuck::cast_slice(&normals));
+
+        data.extend_from_slice(bytemuck::cast_slice(&tex_coords));
+
+        data.extend_from_slice(bytemuck::cast_slice(&[0u16, 1, 5, 0]));
+
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(&data)
+        );
+
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": [
+                    {{ "children": [1], "translation": [1.0, 0.0, 0.0] }},
+                    {{ "mesh": 0, "scale": [2.0, 2.0, 2.0] }}
+                ],
+                "meshes": [{{ "name": "tri", "primitives": [{{ {primitive} }}] }}],
+                "buffers": [{{ "byteLength": 104, "uri": "{uri}" }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 72, "byteLength": 24 }},
+                    {{ "buffer": 0, "byteOffset": 96, "byteLength": 6 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                       "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0] }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                    {{ "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC2" }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": 2, "type": "VEC3" }},
+                    {{ "bufferView": 3, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+                ]
+            }}"#
+        );
+
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+
+        let buffers = vec![decode_data_uri(&uri).unwrap().unwrap()];
+
+        (gltf, buffers)
+    }
+
+    #[test]
+
+    fn test_mtl_uniform() {
+
+        let mtl = include_str!("../res/cube.mtl");
+
+        let (materials, _) = tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mtl))).unwrap();
+
+        let uniform = mtl_uniform(&materials[0]);
+
+        assert_eq!(uniform.diffuse, [0.8, 0.8, 0.8]);
+
+        assert_eq!(uniform.specular, [0.5, 0.5, 0.5]);
+
+        assert_eq!(uniform.emissive, [0.0, 0.0, 0.0]);
+
+        assert_eq!(uniform.opacity, 1.0);
+
+        assert_eq!(materials[0].normal_texture, "cube-normal.png");
+    }
+
+    fn obj_mesh(material : usize) -> ObjMesh {
+
+        ObjMesh {
+            name : format!("mesh{}", material),
+            vertices : Vec::new(),
+            indices : Vec::new(),
+            material,
+        }
+    }
+
+    fn obj_material(name : &str) -> ObjMaterial {
+
+        ObjMaterial {
+            name : name.to_string(),
+            uniform : model::MaterialUniform::default(),
+            maps : [Some("diffuse.png".to_string()), None, None, None],
+        }
+    }
+
+    #[test]
+
+    fn test_obj_default_material() {
+
+        // no mtllib, decode_obj points every part past the empty material list
+        let obj = DecodedObj::new(vec![obj_mesh(0), obj_mesh(0)], Vec::new());
+
+        assert_eq!(obj.materials.len(), 1);
+
+        assert_eq!(obj.materials[0].name, "default");
+
+        assert!(obj.materials[0].maps.iter().all(Option::is_none));
+
+        assert!(obj.meshes.iter().all(|mesh| mesh.material == 0));
+
+        assert_eq!(obj.texture_paths().count(), 0);
+
+        // a usemtl naming nothing shares the default, known materials are kept
+        let obj = DecodedObj::new(
+            vec![obj_mesh(1), obj_mesh(5), obj_mesh(2)],
+            vec![obj_material("a"), obj_material("b")],
+        );
+
+        assert_eq!(
+            obj.meshes
+                .iter()
+                .map(|mesh| mesh.material)
+                .collect::<Vec<_>>(),
+            [1, 2, 2]
+        );
+
+        assert_eq!(obj.materials[2].name, "default");
+
+        // every material is used, nothing is appended
+        let obj = DecodedObj::new(vec![obj_mesh(0)], vec![obj_material("a")]);
+
+        assert_eq!(obj.materials.len(), 1);
+    }
+
+    #[test]
+
+    fn test_gltf_node_transforms() {
+
+        let (gltf, buffers) =
+            triangle_gltf(r#""attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 }"#);
+
+        let primitives = read_gltf_primitives(&gltf.document, &buffers).unwrap();
+
+        assert_eq!(primitives.len(), 1);
+
+        assert_eq!(primitives[0].name, "tri");
+
+        assert_eq!(primitives[0].indices, vec![0, 1, 2]);
+
+        // child scale 2, then parent translation 1 along x
+        assert_eq!(primitives[0].vertices[1].position, [3.0, 0.0, 0.0]);
+
+        assert_eq!(primitives[0].vertices[2].position, [1.0, 2.0, 0.0]);
+
+        assert_eq!(primitives[0].vertices[0].normal, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+
+    fn test_gltf_missing_attributes() {
+
+        let (gltf, buffers) = triangle_gltf(r#""attributes": { "POSITION": 0, "TEXCOORD_0": 2 }"#);
+
+        let result = read_gltf_primitives(&gltf.document, &buffers);
+
+        assert!(matches!(result, Err(GltfError::MissingNormals { .. })));
+
+        let (gltf, buffers) = triangle_gltf(r#""attributes": { "POSITION": 0, "NORMAL": 1 }"#);
+
+        let result = read_gltf_primitives(&gltf.document, &buffers);
+
+        assert!(matches!(result, Err(GltfError::MissingTexCoords { .. })));
+    }
+
+    #[test]
+
+    fn test_gltf_attribute_count_mismatch() {
+
+        let (gltf, buffers) =
+            triangle_gltf(r#""attributes": { "POSITION": 0, "NORMAL": 3, "TEXCOORD_0": 2 }"#);
+
+        let result = read_gltf_primitives(&gltf.document, &buffers);
+
+        assert!(matches!(
+            result,
+            Err(GltfError::AttributeCountMismatch {
+                attribute : "NORMAL",
+                count : 2,
+                positions : 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+
+    fn test_gltf_index_out_of_range() {
+
+        let (gltf, buffers) = triangle_gltf(
+            r#""attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 }, "indices": 4"#,
+        );
+
+        let result = read_gltf_primitives(&gltf.document, &buffers);
+
+        assert!(matches!(
+            result,
+            Err(GltfError::IndexOutOfRange {
+                index : 5,
+                vertex_count : 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+
+    fn test_gltf_image_out_of_range() {
+
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": 64 }],
+            "bufferViews": [{ "buffer": 0, "byteOffset": 16, "byteLength": 48 }],
+            "images": [{ "bufferView": 0, "mimeType": "image/png" }]
+        }"#;
+
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+
+        let image = gltf.document.images().next().unwrap();
+
+        // the .bin is shorter than the buffer claims
+        let buffers = vec![vec![0u8; 32]];
+
+        let result = pollster::block_on(load_gltf_image("short.gltf", &buffers, image));
+
+        let error = result.err().unwrap();
+
+        assert!(matches!(
+            error.downcast_ref::<GltfError>(),
+            Some(GltfError::BufferViewOutOfRange { view : 0 })
+        ));
+    }
 }
@@ -0,0 +1,458 @@
+// NOTE: scene graph, nodes with a local transform, a parent and children
+// world matrices are recomputed from the roots whenever something changed
+
+use cgmath::{Matrix4, One, Quaternion, Vector3};
+
+use crate::share::InstanceRaw;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+
+pub struct Transform {
+    pub translation : Vector3<f32>,
+    pub rotation : Quaternion<f32>,
+    pub scale : Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+
+        Self {
+            translation : Vector3::new(0.0, 0.0, 0.0),
+            rotation : Quaternion::one(),
+            scale : Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation : Vector3<f32>) -> Self {
+
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_translation_rotation(
+        translation : Vector3<f32>,
+        rotation : Quaternion<f32>,
+    ) -> Self {
+
+        Self {
+            translation,
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    // T * R * S
+    pub fn to_matrix(&self) -> Matrix4<f32> {
+
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+// slot index + generation, ids of removed nodes never resolve again
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+
+pub struct NodeId {
+    index : u32,
+    generation : u32,
+}
+
+pub struct Node {
+    pub name : String,
+    pub transform : Transform,
+    // index into State::models, every mesh of the model is drawn at this node
+    pub model : Option<usize>,
+    // per instance tint, multiplies the material diffuse color
+    pub color : [f32; 4],
+    pub visible : bool,
+    parent : Option<NodeId>,
+    children : Vec<NodeId>,
+    world : Matrix4<f32>,
+}
+
+impl Node {
+    pub fn parent(&self) -> Option<NodeId> { self.parent }
+
+    pub fn children(&self) -> &[NodeId] { &self.children }
+
+    // valid after Scene::update
+    pub fn world_matrix(&self) -> Matrix4<f32> { self.world }
+}
+
+struct Slot {
+    generation : u32,
+    node : Option<Node>,
+}
+
+#[derive(Default)]
+
+pub struct Scene {
+    slots : Vec<Slot>,
+    free : Vec<u32>,
+    roots : Vec<NodeId>,
+    dirty : bool,
+}
+
+impl Scene {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn add_node(
+        &mut self,
+        name : &str,
+        parent : Option<NodeId>,
+        transform : Transform,
+        model : Option<usize>,
+    ) -> NodeId {
+
+        // a stale parent makes the node a root
+        let parent = parent.filter(|parent| self.node(*parent).is_some());
+
+        let node = Node {
+            name : name.to_string(),
+            transform,
+            model,
+            color : [1.0; 4],
+            visible : true,
+            parent,
+            children : Vec::new(),
+            world : Matrix4::one(),
+        };
+
+        let id = match self.free.pop() {
+            Some(index) => {
+
+                let slot = &mut self.slots[index as usize];
+
+                slot.node = Some(node);
+
+                NodeId {
+                    index,
+                    generation : slot.generation,
+                }
+            }
+            None => {
+
+                self.slots.push(Slot {
+                    generation : 0,
+                    node : Some(node),
+                });
+
+                NodeId {
+                    index : self.slots.len() as u32 - 1,
+                    generation : 0,
+                }
+            }
+        };
+
+        match parent {
+            Some(parent) => self.node_entry(parent).unwrap().children.push(id),
+            None => self.roots.push(id),
+        }
+
+        self.dirty = true;
+
+        id
+    }
+
+    // removes the node and its whole subtree
+    pub fn remove_node(&mut self, id : NodeId) -> bool {
+
+        let parent = match self.node(id) {
+            Some(node) => node.parent,
+            None => return false,
+        };
+
+        self.detach(id, parent);
+
+        let mut stack = vec![id];
+
+        while let Some(id) = stack.pop() {
+
+            let slot = &mut self.slots[id.index as usize];
+
+            if let Some(node) = slot.node.take() {
+
+                stack.extend(node.children);
+
+                slot.generation += 1;
+
+                self.free.push(id.index);
+            }
+        }
+
+        self.dirty = true;
+
+        true
+    }
+
+    // None makes the node a root, fails if the parent is a descendant
+    pub fn set_parent(&mut self, id : NodeId, parent : Option<NodeId>) -> bool {
+
+        if self.node(id).is_none() {
+
+            return false;
+        }
+
+        if let Some(parent) = parent {
+
+            if self.node(parent).is_none() || self.is_ancestor(id, parent) {
+
+                return false;
+            }
+        }
+
+        let old_parent = self.node(id).unwrap().parent;
+
+        self.detach(id, old_parent);
+
+        self.node_entry(id).unwrap().parent = parent;
+
+        match parent {
+            Some(parent) => self.node_entry(parent).unwrap().children.push(id),
+            None => self.roots.push(id),
+        }
+
+        self.dirty = true;
+
+        true
+    }
+
+    fn detach(&mut self, id : NodeId, parent : Option<NodeId>) {
+
+        match parent.and_then(|parent| self.node_entry(parent)) {
+            Some(parent) => parent.children.retain(|child| *child != id),
+            None => self.roots.retain(|root| *root != id),
+        }
+    }
+
+    // true when `ancestor` is `id` or one of its parents
+    fn is_ancestor(&self, ancestor : NodeId, id : NodeId) -> bool {
+
+        let mut current = Some(id);
+
+        while let Some(node_id) = current {
+
+            if node_id == ancestor {
+
+                return true;
+            }
+
+            current = self.node(node_id).and_then(|node| node.parent);
+        }
+
+        false
+    }
+
+    fn node_entry(&mut self, id : NodeId) -> Option<&mut Node> {
+
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_mut())
+    }
+
+    pub fn node(&self, id : NodeId) -> Option<&Node> {
+
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
+
+    // marks the scene dirty, world matrices are refreshed on the next update
+    pub fn node_mut(&mut self, id : NodeId) -> Option<&mut Node> {
+
+        self.node(id)?;
+
+        self.dirty = true;
+
+        self.node_entry(id)
+    }
+
+    pub fn transform_mut(&mut self, id : NodeId) -> Option<&mut Transform> {
+
+        self.node_mut(id).map(|node| &mut node.transform)
+    }
+
+    pub fn roots(&self) -> &[NodeId] { &self.roots }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+
+            slot.node.as_ref().map(|node| {
+
+                (
+                    NodeId {
+                        index : index as u32,
+                        generation : slot.generation,
+                    },
+                    node,
+                )
+            })
+        })
+    }
+
+    pub fn len(&self) -> usize { self.slots.len() - self.free.len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn is_dirty(&self) -> bool { self.dirty }
+
+    // NOTE: parent world * local, depth first from the roots
+    // returns true when the world matrices changed
+    pub fn update(&mut self) -> bool {
+
+        if !self.dirty {
+
+            return false;
+        }
+
+        let mut stack : Vec<(NodeId, Matrix4<f32>)> = self
+            .roots
+            .iter()
+            .map(|root| (*root, Matrix4::one()))
+            .collect();
+
+        while let Some((id, parent_world)) = stack.pop() {
+
+            if let Some(node) = self.node_entry(id) {
+
+                node.world = parent_world * node.transform.to_matrix();
+
+                let world = node.world;
+
+                stack.extend(node.children.iter().map(|child| (*child, world)));
+            }
+        }
+
+        self.dirty = false;
+
+        true
+    }
+
+    // NOTE: per model instance data of every visible node, index = model index
+    pub fn instances(&self, model_count : usize) -> Vec<Vec<InstanceRaw>> {
+
+        let mut instances = vec![Vec::new(); model_count];
+
+        for (_, node) in self.iter() {
+
+            if let Some(model) = node.model.filter(|model| *model < model_count) {
+
+                if node.visible {
+
+                    instances[model]
+                        .push(InstanceRaw::from_matrix(node.world).with_color(node.color));
+                }
+            }
+        }
+
+        instances
+    }
+
+    // inverse of instances(), the node drawn as instance `index` of `model`
+    pub fn instance_node(&self, model : usize, index : usize) -> Option<NodeId> {
+
+        self.iter()
+            .filter(|(_, node)| node.visible && node.model == Some(model))
+            .nth(index)
+            .map(|(id, _)| id)
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    use cgmath::{InnerSpace, Rotation3, SquareMatrix};
+
+    fn origin(m : Matrix4<f32>) -> Vector3<f32> { m.w.truncate() }
+
+    #[test]
+
+    fn test_world_transforms() {
+
+        let mut scene = Scene::new();
+
+        let parent = scene.add_node(
+            "parent",
+            None,
+            Transform::from_translation_rotation(
+                Vector3::new(1.0, 0.0, 0.0),
+                Quaternion::from_angle_y(cgmath::Deg(90.0)),
+            ),
+            None,
+        );
+
+        let child = scene.add_node(
+            "child",
+            Some(parent),
+            Transform::from_translation(Vector3::new(0.0, 0.0, 1.0)),
+            Some(0),
+        );
+
+        assert!(scene.update());
+
+        assert!(!scene.update());
+
+        // +z rotated 90 degrees around y is +x
+        let p = origin(scene.node(child).unwrap().world_matrix());
+
+        assert!((p - Vector3::new(2.0, 0.0, 0.0)).magnitude2() < 1e-10);
+
+        scene.transform_mut(parent).unwrap().translation = Vector3::new(0.0, 0.0, 0.0);
+
+        assert!(scene.update());
+
+        let p = origin(scene.node(child).unwrap().world_matrix());
+
+        assert!((p - Vector3::new(1.0, 0.0, 0.0)).magnitude2() < 1e-10);
+
+        assert_eq!(scene.instances(1)[0].len(), 1);
+
+        assert_eq!(scene.instance_node(0, 0), Some(child));
+
+        assert!(scene.node(child).unwrap().world_matrix().invert().is_some());
+    }
+
+    #[test]
+
+    fn test_remove_and_reparent() {
+
+        let mut scene = Scene::new();
+
+        let a = scene.add_node("a", None, Transform::default(), None);
+
+        let b = scene.add_node("b", Some(a), Transform::default(), Some(0));
+
+        let c = scene.add_node("c", Some(b), Transform::default(), Some(0));
+
+        // no cycles
+        assert!(!scene.set_parent(a, Some(c)));
+
+        assert!(scene.set_parent(c, None));
+
+        assert_eq!(scene.roots(), &[a, c]);
+
+        assert!(scene.remove_node(a));
+
+        assert!(scene.node(b).is_none());
+
+        assert_eq!(scene.len(), 1);
+
+        // the freed slot is reused with a new generation
+        let d = scene.add_node("d", None, Transform::default(), None);
+
+        assert!(scene.node(a).is_none() && scene.node(b).is_none());
+
+        assert_eq!(scene.node(d).unwrap().name, "d");
+    }
+}
@@ -0,0 +1,568 @@
+// NOTE: shadow mapping, promoted from example/shadow
+// one depth layer per shadowed light, sampled with hardware pcf at @group(3)
+
+use std::ops::Range;
+
+use cgmath::InnerSpace;
+
+use crate::hot_reload::{self, ShaderWatcher};
+use crate::light::{Light, LightKind, Lights};
+use crate::model::{Mesh, Model, TangentVertex};
+use crate::share::{InstanceRaw, OPENGL_TO_WGPU_MATRIX};
+
+// keep in sync with MAX_SHADOWS in assets/shaders/shader.wgsl
+pub const MAX_SHADOWS : usize = 4;
+
+#[derive(Debug, Copy, Clone)]
+
+pub struct ShadowConfig {
+    // width and height of every shadow map
+    pub resolution : u32,
+    // the first `light_count` lights of `Lights` cast shadows
+    pub light_count : u32,
+    // 0 = single hardware 2x2 lookup, n = (2n + 1)^2 lookups
+    pub pcf_radius : u32,
+    // area covered by directional lights, point lights look at the center
+    pub scene_center : cgmath::Point3<f32>,
+    pub scene_radius : f32,
+    pub znear : f32,
+    pub zfar : f32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+
+        Self {
+            resolution : 1024,
+            light_count : 2,
+            pcf_radius : 1,
+            scene_center : cgmath::Point3::new(0.0, 0.0, 0.0),
+            scene_radius : 20.0,
+            znear : 0.1,
+            zfar : 50.0,
+        }
+    }
+}
+
+impl ShadowConfig {
+    // light space view projection, already in wgpu clip space
+    pub fn light_view_proj(&self, light : &Light) -> cgmath::Matrix4<f32> {
+
+        let direction = match light.kind {
+            LightKind::Point => self.scene_center - light.position,
+            _ => light.direction,
+        }
+        .normalize();
+
+        // look_at breaks down when up and direction are parallel
+        let up = if direction.y.abs() > 0.99 {
+
+            cgmath::Vector3::unit_z()
+        } else {
+
+            cgmath::Vector3::unit_y()
+        };
+
+        let (view, proj) = match light.kind {
+            LightKind::Directional => {
+
+                let r = self.scene_radius;
+
+                let eye = self.scene_center - direction * (self.zfar * 0.5);
+
+                (
+                    cgmath::Matrix4::look_at_rh(eye, self.scene_center, up),
+                    cgmath::ortho(-r, r, -r, r, self.znear, self.zfar),
+                )
+            }
+            LightKind::Spot { outer, .. } => (
+                cgmath::Matrix4::look_at_rh(light.position, light.position + direction, up),
+                cgmath::perspective(outer * 2.0, 1.0, self.znear, light.range),
+            ),
+            LightKind::Point => (
+                cgmath::Matrix4::look_at_rh(light.position, self.scene_center, up),
+                cgmath::perspective(cgmath::Deg(90.0), 1.0, self.znear, light.range),
+            ),
+        };
+
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+
+pub struct ShadowUniform {
+    view_proj : [[[f32; 4]; 4]; MAX_SHADOWS],
+    count : u32,
+    pcf_radius : u32,
+    texel_size : f32,
+    _padding : u32,
+}
+
+// NOTE: everything drawn into the shadow maps, any model + its instances
+pub struct ShadowCaster<'a> {
+    pub model : &'a Model,
+    pub instance_buffer : &'a wgpu::Buffer,
+    pub instances : Range<u32>,
+}
+
+pub struct ShadowMaps {
+    pub config : ShadowConfig,
+    pub bind_group_layout : wgpu::BindGroupLayout,
+    pub bind_group : wgpu::BindGroup,
+    texture : wgpu::Texture,
+    target_views : Vec<wgpu::TextureView>,
+    uniform : ShadowUniform,
+    uniform_buffer : wgpu::Buffer,
+    bake_buffer : wgpu::Buffer,
+    bake_bind_group : wgpu::BindGroup,
+    bake_pipeline : wgpu::RenderPipeline,
+    bake_pipeline_layout : wgpu::PipelineLayout,
+}
+
+impl ShadowMaps {
+    pub const FORMAT : wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    // @group(3) shadows, binding 0 uniform, 1 depth array, 2 comparison sampler
+    pub fn bind_group_layout(device : &wgpu::Device) -> wgpu::BindGroupLayout {
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries : &[
+                wgpu::BindGroupLayoutEntry {
+                    binding : 0,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Buffer {
+                        ty : wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset : false,
+                        min_binding_size : None,
+                    },
+                    count : None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding : 1,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Texture {
+                        multisampled : false,
+                        sample_type : wgpu::TextureSampleType::Depth,
+                        view_dimension : wgpu::TextureViewDimension::D2Array,
+                    },
+                    count : None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding : 2,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count : None,
+                },
+            ],
+            label : Some("shadow_bind_group_layout"),
+        })
+    }
+
+    pub fn new(device : &wgpu::Device, config : ShadowConfig) -> Self {
+
+        let config = Self::clamp_config(config);
+
+        let uniform = ShadowUniform {
+            view_proj : [[[0.0; 4]; 4]; MAX_SHADOWS],
+            count : 0,
+            pcf_radius : config.pcf_radius,
+            texel_size : 1.0 / config.resolution as f32,
+            _padding : 0,
+        };
+
+        use wgpu::util::DeviceExt;
+
+        // NOTE: COPY_SRC, every light matrix is copied into the bake buffer
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label : Some("Shadow Buffer"),
+            contents : bytemuck::cast_slice(&[uniform]),
+            usage : wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = Self::bind_group_layout(device);
+
+        let (texture, target_views, bind_group) =
+            Self::create_maps(device, &bind_group_layout, &uniform_buffer, &config);
+
+        // NOTE: bake pass, depth only, one light matrix at a time
+        let bake_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Shadow Bake Buffer"),
+            size : std::mem::size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress,
+            usage : wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        });
+
+        let bake_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries : &[wgpu::BindGroupLayoutEntry {
+                    binding : 0,
+                    visibility : wgpu::ShaderStages::VERTEX,
+                    ty : wgpu::BindingType::Buffer {
+                        ty : wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset : false,
+                        min_binding_size : None,
+                    },
+                    count : None,
+                }],
+                label : Some("shadow_bake_bind_group_layout"),
+            });
+
+        let bake_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout : &bake_bind_group_layout,
+            entries : &[wgpu::BindGroupEntry {
+                binding : 0,
+                resource : bake_buffer.as_entire_binding(),
+            }],
+            label : Some("shadow_bake_bind_group"),
+        });
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../assets/shaders/shadow.wgsl"));
+
+        let bake_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label : Some("Shadow Bake Pipeline Layout"),
+            bind_group_layouts : &[&bake_bind_group_layout],
+            push_constant_ranges : &[],
+        });
+
+        let bake_pipeline = Self::create_bake_pipeline(device, &bake_pipeline_layout, &shader);
+
+        Self {
+            config,
+            bind_group_layout,
+            bind_group,
+            texture,
+            target_views,
+            uniform,
+            uniform_buffer,
+            bake_buffer,
+            bake_bind_group,
+            bake_pipeline,
+            bake_pipeline_layout,
+        }
+    }
+
+    fn create_bake_pipeline(
+        device : &wgpu::Device,
+        layout : &wgpu::PipelineLayout,
+        shader : &wgpu::ShaderModule,
+    ) -> wgpu::RenderPipeline {
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label : Some("Shadow Bake Pipeline"),
+            layout : Some(layout),
+            vertex : wgpu::VertexState {
+                module : shader,
+                entry_point : "vs_bake",
+                buffers : &[TangentVertex::desc(), InstanceRaw::desc()],
+            },
+            fragment : None,
+            primitive : wgpu::PrimitiveState {
+                topology : wgpu::PrimitiveTopology::TriangleList,
+                front_face : wgpu::FrontFace::Ccw,
+                cull_mode : Some(wgpu::Face::Back),
+                unclipped_depth : device
+                    .features()
+                    .contains(wgpu::Features::DEPTH_CLIP_CONTROL),
+                ..Default::default()
+            },
+            depth_stencil : Some(wgpu::DepthStencilState {
+                format : Self::FORMAT,
+                depth_write_enabled : true,
+                depth_compare : wgpu::CompareFunction::LessEqual,
+                stencil : wgpu::StencilState::default(),
+                bias : wgpu::DepthBiasState {
+                    constant : 2, // corresponds to bilinear filtering
+                    slope_scale : 2.0,
+                    clamp : 0.0,
+                },
+            }),
+            multisample : wgpu::MultisampleState::default(),
+            multiview : None,
+        })
+    }
+
+    // NOTE: `changed` comes from ShaderWatcher::poll
+    pub fn reload_shaders(
+        &mut self,
+        device : &wgpu::Device,
+        watcher : &mut ShaderWatcher,
+        changed : &[String],
+    ) {
+
+        for name in changed.iter().filter(|name| *name == "shadow.wgsl") {
+
+            let reloaded = watcher.read(name).and_then(|source| {
+
+                hot_reload::compile(device, name, &source, |shader| {
+
+                    Self::create_bake_pipeline(device, &self.bake_pipeline_layout, shader)
+                })
+            });
+
+            let result = reloaded.map(|(_, pipeline)| self.bake_pipeline = pipeline);
+
+            watcher.report(name, result);
+        }
+    }
+
+    fn clamp_config(config : ShadowConfig) -> ShadowConfig {
+
+        ShadowConfig {
+            light_count : config.light_count.min(MAX_SHADOWS as u32),
+            ..config
+        }
+    }
+
+    // NOTE: everything that depends on the resolution and light count
+    fn create_maps(
+        device : &wgpu::Device,
+        layout : &wgpu::BindGroupLayout,
+        uniform_buffer : &wgpu::Buffer,
+        config : &ShadowConfig,
+    ) -> (wgpu::Texture, Vec<wgpu::TextureView>, wgpu::BindGroup) {
+
+        // a texture array needs at least one layer
+        let layers = config.light_count.max(1);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label : Some("shadow_texture"),
+            size : wgpu::Extent3d {
+                width : config.resolution,
+                height : config.resolution,
+                depth_or_array_layers : layers,
+            },
+            mip_level_count : 1,
+            sample_count : 1,
+            dimension : wgpu::TextureDimension::D2,
+            format : Self::FORMAT,
+            usage : wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats : &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label : Some("shadow_view"),
+            dimension : Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        let target_views : Vec<_> = (0..layers)
+            .map(|i| {
+
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label : Some("shadow_target_view"),
+                    dimension : Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer : i,
+                    array_layer_count : std::num::NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label : Some("shadow_sampler"),
+            address_mode_u : wgpu::AddressMode::ClampToEdge,
+            address_mode_v : wgpu::AddressMode::ClampToEdge,
+            address_mode_w : wgpu::AddressMode::ClampToEdge,
+            mag_filter : wgpu::FilterMode::Linear,
+            min_filter : wgpu::FilterMode::Linear,
+            mipmap_filter : wgpu::FilterMode::Nearest,
+            compare : Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries : &[
+                wgpu::BindGroupEntry {
+                    binding : 0,
+                    resource : uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding : 1,
+                    resource : wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding : 2,
+                    resource : wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label : Some("shadow_bind_group"),
+        });
+
+        (texture, target_views, bind_group)
+    }
+
+    // NOTE: keeps the bind group layout, pipelines built against it stay valid
+    pub fn reconfigure(&mut self, device : &wgpu::Device, config : ShadowConfig) {
+
+        self.config = Self::clamp_config(config);
+
+        self.uniform.pcf_radius = self.config.pcf_radius;
+
+        self.uniform.texel_size = 1.0 / self.config.resolution as f32;
+
+        (self.texture, self.target_views, self.bind_group) = Self::create_maps(
+            device,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+            &self.config,
+        );
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture { &self.texture }
+
+    // NOTE: light matrices follow the lights, call once per frame
+    pub fn update(&mut self, queue : &wgpu::Queue, lights : &Lights) {
+
+        let mut count = 0;
+
+        for ((_, light), view_proj) in lights
+            .iter()
+            .zip(self.uniform.view_proj.iter_mut())
+            .take(self.config.light_count as usize)
+        {
+
+            *view_proj = self.config.light_view_proj(light).into();
+
+            count += 1;
+        }
+
+        self.uniform.count = count;
+
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    // NOTE: one depth pass per shadowed light, before the main pass
+    pub fn encode(&self, encoder : &mut wgpu::CommandEncoder, casters : &[ShadowCaster]) {
+
+        let matrix_size = std::mem::size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress;
+
+        encoder.push_debug_group("shadow passes");
+
+        for (i, target_view) in self
+            .target_views
+            .iter()
+            .enumerate()
+            .take(self.uniform.count as usize)
+        {
+
+            // the shadow uniform already has the matrix, copy it over to the bake buffer
+            encoder.copy_buffer_to_buffer(
+                &self.uniform_buffer,
+                i as wgpu::BufferAddress * matrix_size,
+                &self.bake_buffer,
+                0,
+                matrix_size,
+            );
+
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label : Some("Shadow Pass"),
+                color_attachments : &[],
+                depth_stencil_attachment : Some(wgpu::RenderPassDepthStencilAttachment {
+                    view : target_view,
+                    depth_ops : Some(wgpu::Operations {
+                        load : wgpu::LoadOp::Clear(1.0),
+                        store : true,
+                    }),
+                    stencil_ops : None,
+                }),
+            });
+
+            pass.set_pipeline(&self.bake_pipeline);
+
+            pass.set_bind_group(0, &self.bake_bind_group, &[]);
+
+            for caster in casters {
+
+                pass.set_vertex_buffer(1, caster.instance_buffer.slice(..));
+
+                pass.draw_model_shadow(caster.model, caster.instances.clone());
+            }
+        }
+
+        encoder.pop_debug_group();
+    }
+}
+
+// NOTE: geometry only, no material or camera bindings
+pub trait DrawShadow<'a> {
+    fn draw_mesh_shadow(&mut self, mesh : &'a Mesh, instances : Range<u32>);
+
+    fn draw_model_shadow(&mut self, model : &'a Model, instances : Range<u32>);
+}
+
+impl<'a, 'b> DrawShadow<'b> for wgpu::RenderPass<'a>
+where
+    'b : 'a,
+{
+    fn draw_mesh_shadow(&mut self, mesh : &'b Mesh, instances : Range<u32>) {
+
+        self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+
+        self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        self.draw_indexed(0..mesh.num_elements, 0, instances);
+    }
+
+    fn draw_model_shadow(&mut self, model : &'b Model, instances : Range<u32>) {
+
+        for mesh in &model.meshes {
+
+            self.draw_mesh_shadow(mesh, instances.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    fn to_shadow_uv(view_proj : cgmath::Matrix4<f32>, p : [f32; 3]) -> [f32; 3] {
+
+        let clip = view_proj * cgmath::Vector4::new(p[0], p[1], p[2], 1.0);
+
+        let ndc = clip / clip.w;
+
+        [ndc.x * 0.5 + 0.5, ndc.y * -0.5 + 0.5, ndc.z]
+    }
+
+    #[test]
+
+    fn test_light_view_proj() {
+
+        let config = ShadowConfig::default();
+
+        // straight down, the point light has up parallel to its direction too
+        let lights = [
+            Light::directional((0.0, -1.0, 0.0).into(), [1.0; 3], 1.0),
+            Light::point((0.0, 10.0, 0.0).into(), [1.0; 3], 1.0),
+        ];
+
+        for light in lights {
+
+            let view_proj = config.light_view_proj(&light);
+
+            let center = to_shadow_uv(view_proj, [0.0, 0.0, 0.0]);
+
+            assert!((center[0] - 0.5).abs() < 1e-5 && (center[1] - 0.5).abs() < 1e-5);
+
+            assert!(center[2] > 0.0 && center[2] < 1.0);
+
+            // closer to the light means smaller depth
+            let above = to_shadow_uv(view_proj, [0.0, 1.0, 0.0]);
+
+            assert!(above[2] < center[2]);
+        }
+    }
+}
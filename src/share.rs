@@ -17,6 +17,15 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.5, 1.0,
 );
 
+// NOTE: z' = w - z, maps wgpu depth 0..1 to 1..0 for reversed-z
+#[rustfmt::skip]
+pub const REVERSED_Z_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 0.0, 1.0, 1.0,
+);
+
 #[derive(Copy, Clone)]
 
 enum Color {
@@ -254,6 +263,43 @@ mod test {
 
         assert_eq!(r[0], 0);
     }
+
+    #[test]
+
+    fn test_reversed_z() {
+
+        let proj = cgmath::perspective(cgmath::Deg(45.0f32), 1.0, 0.1, 100.0);
+
+        let depth = |z : f32| {
+
+            let clip =
+                REVERSED_Z_MATRIX * OPENGL_TO_WGPU_MATRIX * proj * cgmath::vec4(0.0, 0.0, z, 1.0);
+
+            clip.z / clip.w
+        };
+
+        assert!((depth(-0.1) - 1.0).abs() < 1e-5);
+
+        assert!(depth(-100.0).abs() < 1e-5);
+
+        assert!(depth(-1.0) > depth(-10.0));
+    }
+
+    #[test]
+
+    fn test_instance_normal_matrix() {
+
+        let raw = InstanceRaw::from_matrix(cgmath::Matrix4::from_nonuniform_scale(2.0, 1.0, 1.0));
+
+        // normals scale by the inverse
+        assert_eq!(raw.normal[0], [0.5, 0.0, 0.0]);
+
+        assert_eq!(raw.normal[1], [0.0, 1.0, 0.0]);
+
+        assert_eq!(raw.color, [1.0; 4]);
+
+        assert_eq!(std::mem::size_of::<InstanceRaw>(), 29 * 4);
+    }
 }
 
 pub struct Instance {
@@ -262,24 +308,47 @@ pub struct Instance {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 
 pub struct InstanceRaw {
     model : [[f32; 4]; 4],
+    // inverse transpose of the upper 3x3, keeps normals right under non uniform scale
+    normal : [[f32; 3]; 3],
+    color : [f32; 4],
 }
 
 impl Instance {
     pub fn to_raw(&self) -> InstanceRaw {
 
-        InstanceRaw {
-            model : (cgmath::Matrix4::from_translation(self.position)
-                * cgmath::Matrix4::from(self.rotation))
-            .into(),
-        }
+        InstanceRaw::from_matrix(
+            cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation),
+        )
     }
 }
 
 impl InstanceRaw {
+    pub fn from_matrix(model : cgmath::Matrix4<f32>) -> Self {
+
+        use cgmath::{Matrix, SquareMatrix};
+
+        let upper =
+            cgmath::Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate());
+
+        // a degenerate (zero scale) instance keeps its plain rotation part
+        let normal = upper.invert().map(|m| m.transpose()).unwrap_or(upper);
+
+        InstanceRaw {
+            model : model.into(),
+            normal : normal.into(),
+            color : [1.0; 4],
+        }
+    }
+
+    // multiplies the material diffuse color
+    pub fn with_color(self, color : [f32; 4]) -> Self { Self { color, ..self } }
+
+    pub fn model_matrix(&self) -> cgmath::Matrix4<f32> { self.model.into() }
+
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
 
         use std::mem;
@@ -317,6 +386,28 @@ impl InstanceRaw {
                     shader_location : 8,
                     format : wgpu::VertexFormat::Float32x4,
                 },
+                // normal matrix, 3 vec3s
+                wgpu::VertexAttribute {
+                    offset : mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location : 9,
+                    format : wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset : mem::size_of::<[f32; 19]>() as wgpu::BufferAddress,
+                    shader_location : 10,
+                    format : wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset : mem::size_of::<[f32; 22]>() as wgpu::BufferAddress,
+                    shader_location : 11,
+                    format : wgpu::VertexFormat::Float32x3,
+                },
+                // color
+                wgpu::VertexAttribute {
+                    offset : mem::size_of::<[f32; 25]>() as wgpu::BufferAddress,
+                    shader_location : 12,
+                    format : wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
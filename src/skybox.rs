@@ -0,0 +1,181 @@
+// NOTE: cubemap background drawn first in the main pass
+// it never writes depth, so the scene simply draws over it
+
+use crate::hot_reload::{self, ShaderWatcher};
+use crate::texture::{self, DepthMode};
+
+pub struct Skybox {
+    pub texture : texture::Texture,
+    pipeline : wgpu::RenderPipeline,
+    pipeline_layout : wgpu::PipelineLayout,
+    // last skybox.wgsl that compiled, reused by recreate_pipeline
+    shader : wgpu::ShaderModule,
+    bind_group : wgpu::BindGroup,
+}
+
+impl Skybox {
+    pub fn new(
+        device : &wgpu::Device,
+        camera_bind_group_layout : &wgpu::BindGroupLayout,
+        texture : texture::Texture,
+        format : wgpu::TextureFormat,
+        depth_mode : DepthMode,
+        sample_count : u32,
+    ) -> anyhow::Result<Self> {
+
+        if texture.view_dimension != wgpu::TextureViewDimension::Cube {
+
+            anyhow::bail!(
+                "skybox needs a cube texture, got {:?}",
+                texture.view_dimension
+            );
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries : &texture.layout_entries(0, wgpu::ShaderStages::FRAGMENT),
+            label : Some("skybox_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout : &bind_group_layout,
+            entries : &texture.bind_group_entries(0),
+            label : Some("skybox_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label : Some("Skybox Pipeline Layout"),
+            bind_group_layouts : &[camera_bind_group_layout, &bind_group_layout],
+            push_constant_ranges : &[],
+        });
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../assets/shaders/skybox.wgsl"));
+
+        let pipeline = Self::create_pipeline(
+            device,
+            &pipeline_layout,
+            &shader,
+            format,
+            depth_mode,
+            sample_count,
+        );
+
+        Ok(Self {
+            texture,
+            pipeline,
+            pipeline_layout,
+            shader,
+            bind_group,
+        })
+    }
+
+    // NOTE: the pass has a depth attachment, so the pipeline must match its format and sample count
+    fn create_pipeline(
+        device : &wgpu::Device,
+        layout : &wgpu::PipelineLayout,
+        shader : &wgpu::ShaderModule,
+        format : wgpu::TextureFormat,
+        depth_mode : DepthMode,
+        sample_count : u32,
+    ) -> wgpu::RenderPipeline {
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label : Some("Skybox Pipeline"),
+            layout : Some(layout),
+            vertex : wgpu::VertexState {
+                module : shader,
+                entry_point : "vs_main",
+                buffers : &[],
+            },
+            fragment : Some(wgpu::FragmentState {
+                module : shader,
+                entry_point : "fs_main",
+                targets : &[Some(format.into())],
+            }),
+            primitive : wgpu::PrimitiveState::default(),
+            depth_stencil : Some(wgpu::DepthStencilState {
+                depth_write_enabled : false,
+                depth_compare : wgpu::CompareFunction::Always,
+                ..depth_mode.depth_stencil_state()
+            }),
+            multisample : wgpu::MultisampleState {
+                count : sample_count,
+                ..Default::default()
+            },
+            multiview : None,
+        })
+    }
+
+    // called whenever the main pass changes its depth mode or sample count
+    pub fn recreate_pipeline(
+        &mut self,
+        device : &wgpu::Device,
+        format : wgpu::TextureFormat,
+        depth_mode : DepthMode,
+        sample_count : u32,
+    ) {
+
+        self.pipeline = Self::create_pipeline(
+            device,
+            &self.pipeline_layout,
+            &self.shader,
+            format,
+            depth_mode,
+            sample_count,
+        );
+    }
+
+    // NOTE: `changed` comes from ShaderWatcher::poll, the target arguments as in recreate_pipeline
+    pub fn reload_shaders(
+        &mut self,
+        device : &wgpu::Device,
+        watcher : &mut ShaderWatcher,
+        changed : &[String],
+        format : wgpu::TextureFormat,
+        depth_mode : DepthMode,
+        sample_count : u32,
+    ) {
+
+        for name in changed.iter().filter(|name| *name == "skybox.wgsl") {
+
+            let reloaded = watcher.read(name).and_then(|source| {
+
+                hot_reload::compile(device, name, &source, |shader| {
+
+                    Self::create_pipeline(
+                        device,
+                        &self.pipeline_layout,
+                        shader,
+                        format,
+                        depth_mode,
+                        sample_count,
+                    )
+                })
+            });
+
+            let result = reloaded.map(|(shader, pipeline)| {
+
+                self.shader = shader;
+
+                self.pipeline = pipeline;
+            });
+
+            watcher.report(name, result);
+        }
+    }
+
+    pub fn draw<'a>(
+        &'a self,
+        rpass : &mut wgpu::RenderPass<'a>,
+        camera_bind_group : &'a wgpu::BindGroup,
+    ) {
+
+        rpass.set_pipeline(&self.pipeline);
+
+        rpass.set_bind_group(0, camera_bind_group, &[]);
+
+        rpass.set_bind_group(1, &self.bind_group, &[]);
+
+        rpass.draw(0..3, 0..1);
+    }
+}
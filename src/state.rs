@@ -4,11 +4,23 @@ use cgmath::prelude::*;
 use wgpu::BindingResource::TextureView;
 use wgpu::BufferUsages;
 
+use crate::asset::{AssetServer, Handle, LoadState};
 use crate::camera::*;
+use crate::camera_path::{CameraPath, CameraPlayback, CameraRecorder};
+use crate::culling::{CullTarget, Frustum, GpuCuller};
+use crate::depth_view::DepthView;
+use crate::hdr::{HdrPipeline, ToneMapOperator, ToneMapping};
+use crate::hot_reload::{self, ShaderWatcher};
 use crate::imgui_layer::Layer;
-use crate::model::Model;
-use crate::resource;
+use crate::instance::InstanceBuffer;
+use crate::light::{Light, LightId, Lights};
+use crate::model::{Material, Model};
+use crate::picking::{self, Hit, IdPicker, Ray};
+use crate::post::{Effect, PostStack};
+use crate::scene::{Scene, Transform};
+use crate::shadow::{ShadowCaster, ShadowConfig, ShadowMaps};
 use crate::share::*;
+use crate::skybox::Skybox;
 use crate::texture;
 use wgpu::util::DeviceExt;
 
@@ -18,35 +30,95 @@ use winit::{event::WindowEvent, window::Window};
 use imgui::*;
 use imgui_wgpu::{Renderer, RendererConfig};
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+
+pub enum PickMode {
+    // ray against bounds and triangles, no gpu round trip
+    #[default]
+    Cpu,
+    // id buffer readback, exact to the pixel
+    Gpu,
+}
+
 pub struct State {
-    pub surface : wgpu::Surface,
+    // NOTE: surface and window are None for headless states
+    pub surface : Option<wgpu::Surface>,
     pub device : wgpu::Device,
     pub queue : wgpu::Queue,
     pub config : wgpu::SurfaceConfiguration,
     pub size : winit::dpi::PhysicalSize<u32>,
-    pub window : Window,
+    pub window : Option<Window>,
+
+    // headless render target, replaces the surface texture
+    pub offscreen_target : Option<wgpu::Texture>,
 
     // Pipeline
     pub render_pipeline : wgpu::RenderPipeline,
+    render_pipeline_layout : wgpu::PipelineLayout,
+    // last shader.wgsl that compiled, reused when the depth mode or sample count changes
+    scene_shader : wgpu::ShaderModule,
+    // NOTE: development mode, recompiles shaders when they change on disk, see reload_shaders
+    pub shader_watcher : Option<ShaderWatcher>,
+
+    // depth
+    depth_mode : texture::DepthMode,
+    pub depth_view : Option<DepthView>,
+
+    // msaa, see set_sample_count
+    sample_count : u32,
+    supported_sample_counts : Vec<u32>,
 
     // texture
     pub diffuse_bind_group : wgpu::BindGroup,
-    pub diffuse_texture : texture::Texture,
+    pub diffuse_texture : Handle<texture::Texture>,
     pub depth_texture : texture::Texture,
-    pub obj_model : Model,
 
-    // instance
-    instances : Vec<Instance>,
-    instance_buffer : wgpu::Buffer,
+    // NOTE: loads through here share textures, see AssetServer
+    // background loads are uploaded in update, see wait_for_assets
+    pub assets : AssetServer,
+    material_bind_group_layout : wgpu::BindGroupLayout,
+    // NOTE: scene nodes attach models by their index in here, pending ones are skipped
+    pub models : Vec<Handle<Model>>,
+
+    // scene graph -> per model instance buffers
+    pub scene : Scene,
+    instance_buffers : Vec<InstanceBuffer>,
+
+    // frustum culling, compute pass + indirect draws when the device supports it,
+    // otherwise the visible instances are culled on the cpu into `visible_buffers`
+    pub frustum_culling : bool,
+    culler : Option<GpuCuller>,
+    cull_targets : Vec<CullTarget>,
+    visible_buffers : Vec<InstanceBuffer>,
+
+    // created on the first gpu pick
+    picker : Option<IdPicker>,
 
     // camera
     pub camera : Camera,
-    pub camera_controller : CameraController,
+    // NOTE: swap with set_camera_controller, orbit by default
+    pub camera_controller : Box<dyn CameraController>,
+    last_update : Instant,
+    // NOTE: a playing path replaces the controller, the recorder samples every update
+    pub camera_playback : Option<CameraPlayback>,
+    pub camera_recorder : Option<CameraRecorder>,
     pub camera_uniform : CameraUniform,
     pub camera_buffer : wgpu::Buffer,
     pub camera_bind_group : wgpu::BindGroup,
+    camera_bind_group_layout : wgpu::BindGroupLayout,
+
+    // lights
+    pub lights : Lights,
+    pub shadows : ShadowMaps,
 
     pub clear_color : wgpu::Color,
+    // hdr scene target + tone mapping into the surface
+    hdr : HdrPipeline,
+    pub tone_mapping : ToneMapping,
+    // NOTE: effects after tone mapping, all disabled until toggled
+    pub post : PostStack,
+    // NOTE: drawn behind the scene instead of the clear color, see set_skybox
+    skybox : Option<Skybox>,
 
     // imgui
     pub imgui_context : imgui::Context,
@@ -82,13 +154,6 @@ impl State {
 
         let hidpi_factor = window.scale_factor();
 
-        let clear_color = wgpu::Color {
-            r : 0.1,
-            g : 0.2,
-            b : 0.3,
-            a : 1.0,
-        };
-
         let size = window.inner_size();
 
         // The instance is a handle to our GPU
@@ -116,17 +181,107 @@ impl State {
             .next()
             .unwrap();
 
-        // Device and queue with features
+        let (device, queue) = Self::request_device(&adapter).await.unwrap();
+
+        // surfaces formats
+
+        let surface_caps = surface.get_capabilities(&adapter);
+
+        let config = wgpu::SurfaceConfiguration {
+            usage : wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format : wgpu::TextureFormat::Bgra8UnormSrgb,
+            width : size.width,
+            height : size.height,
+            present_mode : surface_caps.present_modes[0],
+            alpha_mode : surface_caps.alpha_modes[0],
+            view_formats : vec![wgpu::TextureFormat::Bgra8Unorm],
+        };
+
+        //
+        surface.configure(&device, &config);
+
+        Self::build(
+            &adapter,
+            device,
+            queue,
+            config,
+            Some(surface),
+            Some(window),
+            hidpi_factor,
+        )
+        .await
+    }
+
+    // NOTE: no window, no surface, the scene is rendered into an owned
+    // Rgba8UnormSrgb texture, see render_offscreen
+    pub async fn new_headless(width : u32, height : u32) -> anyhow::Result<Self> {
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends : wgpu::util::backend_bits_from_env().unwrap_or_else(wgpu::Backends::all),
+            dx12_shader_compiler : Default::default(),
+        });
+
+        // prefer a real gpu, fall back to the software adapter (CI, batch jobs)
+        let mut adapter = None;
+
+        for force_fallback_adapter in [false, true] {
+
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference : wgpu::PowerPreference::default(),
+                    compatible_surface : None,
+                    force_fallback_adapter,
+                })
+                .await;
 
-        let (device, queue) = adapter
+            if adapter.is_some() {
+
+                break;
+            }
+        }
+
+        let adapter = adapter.ok_or_else(|| anyhow::anyhow!("no suitable adapter found"))?;
+
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage : wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format : texture::Texture::OFFSCREEN_FORMAT,
+            width,
+            height,
+            present_mode : wgpu::PresentMode::Fifo,
+            alpha_mode : wgpu::CompositeAlphaMode::Auto,
+            view_formats : vec![],
+        };
+
+        let state = Self::build(&adapter, device, queue, config, None, None, 1.0).await;
+
+        // a headless frame is rendered right away, don't capture the placeholders
+        state.wait_for_assets();
+
+        Ok(state)
+    }
+
+    // Device and queue with features
+    async fn request_device(
+        adapter : &wgpu::Adapter,
+    ) -> Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError> {
+
+        adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    features : wgpu::Features::empty(),
+                    // NOTE: unlocks 2x/8x msaa where the adapter has it, see supported_sample_counts
+                    features : adapter.features()
+                        & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
                     // WebGL doesn't support all of wgpu's features, so if
                     // we're building for the web we'll have to disable some.
                     limits : if cfg!(target_arch = "wasm32") {
 
                         wgpu::Limits::downlevel_webgl2_defaults()
+                    } else if adapter.get_info().device_type == wgpu::DeviceType::Cpu {
+
+                        // software adapters may not reach the default limits
+                        wgpu::Limits::downlevel_defaults()
                     } else {
 
                         wgpu::Limits::default()
@@ -136,24 +291,36 @@ impl State {
                 None, // Trace path
             )
             .await
-            .unwrap();
-
-        // surfaces formats
+    }
 
-        let surface_caps = surface.get_capabilities(&adapter);
+    async fn build(
+        adapter : &wgpu::Adapter,
+        device : wgpu::Device,
+        queue : wgpu::Queue,
+        config : wgpu::SurfaceConfiguration,
+        surface : Option<wgpu::Surface>,
+        window : Option<Window>,
+        hidpi_factor : f64,
+    ) -> Self {
 
-        let config = wgpu::SurfaceConfiguration {
-            usage : wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format : wgpu::TextureFormat::Bgra8UnormSrgb,
-            width : size.width,
-            height : size.height,
-            present_mode : surface_caps.present_modes[0],
-            alpha_mode : surface_caps.alpha_modes[0],
-            view_formats : vec![wgpu::TextureFormat::Bgra8Unorm],
+        let clear_color = wgpu::Color {
+            r : 0.1,
+            g : 0.2,
+            b : 0.3,
+            a : 1.0,
         };
 
-        //
-        surface.configure(&device, &config);
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+
+        let offscreen_target = match surface {
+            Some(_) => None,
+            None => Some(texture::Texture::create_offscreen_target(
+                &device,
+                config.width,
+                config.height,
+                "offscreen_target",
+            )),
+        };
 
         // NOTE: camera controller -> camera -> unifom -> buffer -> vextex shader
 
@@ -165,9 +332,10 @@ impl State {
             fovy : 45.0,
             znear : 0.1,
             zfar : 100.0,
+            projection : Projection::Perspective,
         };
 
-        let camera_controller = CameraController::new(0.2);
+        let camera_controller : Box<dyn CameraController> = Box::new(OrbitController::new(&camera));
 
         let mut camera_uniform = CameraUniform::new();
 
@@ -184,7 +352,8 @@ impl State {
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries : &[wgpu::BindGroupLayoutEntry {
                     binding : 0,
-                    visibility : wgpu::ShaderStages::VERTEX,
+                    // NOTE: fragment reads view_position for specular
+                    visibility : wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty : wgpu::BindingType::Buffer {
                         ty : wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset : false,
@@ -204,6 +373,22 @@ impl State {
             label : Some("camera_bind_group"),
         });
 
+        // NOTE: @group(2) lights, a sun and a lamp above the instances
+        let light_bind_group_layout = Lights::bind_group_layout(&device);
+
+        let mut lights = Lights::new(&device, &light_bind_group_layout);
+
+        lights.add(Light::directional(
+            (-0.4, -0.8, -0.45).into(),
+            [1.0, 1.0, 1.0],
+            0.8,
+        ));
+
+        lights.add(Light::point((0.0, 4.0, 0.0).into(), [1.0, 0.9, 0.7], 10.0));
+
+        // NOTE: @group(3) shadow maps of the first lights
+        let shadows = ShadowMaps::new(&device, ShadowConfig::default());
+
         // NOTE: Imgui
 
         // [doc] file:///home/vagrant/workspace/rust/wgpu-tutorial-rs/target/doc/imgui_winit_support/index.html
@@ -211,11 +396,14 @@ impl State {
 
         let mut platform = imgui_winit_support::WinitPlatform::init(&mut imgui_context);
 
-        platform.attach_window(
-            imgui_context.io_mut(),
-            &window,
-            imgui_winit_support::HiDpiMode::Default,
-        );
+        match &window {
+            Some(window) => platform.attach_window(
+                imgui_context.io_mut(),
+                window,
+                imgui_winit_support::HiDpiMode::Default,
+            ),
+            None => imgui_context.io_mut().display_size = [size.width as f32, size.height as f32],
+        }
 
         imgui_context.set_ini_filename(None);
 
@@ -303,69 +491,55 @@ impl State {
             label : Some("diffuse_bind_group"),
         });
 
+        // NOTE: @group(0) of the model pipeline, see model::Material
+        let material_bind_group_layout = Material::bind_group_layout(&device);
+
         // depth_texture
-        let depth_texture =
-            texture::Texture::create_depth_texture(&device, &config, "depth_texture");
+        let depth_texture = texture::Texture::create_depth_texture_with_mode(
+            &device,
+            &config,
+            texture::DepthMode::default(),
+            "depth_texture",
+        );
 
         // TODO: render_pipeline
-        let shader =
-            device.create_shader_module(wgpu::include_wgsl!("../assets/shaders/shader.wgsl"));
-
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout], // NOTE:
-                push_constant_ranges: &[],
+                label : Some("Render Pipeline Layout"),
+                bind_group_layouts : &[
+                    &material_bind_group_layout,
+                    &camera_bind_group_layout,
+                    &light_bind_group_layout,
+                    &shadows.bind_group_layout,
+                ], // NOTE:
+                push_constant_ranges : &[],
             });
 
-        use crate::model::ModelVertex;
+        let depth_mode = texture::DepthMode::default();
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label : Some("Render Pipeline"),
-            layout : Some(&render_pipeline_layout),
-            vertex : wgpu::VertexState {
-                module : &shader,
-                entry_point : "vs_main",                               // 1.
-                buffers : &[ModelVertex::desc(), InstanceRaw::desc()], // 2. added instances
-            },
-            fragment : Some(wgpu::FragmentState {
-                // 3.
-                module : &shader,
-                entry_point : "fs_main",
-                targets : &[Some(wgpu::ColorTargetState {
-                    // 4.
-                    format : wgpu::TextureFormat::Bgra8UnormSrgb,
-                    blend : Some(wgpu::BlendState::REPLACE),
-                    write_mask : wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive : wgpu::PrimitiveState {
-                topology : wgpu::PrimitiveTopology::TriangleList, // 1.
-                strip_index_format : None,
-                front_face : wgpu::FrontFace::Ccw, // 2.
-                cull_mode : Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode : wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth : false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative : false,
-            },
-            depth_stencil : None,
-            // depth_stencil : Some(wgpu::DepthStencilState {
-            //     format : texture::Texture::DEPTH_FORMAT,
-            //     depth_write_enabled : true,
-            //     depth_compare : wgpu::CompareFunction::Less, // 1.
-            //     stencil : wgpu::StencilState::default(),     // 2.
-            //     bias : wgpu::DepthBiasState::default(),
-            // }),
-            multisample : wgpu::MultisampleState {
-                count : 1,                         // 2.
-                mask : !0,                         // 3.
-                alpha_to_coverage_enabled : false, // 4.
-            },
-            multiview : None, // 5.
-        });
+        let supported_sample_counts = Self::query_sample_counts(adapter, &device);
+
+        let hdr = HdrPipeline::new(&device, config.width, config.height, config.format);
+
+        let post = PostStack::new(
+            &device,
+            config.width,
+            config.height,
+            config.format,
+            Effect::defaults(),
+        );
+
+        let scene_shader =
+            device.create_shader_module(wgpu::include_wgsl!("../assets/shaders/shader.wgsl"));
+
+        let render_pipeline = Self::create_render_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &scene_shader,
+            HdrPipeline::FORMAT,
+            depth_mode,
+            1,
+        );
 
         // HACK: vertex buffer
 
@@ -402,7 +576,12 @@ impl State {
 
         const SPACE_BETWEEN : f32 = 3.0;
 
-        let instances = (0..NUM_INSTANCES_PER_ROW)
+        // NOTE: the old 10x10 grid, one child node per cube under a common root
+        let mut scene = Scene::new();
+
+        let grid = scene.add_node("grid", None, Transform::default(), None);
+
+        let grid_transforms = (0..NUM_INSTANCES_PER_ROW)
             .flat_map(|z| {
 
                 (0..NUM_INSTANCES_PER_ROW).map(move |x| {
@@ -430,23 +609,40 @@ impl State {
                         cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
                     };
 
-                    Instance { position, rotation }
+                    Transform::from_translation_rotation(position, rotation)
                 })
             })
             .collect::<Vec<_>>();
 
-        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+        for (i, transform) in grid_transforms.into_iter().enumerate() {
 
-        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label : Some("Instance Buffer"),
-            contents : bytemuck::cast_slice(&instance_data),
-            usage : BufferUsages::VERTEX,
-        });
+            scene.add_node(&format!("cube {}", i), Some(grid), transform, Some(0));
+        }
+
+        let assets = AssetServer::new();
+
+        // NOTE: decoded on the asset workers, drawn once update has uploaded it
+        let obj_model = assets.load_model_background("cube.obj");
+
+        let models = vec![obj_model];
+
+        scene.update();
+
+        let instance_buffers = scene
+            .instances(models.len())
+            .iter()
+            .map(|instances| {
+
+                let mut buffer =
+                    InstanceBuffer::from_instances(&device, "Instance Buffer", instances);
+
+                buffer.sync(&device, &queue);
+
+                buffer
+            })
+            .collect();
 
-        let obj_model =
-            resource::load_model("cube.obj", &device, &queue, &texture_bind_group_layout)
-                .await
-                .unwrap();
+        let culler = GpuCuller::is_supported(&device).then(|| GpuCuller::new(&device));
 
         Self {
             window,
@@ -455,22 +651,47 @@ impl State {
             queue,
             config,
             size,
+            offscreen_target,
             clear_color,
+            hdr,
+            tone_mapping : ToneMapping::default(),
+            post,
+            skybox : None,
             render_pipeline,
+            render_pipeline_layout,
+            scene_shader,
+            shader_watcher : ShaderWatcher::development(),
+            depth_mode,
+            depth_view : None,
+            sample_count : 1,
+            supported_sample_counts,
             // vertex_buffer,
             // index_buffer,
             // num_indices,
-            instance_buffer,
-            instances,
+            scene,
+            instance_buffers,
+            frustum_culling : true,
+            culler,
+            cull_targets : Vec::new(),
+            visible_buffers : Vec::new(),
+            picker : None,
             diffuse_bind_group,
-            diffuse_texture,
+            diffuse_texture : Handle::loaded("happy-tree.png", diffuse_texture),
             depth_texture,
-            obj_model,
+            assets,
+            material_bind_group_layout,
+            models,
             camera,
             camera_controller,
+            last_update : Instant::now(),
+            camera_playback : None,
+            camera_recorder : None,
             camera_buffer,
             camera_bind_group,
+            camera_bind_group_layout,
             camera_uniform,
+            lights,
+            shadows,
             renderer,
             imgui_context,
             platform,
@@ -481,7 +702,60 @@ impl State {
         }
     }
 
-    pub fn window(&self) -> &Window { &self.window }
+    // NOTE: rebuilt whenever the depth mode or sample count changes
+    fn create_render_pipeline(
+        device : &wgpu::Device,
+        layout : &wgpu::PipelineLayout,
+        shader : &wgpu::ShaderModule,
+        format : wgpu::TextureFormat,
+        depth_mode : texture::DepthMode,
+        sample_count : u32,
+    ) -> wgpu::RenderPipeline {
+
+        use crate::model::TangentVertex;
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label : Some("Render Pipeline"),
+            layout : Some(layout),
+            vertex : wgpu::VertexState {
+                module : shader,
+                entry_point : "vs_main",                                 // 1.
+                buffers : &[TangentVertex::desc(), InstanceRaw::desc()], // 2. added instances
+            },
+            fragment : Some(wgpu::FragmentState {
+                // 3.
+                module : shader,
+                entry_point : "fs_main",
+                targets : &[Some(wgpu::ColorTargetState {
+                    // 4.
+                    format,
+                    blend : Some(wgpu::BlendState::REPLACE),
+                    write_mask : wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive : wgpu::PrimitiveState {
+                topology : wgpu::PrimitiveTopology::TriangleList, // 1.
+                strip_index_format : None,
+                front_face : wgpu::FrontFace::Ccw, // 2.
+                cull_mode : Some(wgpu::Face::Back),
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                polygon_mode : wgpu::PolygonMode::Fill,
+                // Requires Features::DEPTH_CLIP_CONTROL
+                unclipped_depth : false,
+                // Requires Features::CONSERVATIVE_RASTERIZATION
+                conservative : false,
+            },
+            depth_stencil : Some(depth_mode.depth_stencil_state()),
+            multisample : wgpu::MultisampleState {
+                count : sample_count,              // 2.
+                mask : !0,                         // 3.
+                alpha_to_coverage_enabled : false, // 4.
+            },
+            multiview : None, // 5.
+        })
+    }
+
+    pub fn window(&self) -> &Window { self.window.as_ref().expect("headless State has no window") }
 
     // impl State
     pub fn resize(&mut self, new_size : winit::dpi::PhysicalSize<u32>) {
@@ -490,20 +764,32 @@ impl State {
 
             self.size = new_size;
 
-            self.config = wgpu::SurfaceConfiguration {
-                usage : wgpu::TextureUsages::RENDER_ATTACHMENT,
-                format : wgpu::TextureFormat::Bgra8UnormSrgb,
-                width : new_size.width,
-                height : new_size.height,
-                present_mode : wgpu::PresentMode::Fifo,
-                alpha_mode : wgpu::CompositeAlphaMode::Auto,
-                view_formats : vec![wgpu::TextureFormat::Bgra8Unorm],
-            };
+            self.config.width = new_size.width;
+
+            self.config.height = new_size.height;
+
+            match &self.surface {
+                Some(surface) => surface.configure(&self.device, &self.config),
+                None => {
+
+                    self.offscreen_target = Some(texture::Texture::create_offscreen_target(
+                        &self.device,
+                        new_size.width,
+                        new_size.height,
+                        "offscreen_target",
+                    ));
+                }
+            }
+
+            self.camera.aspect = new_size.width as f32 / new_size.height as f32;
+
+            self.recreate_depth_texture();
 
-            self.surface.configure(&self.device, &self.config);
+            self.hdr
+                .resize(&self.device, new_size.width, new_size.height);
 
-            self.depth_texture =
-                texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            self.post
+                .resize(&self.device, new_size.width, new_size.height);
 
             // TODO:
 
@@ -517,141 +803,960 @@ impl State {
 
     pub fn input(&mut self, event : &WindowEvent) -> bool {
 
-        self.camera_controller.process_events(event)
+        // NOTE: clicks, wheel and keys over imgui windows stay with imgui,
+        // releases and cursor moves always reach the controller so drags can end
+        let io = self.imgui_context.io();
+
+        let captured = match event {
+            WindowEvent::MouseInput {
+                state: winit::event::ElementState::Pressed,
+                ..
+            }
+            | WindowEvent::MouseWheel { .. } => io.want_capture_mouse,
+            WindowEvent::KeyboardInput { .. } => io.want_capture_keyboard,
+            _ => false,
+        };
+
+        !captured && self.camera_controller.process_events(event)
+    }
+
+    pub fn set_camera_controller(&mut self, controller : impl CameraController + 'static) {
+
+        self.camera_controller = Box::new(controller);
     }
 
     pub fn update(&mut self) {
 
-        // update camera eye, target, fov,
+        // finished background loads -> gpu, pending models are skipped until then
+        self.assets
+            .poll(&self.device, &self.queue, &self.material_bind_group_layout);
+
+        self.reload_shaders();
+
+        // update camera eye, target, fov, scaled by the time since the last update
+
+        let now = Instant::now();
+
+        let dt = now - self.last_update;
+
+        self.last_update = now;
+
+        match &mut self.camera_playback {
+            Some(playback) => playback.update(&mut self.camera, dt),
+            None => self.camera_controller.update_camera(&mut self.camera, dt),
+        }
+
+        if let Some(recorder) = &mut self.camera_recorder {
+
+            recorder.record(&self.camera, dt);
+        }
 
-        self.camera_controller.update_camera(&mut self.camera);
+        self.upload_frame();
+    }
+
+    // NOTE: camera, lights, shadows and instances -> gpu, after the camera moved
+    fn upload_frame(&mut self) {
 
         // update v-p matrix from camera eye, target, fov, up
 
         self.camera_uniform.update_view_proj(&self.camera);
 
+        // NOTE: the depth mode decides the depth direction, a projection going the other way
+        // is flipped
+        if self.depth_mode.is_reversed() != self.camera.projection.is_reversed() {
+
+            self.camera_uniform.reverse_z();
+        }
+
         // NOTE: camera.vp matrix -> slice -> uniform buffer -> shader
         self.queue.write_buffer(
             &self.camera_buffer,
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
-    }
-
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
 
-        // NOTE: imgui timer
-        let delta_s = self.last_frame.elapsed();
+        // NOTE: only uploads after lights were added, moved or recolored
+        self.lights.write_buffer(&self.queue);
 
-        let now = Instant::now();
+        self.shadows.update(&self.queue, &self.lights);
 
-        let io = self.imgui_context.io_mut();
+        // NOTE: world matrices -> instance buffers, only after the scene changed
+        if self.scene.update() {
 
-        io.update_delta_time(delta_s);
+            self.upload_instances();
+        }
 
-        self.last_frame = now;
+        self.prepare_culling();
+    }
 
-        // NOTE: imgui ui = frame -> layers -> widgets
+    fn prepare_culling(&mut self) {
 
-        let main_frame = self.surface.get_current_texture()?;
+        if !self.frustum_culling {
 
-        self.platform
-            .prepare_frame(io, &self.window)
-            .expect("Failed to prepare frame");
+            return;
+        }
 
-        let imgui_ui = self.imgui_context.frame();
+        match &self.culler {
+            Some(culler) => {
 
-        // NOTE: prepare imgui layers
+                let models = self.models.iter().map(Handle::get).collect::<Vec<_>>();
 
-        let _texture_context = &mut texture::Context {
-            device : &self.device,
-            queue : &self.queue,
-            renderer : &mut self.renderer,
-        };
+                // NOTE: targets bind the instance buffers, recreate them after a buffer grew
+                for (i, (model, instances)) in models.iter().zip(&self.instance_buffers).enumerate()
+                {
 
-        // self.imgui_render(texture_context, &mut imgui_ui);
+                    // targets are indexed like the models, later ones draw unculled for now
+                    let model = match model.as_deref() {
+                        Some(model) => model,
+                        None => break,
+                    };
 
-        for _layer in &mut self.layers {
+                    if i == self.cull_targets.len() {
+
+                        self.cull_targets.push(culler.create_target(
+                            &self.device,
+                            model,
+                            instances,
+                        ));
+                    } else if self.cull_targets[i].is_stale(instances) {
+
+                        self.cull_targets[i] = culler.create_target(&self.device, model, instances);
+                    }
+                }
+            }
+            None => {
+
+                let frustum = Frustum::from_camera(&self.camera);
+
+                for (i, (model, instances)) in
+                    self.models.iter().zip(&self.instance_buffers).enumerate()
+                {
+
+                    if i == self.visible_buffers.len() {
+
+                        self.visible_buffers.push(InstanceBuffer::new(
+                            &self.device,
+                            "Visible Instance Buffer",
+                            0,
+                        ));
+                    }
+
+                    let visible = match model.get().as_deref().and_then(Model::bounds) {
+                        Some(bounds) => {
+                            frustum.cull(instances.instances(), &bounds.bounding_sphere())
+                        }
+                        None => Vec::new(),
+                    };
 
-            if let Some(window) = imgui_ui
-                .window("Gallery")
-                .size([512.0, 512.0], imgui::Condition::FirstUseEver)
-                .begin()
-            {
+                    let buffer = &mut self.visible_buffers[i];
 
-                let _new_imgui_region_size = imgui_ui.content_region_avail();
+                    buffer.set(&visible);
 
-                window.end();
-            };
+                    buffer.sync(&self.device, &self.queue);
+                }
+            }
         }
+    }
 
-        // NOTE: prepare render
-        if self.last_cursor != imgui_ui.mouse_cursor() {
+    fn upload_instances(&mut self) {
 
-            self.last_cursor = imgui_ui.mouse_cursor();
+        for (i, instances) in self.scene.instances(self.models.len()).iter().enumerate() {
 
-            self.platform.prepare_render(&imgui_ui, &self.window);
-        }
+            if i == self.instance_buffers.len() {
 
-        let main_view = main_frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+                self.instance_buffers
+                    .push(InstanceBuffer::new(&self.device, "Instance Buffer", 0));
+            }
 
-        let mut main_encoder =
-            self.device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label : Some("Render Encoder"),
-                });
+            // NOTE: only the instances that moved are written, grows when needed
+            let buffer = &mut self.instance_buffers[i];
 
-        // Render pass scope
-        {
+            buffer.set(instances);
 
-            let mut main_rpass = main_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label : Some("Render Pass"),
-                color_attachments : &[Some(wgpu::RenderPassColorAttachment {
-                    view : &main_view,
-                    resolve_target : None,
-                    ops : wgpu::Operations {
-                        load : wgpu::LoadOp::Clear(self.clear_color),
-                        store : true,
-                    },
-                })],
-                depth_stencil_attachment : None,
-                // depth_stencil_attachment : Some(wgpu::RenderPassDepthStencilAttachment {
-                //     view : &self.depth_texture.view,
-                //     depth_ops : Some(wgpu::Operations {
-                //         load : wgpu::LoadOp::Clear(1.0),
-                //         store : true,
-                //     }),
-                //     stencil_ops : None,
-                // }),
+            buffer.sync(&self.device, &self.queue);
+        }
+    }
+
+    // NOTE: blocks until every background load is uploaded
+    pub fn wait_for_assets(&self) {
+
+        self.assets
+            .wait(&self.device, &self.queue, &self.material_bind_group_layout);
+    }
+
+    // what materials of models built in code are created with, see Material::new
+    pub fn material_layout(&self) -> &wgpu::BindGroupLayout { &self.material_bind_group_layout }
+
+    // NOTE: returns the index scene nodes use to attach the model
+    // e.g. state.assets.load_model(..), or Handle::loaded for a model built in code
+    pub fn add_model(&mut self, model : Handle<Model>) -> usize {
+
+        self.models.push(model);
+
+        self.upload_instances();
+
+        self.models.len() - 1
+    }
+
+    // NOTE: cursor in physical pixels, as in WindowEvent::CursorMoved
+    // Scene::instance_node maps the hit back to its node
+    pub fn pick(&mut self, cursor : [f32; 2], mode : PickMode) -> anyhow::Result<Option<Hit>> {
+
+        let size = [self.config.width, self.config.height];
+
+        match mode {
+            PickMode::Cpu => {
+
+                let instances : Vec<_> = self
+                    .instance_buffers
+                    .iter()
+                    .map(|buffer| buffer.instances())
+                    .collect();
+
+                Ok(Ray::from_cursor(&self.camera, cursor, size)
+                    .and_then(|ray| picking::pick(&ray, &self.models, &instances)))
+            }
+            PickMode::Gpu => {
+
+                let picker = self
+                    .picker
+                    .get_or_insert_with(|| IdPicker::new(&self.device, size));
+
+                picker.resize(&self.device, size);
+
+                picker.pick(
+                    &self.device,
+                    &self.queue,
+                    &self.camera,
+                    &self.models,
+                    &self.instance_buffers,
+                    cursor,
+                )
+            }
+        }
+    }
+
+    // NOTE: new resolution / light count / pcf radius, pipelines stay valid
+    pub fn set_shadow_config(&mut self, config : ShadowConfig) {
+
+        self.shadows.reconfigure(&self.device, config);
+    }
+
+    // NOTE: runtime lights, None when all light slots are taken
+    pub fn add_light(&mut self, light : Light) -> Option<LightId> { self.lights.add(light) }
+
+    pub fn remove_light(&mut self, id : LightId) -> Option<Light> { self.lights.remove(id) }
+
+    pub fn move_light(&mut self, id : LightId, position : cgmath::Point3<f32>) -> bool {
+
+        self.lights
+            .get_mut(id)
+            .map(|light| light.position = position)
+            .is_some()
+    }
+
+    pub fn aim_light(&mut self, id : LightId, direction : cgmath::Vector3<f32>) -> bool {
+
+        self.lights
+            .get_mut(id)
+            .map(|light| light.direction = direction)
+            .is_some()
+    }
+
+    pub fn recolor_light(&mut self, id : LightId, color : [f32; 3], intensity : f32) -> bool {
+
+        self.lights
+            .get_mut(id)
+            .map(|light| {
+
+                light.color = color;
+
+                light.intensity = intensity;
+            })
+            .is_some()
+    }
+
+    pub fn depth_mode(&self) -> texture::DepthMode { self.depth_mode }
+
+    // NOTE: new depth format -> new depth texture + pipeline
+    pub fn set_depth_mode(&mut self, depth_mode : texture::DepthMode) {
+
+        if self.depth_mode == depth_mode {
+
+            return;
+        }
+
+        self.depth_mode = depth_mode;
+
+        self.recreate_scene_pipelines();
+
+        self.recreate_depth_texture();
+    }
+
+    fn recreate_scene_pipelines(&mut self) {
+
+        self.render_pipeline = Self::create_render_pipeline(
+            &self.device,
+            &self.render_pipeline_layout,
+            &self.scene_shader,
+            HdrPipeline::FORMAT,
+            self.depth_mode,
+            self.sample_count,
+        );
+
+        if let Some(skybox) = &mut self.skybox {
+
+            skybox.recreate_pipeline(
+                &self.device,
+                HdrPipeline::FORMAT,
+                self.depth_mode,
+                self.sample_count,
+            );
+        }
+    }
+
+    // NOTE: a shader that fails to compile keeps the last good pipeline, the error goes
+    // to the "Shader Errors" window, as does a change to a shader nothing here rebuilds
+    fn reload_shaders(&mut self) {
+
+        let watcher = match &mut self.shader_watcher {
+            Some(watcher) => watcher,
+            None => return,
+        };
+
+        let changed = watcher.poll();
+
+        for name in changed.iter().filter(|name| *name == "shader.wgsl") {
+
+            let reloaded = watcher.read(name).and_then(|source| {
+
+                hot_reload::compile(&self.device, name, &source, |shader| {
+
+                    Self::create_render_pipeline(
+                        &self.device,
+                        &self.render_pipeline_layout,
+                        shader,
+                        HdrPipeline::FORMAT,
+                        self.depth_mode,
+                        self.sample_count,
+                    )
+                })
             });
 
-            main_rpass.set_vertex_buffer(1, self.instance_buffer.slice(..)); //NOTE: more instances
+            let result = reloaded.map(|(shader, pipeline)| {
+
+                self.scene_shader = shader;
+
+                self.render_pipeline = pipeline;
+            });
+
+            watcher.report(name, result);
+        }
+
+        let mut reloaded = vec!["shader.wgsl", "tonemap.wgsl", "post.wgsl", "shadow.wgsl"];
+
+        if let Some(skybox) = &mut self.skybox {
+
+            reloaded.push("skybox.wgsl");
+
+            skybox.reload_shaders(
+                &self.device,
+                watcher,
+                &changed,
+                HdrPipeline::FORMAT,
+                self.depth_mode,
+                self.sample_count,
+            );
+        }
+
+        self.hdr.reload_shaders(&self.device, watcher, &changed);
+
+        self.post.reload_shaders(&self.device, watcher, &changed);
+
+        self.shadows.reload_shaders(&self.device, watcher, &changed);
+
+        if let Some(depth_view) = &mut self.depth_view {
+
+            reloaded.push("depth_view.wgsl");
+
+            depth_view.reload_shaders(&self.device, watcher, &changed);
+        }
+
+        if let Some(picker) = &mut self.picker {
+
+            reloaded.push("picking.wgsl");
+
+            picker.reload_shaders(&self.device, watcher, &changed);
+        }
+
+        if let Some(culler) = &mut self.culler {
+
+            reloaded.push("cull.wgsl");
+
+            culler.reload_shaders(&self.device, watcher, &changed);
+        }
+
+        // NOTE: e.g. blit.wgsl, or a pass not created yet, those use the include_wgsl! copy
+        for name in changed
+            .iter()
+            .filter(|name| !reloaded.contains(&name.as_str()))
+        {
+
+            watcher.report(
+                name,
+                Err(anyhow::anyhow!(
+                    "{}: no hot reload here, rebuild to apply",
+                    name
+                )),
+            );
+        }
+    }
+
+    // counts usable by the hdr color target and every depth mode
+    fn query_sample_counts(adapter : &wgpu::Adapter, device : &wgpu::Device) -> Vec<u32> {
+
+        let adapter_specific = device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+
+        let mut formats = vec![HdrPipeline::FORMAT];
+
+        formats.extend(texture::DepthMode::ALL.iter().map(|mode| mode.format()));
+
+        texture::supported_sample_counts(&formats, |format| {
+
+            let features = if adapter_specific {
+
+                adapter.get_texture_format_features(format)
+            } else {
+
+                format.describe().guaranteed_format_features
+            };
+
+            // the hdr target is resolved, depth formats never are
+            let resolve = format != HdrPipeline::FORMAT
+                || features
+                    .flags
+                    .contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE);
+
+            wgpu::TextureFormatFeatures {
+                flags : if resolve {
+
+                    features.flags
+                } else {
+
+                    wgpu::TextureFormatFeatureFlags::empty()
+                },
+                ..features
+            }
+        })
+    }
+
+    pub fn sample_count(&self) -> u32 { self.sample_count }
+
+    pub fn supported_sample_counts(&self) -> &[u32] { &self.supported_sample_counts }
+
+    // NOTE: new sample count -> new color/depth targets + pipelines
+    pub fn set_sample_count(&mut self, sample_count : u32) -> anyhow::Result<()> {
+
+        if !self.supported_sample_counts.contains(&sample_count) {
+
+            anyhow::bail!(
+                "{}x msaa is not supported, expected one of {:?}",
+                sample_count,
+                self.supported_sample_counts
+            );
+        }
+
+        if self.sample_count == sample_count {
+
+            return Ok(());
+        }
+
+        self.sample_count = sample_count;
+
+        self.recreate_scene_pipelines();
+
+        self.hdr.set_sample_count(&self.device, sample_count);
+
+        self.recreate_depth_texture();
+
+        // the depth view binds the depth texture as (non) multisampled, rebuild it
+        if self.depth_view.is_some() {
+
+            self.set_depth_view_visible(true);
+        }
+
+        Ok(())
+    }
+
+    // NOTE: needs a cube texture, e.g. Texture::from_cube_images / from_equirectangular
+    pub fn set_skybox(&mut self, texture : texture::Texture) -> anyhow::Result<()> {
+
+        self.skybox = Some(Skybox::new(
+            &self.device,
+            &self.camera_bind_group_layout,
+            texture,
+            HdrPipeline::FORMAT,
+            self.depth_mode,
+            self.sample_count,
+        )?);
+
+        Ok(())
+    }
+
+    pub fn clear_skybox(&mut self) -> Option<texture::Texture> {
+
+        self.skybox.take().map(|skybox| skybox.texture)
+    }
+
+    fn recreate_depth_texture(&mut self) {
+
+        self.depth_texture = texture::Texture::create_depth_texture_multisampled(
+            &self.device,
+            &self.config,
+            self.depth_mode,
+            self.sample_count,
+            "depth_texture",
+        );
+
+        if let Some(depth_view) = &mut self.depth_view {
+
+            depth_view.resize(
+                &self.device,
+                &mut self.renderer,
+                &self.depth_texture,
+                [self.config.width, self.config.height],
+            );
+        }
+    }
+
+    // NOTE: shows the depth buffer in an imgui window
+    pub fn set_depth_view_visible(&mut self, visible : bool) {
+
+        if let Some(depth_view) = self.depth_view.take() {
+
+            self.renderer.textures.remove(depth_view.texture_id);
+        }
+
+        self.depth_view = visible.then(|| {
+
+            DepthView::new(
+                &self.device,
+                &mut self.renderer,
+                &self.depth_texture,
+                [self.config.width, self.config.height],
+            )
+        });
+    }
+
+    // NOTE: scene pass, shared by the surface and the offscreen path
+    fn encode_scene(&mut self, encoder : &mut wgpu::CommandEncoder, view : &wgpu::TextureView) {
+
+        // NOTE: pin the current models, a finished upload may swap them between frames
+        let models = self.models.iter().map(Handle::get).collect::<Vec<_>>();
+
+        let casters = models
+            .iter()
+            .zip(&self.instance_buffers)
+            .filter(|(_, instances)| !instances.is_empty())
+            .filter_map(|(model, instances)| {
+
+                Some(ShadowCaster {
+                    model : model.as_deref()?,
+                    instance_buffer : instances.buffer(),
+                    instances : instances.range(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        self.shadows.encode(encoder, &casters);
+
+        // NOTE: shadow casters stay unculled, they may be outside the view and still cast
+        let gpu_culler = self.culler.as_ref().filter(|_| self.frustum_culling);
+
+        if let Some(culler) = gpu_culler {
+
+            let frustum = Frustum::from_camera(&self.camera);
+
+            for ((model, instances), target) in models
+                .iter()
+                .zip(&self.instance_buffers)
+                .zip(&self.cull_targets)
+            {
+
+                let model = model.as_deref().filter(|_| !instances.is_empty());
+
+                if let Some(model) = model.filter(|_| !target.is_stale(instances)) {
+
+                    culler.encode(&self.queue, encoder, &frustum, model, instances, target);
+                }
+            }
+        }
+
+        // NOTE: scene -> hdr target, tone mapped into `view` below
+        {
+
+            let mut main_rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label : Some("Render Pass"),
+                color_attachments : &[Some(
+                    self.hdr
+                        .color_attachment(wgpu::LoadOp::Clear(self.clear_color)),
+                )],
+                depth_stencil_attachment : Some(
+                    self.depth_mode.attachment(&self.depth_texture.view),
+                ),
+            });
+
+            if let Some(skybox) = &self.skybox {
+
+                skybox.draw(&mut main_rpass, &self.camera_bind_group);
+            }
 
             main_rpass.set_pipeline(&self.render_pipeline);
 
+            main_rpass.set_bind_group(2, &self.lights.bind_group, &[]);
+
+            main_rpass.set_bind_group(3, &self.shadows.bind_group, &[]);
+
             use crate::model::DrawModel;
 
-            let mesh = &self.obj_model.meshes[0];
+            for (i, (model, instances)) in models.iter().zip(&self.instance_buffers).enumerate() {
+
+                let model = match model.as_deref() {
+                    Some(model) if !instances.is_empty() => model,
+                    _ => continue,
+                };
+
+                let target = self
+                    .cull_targets
+                    .get(i)
+                    .filter(|target| gpu_culler.is_some() && !target.is_stale(instances));
+
+                if let Some(target) = target {
+
+                    main_rpass.set_vertex_buffer(1, target.visible_buffer().slice(..));
+
+                    main_rpass.draw_model_indirect(
+                        model,
+                        target.indirect_buffer(),
+                        &self.camera_bind_group,
+                    );
+
+                    continue;
+                }
+
+                // cpu culled copy, the full buffer when culling is off
+                let instances = match self.visible_buffers.get(i) {
+                    Some(visible) if self.frustum_culling && gpu_culler.is_none() => visible,
+                    _ => instances,
+                };
+
+                //NOTE: more instances
+                main_rpass.draw_model_instance_buffer(model, instances, &self.camera_bind_group);
+            }
+        }
+
+        // NOTE: with post effects the tone mapped frame goes through the stack first
+        match self.post.input_view() {
+            Some(post_input) => {
+
+                self.hdr
+                    .process(&self.queue, encoder, post_input, self.tone_mapping);
+
+                self.post.encode(&self.device, &self.queue, encoder, view);
+            }
+            None => self
+                .hdr
+                .process(&self.queue, encoder, view, self.tone_mapping),
+        }
+    }
+
+    // NOTE: headless frame, scene -> offscreen target -> tightly packed rgba rows
+    pub fn render_offscreen(&mut self) -> anyhow::Result<Vec<u8>> {
+
+        // NOTE: taken out while the scene encodes, encode_scene needs the whole State
+        let target = self.offscreen_target.take().ok_or_else(|| {
+
+            anyhow::anyhow!("State renders to a surface, use State::new_headless")
+        })?;
+
+        // NOTE: there is no update before a headless frame, lights added in build or since
+        // the last frame only reach the gpu here
+        self.upload_frame();
+
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label : Some("Offscreen Encoder"),
+            });
+
+        self.encode_scene(&mut encoder, &view);
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let rgba = texture::Texture::read_rgba8(
+            &self.device,
+            &self.queue,
+            &target,
+            self.config.width,
+            self.config.height,
+        );
+
+        self.offscreen_target = Some(target);
+
+        rgba
+    }
+
+    pub fn render_to_png(&mut self, path : impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+
+        let rgba = self.render_offscreen()?;
+
+        texture::Texture::write_png(path.as_ref(), self.config.width, self.config.height, &rgba)
+    }
+
+    // NOTE: headless fly-through, one png per frame (frame_00000.png, ...) at a fixed rate,
+    // returns the number of frames written
+    pub fn render_camera_path(
+        &mut self,
+        path : &CameraPath,
+        fps : f32,
+        dir : impl AsRef<std::path::Path>,
+    ) -> anyhow::Result<usize> {
+
+        // NOTE: an infinite rate would mean endless frames, NaN fails the comparison too
+        anyhow::ensure!(
+            fps.is_finite() && fps > 0.0,
+            "fps must be positive and finite, got {}",
+            fps
+        );
+
+        std::fs::create_dir_all(dir.as_ref())?;
+
+        let frames = (path.duration() * fps).floor() as usize + 1;
+
+        for frame in 0..frames {
+
+            if let Some(key) = path.sample(frame as f32 / fps) {
+
+                key.apply(&mut self.camera);
+            }
 
-            let material = &self.obj_model.materials[mesh.material];
+            self.render_to_png(dir.as_ref().join(format!("frame_{:05}.png", frame)))?;
+        }
+
+        Ok(frames)
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+
+        let (surface, window) = match (&self.surface, &self.window) {
+            (Some(surface), Some(window)) => (surface, window),
+            // NOTE: nothing to present, headless frames go through render_offscreen
+            _ => return Ok(()),
+        };
+
+        // NOTE: imgui timer
+        let delta_s = self.last_frame.elapsed();
+
+        let now = Instant::now();
+
+        let io = self.imgui_context.io_mut();
+
+        io.update_delta_time(delta_s);
+
+        self.last_frame = now;
+
+        // NOTE: imgui ui = frame -> layers -> widgets
+
+        let main_frame = surface.get_current_texture()?;
+
+        self.platform
+            .prepare_frame(io, window)
+            .expect("Failed to prepare frame");
+
+        let imgui_ui = self.imgui_context.frame();
+
+        // NOTE: prepare imgui layers
+
+        let _texture_context = &mut texture::Context {
+            device : &self.device,
+            queue : &self.queue,
+            renderer : &mut self.renderer,
+        };
+
+        // self.imgui_render(texture_context, &mut imgui_ui);
+
+        for _layer in &mut self.layers {
+
+            if let Some(window) = imgui_ui
+                .window("Gallery")
+                .size([512.0, 512.0], imgui::Condition::FirstUseEver)
+                .begin()
+            {
+
+                let _new_imgui_region_size = imgui_ui.content_region_avail();
+
+                window.end();
+            };
+        }
+
+        // NOTE: depth buffer window, a new depth mode applies after this frame
+        let mut requested_depth_mode = None;
+
+        if let Some(depth_view) = &self.depth_view {
+
+            imgui_ui
+                .window("Depth")
+                .size([320.0, 280.0], imgui::Condition::FirstUseEver)
+                .build(|| {
+
+                    for mode in texture::DepthMode::ALL {
+
+                        if imgui_ui.radio_button_bool(mode.name(), mode == self.depth_mode) {
+
+                            requested_depth_mode = Some(mode);
+                        }
+                    }
+
+                    imgui::Image::new(depth_view.texture_id, imgui_ui.content_region_avail())
+                        .build(imgui_ui);
+                });
+        }
+
+        // NOTE: tone mapping window, applied from the next frame
+        let mut tone_mapping = self.tone_mapping;
+
+        imgui_ui
+            .window("Tone Mapping")
+            .size([240.0, 140.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+
+                for operator in ToneMapOperator::ALL {
+
+                    if imgui_ui
+                        .radio_button_bool(operator.name(), operator == tone_mapping.operator)
+                    {
+
+                        tone_mapping.operator = operator;
+                    }
+                }
+
+                imgui_ui.slider("Exposure", 0.05, 8.0, &mut tone_mapping.exposure);
+            });
+
+        // NOTE: msaa window, a new sample count applies after this frame
+        let mut requested_sample_count = None;
+
+        imgui_ui
+            .window("MSAA")
+            .size([160.0, 140.0], imgui::Condition::FirstUseEver)
+            .build(|| {
+                for &count in &self.supported_sample_counts {
+
+                    let label = format!("{}x", count);
+
+                    if imgui_ui.radio_button_bool(&label, count == self.sample_count) {
+
+                        requested_sample_count = Some(count);
+                    }
+                }
+            });
+
+        if let Some(watcher) = &self.shader_watcher {
+
+            watcher.ui(imgui_ui);
+        }
+
+        // NOTE: loading window, only while background loads are running
+        let loading = self.assets.progress();
+
+        if !loading.is_empty() {
+
+            imgui_ui
+                .window("Loading")
+                .size([300.0, 120.0], imgui::Condition::FirstUseEver)
+                .build(|| {
+                    for asset in &loading {
+
+                        let overlay = match &asset.state {
+                            LoadState::Failed(_) => {
+
+                                format!("{} {} (failed)", asset.kind, asset.path)
+                            }
+                            _ => format!("{} {}", asset.kind, asset.path),
+                        };
+
+                        imgui::ProgressBar::new(asset.fraction)
+                            .overlay_text(&overlay)
+                            .build(imgui_ui);
+                    }
+                });
+        }
+
+        imgui_ui
+            .window("Post Processing")
+            .size([280.0, 320.0], imgui::Condition::FirstUseEver)
+            .build(|| self.post.ui(imgui_ui));
+
+        // NOTE: prepare render
+        if self.last_cursor != imgui_ui.mouse_cursor() {
+
+            self.last_cursor = imgui_ui.mouse_cursor();
+
+            self.platform.prepare_render(&imgui_ui, window);
+        }
+
+        let main_view = main_frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
 
-            main_rpass.draw_mesh_instanced(
-                mesh,
-                material,
-                0..self.instances.len() as u32,
-                &self.camera_bind_group,
+        let mut main_encoder =
+            self.device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label : Some("Render Encoder"),
+                });
+
+        self.encode_scene(&mut main_encoder, &main_view);
+
+        if let Some(depth_view) = &self.depth_view {
+
+            depth_view.encode(
+                &self.queue,
+                &mut main_encoder,
+                &self.renderer,
+                &self.camera,
+                self.depth_mode,
             );
+        }
 
-            // NOTE: render imgui
+        // Render pass scope
+        {
+
+            // NOTE: render imgui on top of the scene
+            // single sampled, straight onto the resolved and tone mapped surface
+
+            let mut imgui_rpass = main_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label : Some("Imgui Render Pass"),
+                color_attachments : &[Some(wgpu::RenderPassColorAttachment {
+                    view : &main_view,
+                    resolve_target : None,
+                    ops : wgpu::Operations {
+                        load : wgpu::LoadOp::Load,
+                        store : true,
+                    },
+                })],
+                depth_stencil_attachment : None,
+            });
 
             self.renderer
                 .render(
                     self.imgui_context.render(),
                     &self.queue,
                     &self.device,
-                    &mut main_rpass,
+                    &mut imgui_rpass,
                 )
                 .expect("Render imgui failed");
         }
@@ -661,6 +1766,209 @@ impl State {
 
         main_frame.present();
 
+        if let Some(depth_mode) = requested_depth_mode {
+
+            self.set_depth_mode(depth_mode);
+        }
+
+        if let Some(sample_count) = requested_sample_count {
+
+            self.set_sample_count(sample_count)
+                .expect("only supported sample counts are listed");
+        }
+
+        self.tone_mapping = tone_mapping;
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+    use crate::camera_path::Keyframe;
+
+    #[test]
+
+    fn test_headless_first_frame_lit() {
+
+        // NOTE: needs an adapter, the software one is enough
+        let mut state = match pollster::block_on(State::new_headless(64, 64)) {
+            Ok(state) => state,
+            Err(e) => {
+
+                eprintln!("skipped, {}", e);
+
+                return;
+            }
+        };
+
+        let lit = state.render_offscreen().unwrap();
+
+        let ids = state.lights.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+
+        for id in ids {
+
+            state.remove_light(id);
+        }
+
+        let ambient = state.render_offscreen().unwrap();
+
+        let brightness = |rgba : &[u8]| rgba.iter().map(|&c| c as u32).sum::<u32>();
+
+        // NOTE: without the upload in render_offscreen both frames are ambient only
+        assert!(brightness(&lit) > brightness(&ambient) + 64 * 64);
+    }
+
+    // pixels where `channel` beats both others by a clear margin
+    fn dominant(rgba : &[u8], channel : usize) -> usize {
+
+        rgba.chunks_exact(4)
+            .filter(|p| (0..3).all(|c| c == channel || p[channel] as i32 > p[c] as i32 + 48))
+            .count()
+    }
+
+    fn solid(color : [u8; 4]) -> image::DynamicImage {
+
+        image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(4, 4, image::Rgba(color)))
+    }
+
+    #[test]
+
+    fn test_headless_array_material_and_skybox() {
+
+        let mut state = match pollster::block_on(State::new_headless(64, 64)) {
+            Ok(state) => state,
+            Err(e) => {
+
+                eprintln!("skipped, {}", e);
+
+                return;
+            }
+        };
+
+        let mipmaps = texture::MipmapGenerator::new(&state.device);
+
+        let mut encoder = state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label : None });
+
+        let mut upload = texture::TextureUpload {
+            device : &state.device,
+            queue : &state.queue,
+            encoder : &mut encoder,
+            mipmaps : &mipmaps,
+        };
+
+        let layers = texture::Texture::from_array_images(
+            &mut upload,
+            &[solid([255, 0, 0, 255]), solid([0, 255, 0, 255])],
+            Some("layers"),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            texture::TextureOptions::default(),
+        )
+        .unwrap();
+
+        let sky = texture::Texture::from_cube_images(
+            &mut upload,
+            &vec![solid([0, 0, 255, 255]); 6],
+            Some("sky"),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            texture::TextureOptions::default(),
+        )
+        .unwrap();
+
+        state.queue.submit(Some(encoder.finish()));
+
+        // the cube grid, drawn with layer 1 of the array
+        let cube = state.models[0].loaded_arc().unwrap();
+
+        let defaults = crate::model::MaterialDefaults::new(&state.device, &state.queue).unwrap();
+
+        let mut uniform = crate::model::MaterialUniform::default();
+
+        uniform.layer = 1;
+
+        let material = Material::new(
+            &state.device,
+            state.material_layout(),
+            "layers".to_string(),
+            layers,
+            Default::default(),
+            uniform,
+            &defaults,
+        );
+
+        let meshes = cube
+            .meshes
+            .iter()
+            .map(|mesh| crate::model::Mesh {
+                material : 0,
+                ..mesh.clone()
+            })
+            .collect();
+
+        state.models[0] = Handle::loaded("layers", Model::new(meshes, vec![material]));
+
+        let green = state.render_offscreen().unwrap();
+
+        assert!(dominant(&green, 1) > 64);
+
+        assert_eq!(dominant(&green, 0), 0);
+
+        // nothing is blue yet, the clear color is a dull one
+        assert_eq!(dominant(&green, 2), 0);
+
+        let model = state.models[0].loaded_arc().unwrap();
+
+        let material = &model.materials[0];
+
+        let mut uniform = material.uniform;
+
+        uniform.layer = 0;
+
+        state.queue.write_buffer(
+            &material.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[uniform]),
+        );
+
+        assert!(dominant(&state.render_offscreen().unwrap(), 0) > 64);
+
+        // the sky shows between the cubes
+        state.set_skybox(sky).unwrap();
+
+        assert!(dominant(&state.render_offscreen().unwrap(), 2) > 64);
+    }
+
+    #[test]
+
+    fn test_render_camera_path_fps() {
+
+        let mut state = match pollster::block_on(State::new_headless(16, 16)) {
+            Ok(state) => state,
+            Err(e) => {
+
+                eprintln!("skipped, {}", e);
+
+                return;
+            }
+        };
+
+        let mut path = CameraPath::new(Default::default());
+
+        path.push(Keyframe::from_camera(0.0, &state.camera));
+
+        let dir = std::env::temp_dir().join(format!("camera_path_fps_{}", std::process::id()));
+
+        for fps in [0.0, -1.0, f32::INFINITY, f32::NAN] {
+
+            assert!(state.render_camera_path(&path, fps, &dir).is_err());
+        }
+
+        // rejected before anything is written
+        assert!(!dir.exists());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
-    camera::{Camera, CameraController, CameraUniform},
-    model::Model,
+    camera::{Camera, CameraController, CameraUniform, KeyboardController, Projection},
+    hot_reload::{self, ShaderWatcher},
+    model::{Material, Model},
     resource,
     share::create_empty_texels,
 };
@@ -17,13 +18,17 @@ pub struct Swapchain {
     pub camera_bind_group : wgpu::BindGroup,
     pub texture_bind_group : wgpu::BindGroup,
     pub pipeline : wgpu::RenderPipeline,
+    // kept to rebuild the pipeline, see reload_shaders
+    pipeline_layout : wgpu::PipelineLayout,
+    format : wgpu::TextureFormat,
     pub obj_model : Option<Model>,
     // camera
     pub uniform_buf : wgpu::Buffer,
     pub camera : Camera,
-    pub camera_controller : CameraController,
+    pub camera_controller : KeyboardController,
     pub camera_uniform : CameraUniform,
     pub time : f32,
+    delta_time : f32,
 }
 
 impl Swapchain {
@@ -70,9 +75,10 @@ impl Swapchain {
             fovy : 45.0,
             znear : 0.1,
             zfar : 100.0,
+            projection : Projection::Perspective,
         };
 
-        let camera_controller = CameraController::new(0.2);
+        let camera_controller = KeyboardController::new(10.0);
 
         let mut camera_uniform = CameraUniform::new();
 
@@ -91,11 +97,15 @@ impl Swapchain {
             Self::configure_texture_bind_group(device, &cube_texture_view, &cube_texture_sampler);
 
         // pipeline
-        let pipeline = Self::configure_pipeline(
-            config,
-            device,
-            &[&camera_bind_group_layout, &texture_bind_group_layout],
-        );
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label : None,
+            bind_group_layouts : &[&camera_bind_group_layout, &texture_bind_group_layout],
+            push_constant_ranges : &[],
+        });
+
+        let shader = device.create_shader_module(include_wgsl!("../assets/shaders/cube.wgsl"));
+
+        let pipeline = Self::configure_pipeline(device, &pipeline_layout, &shader, config.format);
 
         // Done
         Swapchain {
@@ -110,7 +120,10 @@ impl Swapchain {
             camera_uniform,
             camera_controller,
             pipeline,
+            pipeline_layout,
+            format : config.format,
             time,
+            delta_time : 0.0,
         }
     }
 
@@ -376,7 +389,7 @@ impl Swapchain {
 
         let shader = device.create_shader_module(include_wgsl!("../assets/shaders/shader.wgsl"));
 
-        use crate::model::ModelVertex;
+        use crate::model::TangentVertex;
 
         let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label : None,
@@ -384,7 +397,7 @@ impl Swapchain {
             vertex : wgpu::VertexState {
                 module : &shader,
                 entry_point : "vs_main",
-                buffers : &[ModelVertex::desc()],
+                buffers : &[TangentVertex::desc()],
             },
             fragment : Some(wgpu::FragmentState {
                 module : &shader,
@@ -418,22 +431,15 @@ impl Swapchain {
     }
 
     fn configure_pipeline(
-        config : &wgpu::SurfaceConfiguration,
         device : &wgpu::Device,
-        bind_group_layouts : &[&wgpu::BindGroupLayout],
+        pipeline_layout : &wgpu::PipelineLayout,
+        shader : &wgpu::ShaderModule,
+        format : wgpu::TextureFormat,
     ) -> wgpu::RenderPipeline {
 
         // Create the vertex and index buffers
         let vertex_size = mem::size_of::<ImVertex>();
 
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label : None,
-            bind_group_layouts,
-            push_constant_ranges : &[],
-        });
-
-        let shader = device.create_shader_module(include_wgsl!("../assets/shaders/cube.wgsl"));
-
         let vertex_buffers = [wgpu::VertexBufferLayout {
             array_stride : vertex_size as wgpu::BufferAddress,
             step_mode : wgpu::VertexStepMode::Vertex,
@@ -453,16 +459,16 @@ impl Swapchain {
 
         let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label : None,
-            layout : Some(&pipeline_layout),
+            layout : Some(pipeline_layout),
             vertex : wgpu::VertexState {
-                module : &shader,
+                module : shader,
                 entry_point : "vs_main",
                 buffers : &vertex_buffers,
             },
             fragment : Some(wgpu::FragmentState {
-                module : &shader,
+                module : shader,
                 entry_point : "fs_main",
-                targets : &[Some(config.format.into())],
+                targets : &[Some(format.into())],
             }),
             primitive : wgpu::PrimitiveState {
                 cull_mode : Some(wgpu::Face::Back),
@@ -476,7 +482,37 @@ impl Swapchain {
         pipeline
     }
 
-    pub fn update(&mut self, delta_time : f32) { self.time += delta_time; }
+    // NOTE: `changed` comes from ShaderWatcher::poll, shared by all swapchains
+    // a broken cube.wgsl keeps the current pipeline and shows up in watcher.ui
+    pub fn reload_shaders(
+        &mut self,
+        device : &wgpu::Device,
+        watcher : &mut ShaderWatcher,
+        changed : &[String],
+    ) {
+
+        for name in changed.iter().filter(|name| *name == "cube.wgsl") {
+
+            let reloaded = watcher.read(name).and_then(|source| {
+
+                hot_reload::compile(device, name, &source, |shader| {
+
+                    Self::configure_pipeline(device, &self.pipeline_layout, shader, self.format)
+                })
+            });
+
+            let result = reloaded.map(|(_, pipeline)| self.pipeline = pipeline);
+
+            watcher.report(name, result);
+        }
+    }
+
+    pub fn update(&mut self, delta_time : f32) {
+
+        self.time += delta_time;
+
+        self.delta_time = delta_time;
+    }
 
     // HACK:
 
@@ -484,7 +520,10 @@ impl Swapchain {
 
         // update camera eye, target, fov,
 
-        self.camera_controller.update_camera(&mut self.camera);
+        self.camera_controller.update_camera(
+            &mut self.camera,
+            std::time::Duration::from_secs_f32(self.delta_time),
+        );
 
         // update v-p matrix from camera eye, target, fov, up
 
@@ -515,35 +554,10 @@ impl Swapchain {
 
     pub async fn load_model(&mut self, device : &wgpu::Device, queue : &wgpu::Queue) {
 
-        let difusse_texture_entries = [
-            wgpu::BindGroupLayoutEntry {
-                binding : 0,
-                visibility : wgpu::ShaderStages::FRAGMENT,
-                ty : wgpu::BindingType::Texture {
-                    multisampled : false,
-                    view_dimension : wgpu::TextureViewDimension::D2,
-                    sample_type : wgpu::TextureSampleType::Float { filterable : true },
-                },
-                count : None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding : 1,
-                visibility : wgpu::ShaderStages::FRAGMENT,
-                // This should match the filterable field of the
-                // corresponding Texture entry above.
-                ty : wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count : None,
-            },
-        ];
-
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries : &difusse_texture_entries,
-                label : Some("texture_bind_group_layout"),
-            });
+        let material_bind_group_layout = Material::bind_group_layout(device);
 
         let obj_model =
-            resource::load_model("cube.obj", &device, &queue, &texture_bind_group_layout)
+            resource::load_model("cube.obj", &device, &queue, &material_bind_group_layout)
                 .await
                 .unwrap();
 
@@ -1,8 +1,11 @@
 use anyhow::*;
 use image::{DynamicImage, GenericImageView, RgbaImage};
+use std::collections::HashMap;
 use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
 
 use crate::share::create_cube_texels;
+use crate::texture_container::{block_count, level_extent, TextureData};
 
 pub struct Context<'a> {
     pub device : &'a wgpu::Device,
@@ -14,6 +17,422 @@ pub struct Texture {
     pub texture : wgpu::Texture,
     pub view : wgpu::TextureView,
     pub sampler : wgpu::Sampler,
+    // Cube / D2Array for the layered constructors, what the bind group layout must declare
+    pub view_dimension : wgpu::TextureViewDimension,
+}
+
+// NOTE: how image textures are sampled, the default keeps a single mip level
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+
+pub struct TextureOptions {
+    // full mip chain generated on the gpu, sampled trilinear
+    pub mipmaps : bool,
+    // 1 = off, otherwise 2, 4, 8 or 16, only used together with mipmaps
+    // NOTE: anything else is rounded down to one of those, wgpu rejects other values
+    pub anisotropy : u8,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+
+        Self {
+            mipmaps : false,
+            anisotropy : 1,
+        }
+    }
+}
+
+impl TextureOptions {
+    // what model textures use, far away instances stop shimmering
+    pub fn mipmapped() -> Self {
+
+        Self {
+            mipmaps : true,
+            anisotropy : 16,
+        }
+    }
+
+    pub fn mip_level_count(&self, width : u32, height : u32) -> u32 {
+
+        if self.mipmaps {
+
+            mip_level_count(width, height)
+        } else {
+
+            1
+        }
+    }
+
+    fn sampler(&self) -> wgpu::SamplerDescriptor<'static> {
+
+        if !self.mipmaps {
+
+            return wgpu::SamplerDescriptor {
+                address_mode_u : wgpu::AddressMode::ClampToEdge,
+                address_mode_v : wgpu::AddressMode::ClampToEdge,
+                address_mode_w : wgpu::AddressMode::ClampToEdge,
+                mag_filter : wgpu::FilterMode::Linear,
+                min_filter : wgpu::FilterMode::Nearest,
+                mipmap_filter : wgpu::FilterMode::Nearest,
+                ..Default::default()
+            };
+        }
+
+        // NOTE: anisotropic filtering requires linear min / mag / mipmap filters
+        wgpu::SamplerDescriptor {
+            address_mode_u : wgpu::AddressMode::ClampToEdge,
+            address_mode_v : wgpu::AddressMode::ClampToEdge,
+            address_mode_w : wgpu::AddressMode::ClampToEdge,
+            mag_filter : wgpu::FilterMode::Linear,
+            min_filter : wgpu::FilterMode::Linear,
+            mipmap_filter : wgpu::FilterMode::Linear,
+            anisotropy_clamp : std::num::NonZeroU8::new(1 << self.anisotropy.clamp(1, 16).ilog2())
+                .filter(|clamp| clamp.get() > 1),
+            ..Default::default()
+        }
+    }
+}
+
+// NOTE: images are uploaded as to_rgba8, any other format would not match the row size
+fn check_rgba8(format : wgpu::TextureFormat) -> Result<()> {
+
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Ok(()),
+        _ => bail!(
+            "images are uploaded as rgba8, {:?} is not supported",
+            format
+        ),
+    }
+}
+
+// levels down to 1x1, log2 of the larger side + 1
+pub fn mip_level_count(width : u32, height : u32) -> u32 {
+
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// MSAA counts every format can render with, 1 is always there
+// `features` is usually Adapter::get_texture_format_features
+pub fn supported_sample_counts(
+    formats : &[wgpu::TextureFormat],
+    features : impl Fn(wgpu::TextureFormat) -> wgpu::TextureFormatFeatures,
+) -> Vec<u32> {
+
+    [1, 2, 4, 8]
+        .into_iter()
+        .filter(|&count| {
+
+            count == 1
+                || formats
+                    .iter()
+                    .all(|&format| features(format).flags.sample_count_supported(count))
+        })
+        .collect()
+}
+
+// NOTE: promoted from example/mipmap, each level is a linear blit of the one above
+// one pipeline per target format, built on first use and reused for every texture after
+pub struct MipmapGenerator {
+    shader : wgpu::ShaderModule,
+    bind_group_layout : wgpu::BindGroupLayout,
+    pipeline_layout : wgpu::PipelineLayout,
+    sampler : wgpu::Sampler,
+    pipelines : Mutex<HashMap<wgpu::TextureFormat, Arc<wgpu::RenderPipeline>>>,
+}
+
+impl MipmapGenerator {
+    pub fn new(device : &wgpu::Device) -> Self {
+
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("../assets/shaders/blit.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label : Some("blit"),
+            entries : &[
+                wgpu::BindGroupLayoutEntry {
+                    binding : 0,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Texture {
+                        sample_type : wgpu::TextureSampleType::Float { filterable : true },
+                        view_dimension : wgpu::TextureViewDimension::D2,
+                        multisampled : false,
+                    },
+                    count : None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding : 1,
+                    visibility : wgpu::ShaderStages::FRAGMENT,
+                    ty : wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count : None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label : Some("blit"),
+            bind_group_layouts : &[&bind_group_layout],
+            push_constant_ranges : &[],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label : Some("mip"),
+            address_mode_u : wgpu::AddressMode::ClampToEdge,
+            address_mode_v : wgpu::AddressMode::ClampToEdge,
+            address_mode_w : wgpu::AddressMode::ClampToEdge,
+            mag_filter : wgpu::FilterMode::Linear,
+            min_filter : wgpu::FilterMode::Linear,
+            mipmap_filter : wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            sampler,
+            pipelines : Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn pipeline(
+        &self,
+        device : &wgpu::Device,
+        format : wgpu::TextureFormat,
+    ) -> Arc<wgpu::RenderPipeline> {
+
+        let mut pipelines = self.pipelines.lock().unwrap();
+
+        let pipeline = pipelines.entry(format).or_insert_with(|| {
+
+            Arc::new(
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label : Some("blit"),
+                    layout : Some(&self.pipeline_layout),
+                    vertex : wgpu::VertexState {
+                        module : &self.shader,
+                        entry_point : "vs_main",
+                        buffers : &[],
+                    },
+                    fragment : Some(wgpu::FragmentState {
+                        module : &self.shader,
+                        entry_point : "fs_main",
+                        targets : &[Some(format.into())],
+                    }),
+                    primitive : wgpu::PrimitiveState {
+                        topology : wgpu::PrimitiveTopology::TriangleList,
+                        ..Default::default()
+                    },
+                    depth_stencil : None,
+                    multisample : wgpu::MultisampleState::default(),
+                    multiview : None,
+                }),
+            )
+        });
+
+        pipeline.clone()
+    }
+
+    // the texture needs RENDER_ATTACHMENT usage and a renderable, filterable format
+    // NOTE: only recorded, level 0 has to be written before `encoder` is submitted
+    pub fn generate(
+        &self,
+        device : &wgpu::Device,
+        encoder : &mut wgpu::CommandEncoder,
+        texture : &wgpu::Texture,
+    ) {
+
+        let mip_count = texture.mip_level_count();
+
+        if mip_count < 2 {
+
+            return;
+        }
+
+        let pipeline = self.pipeline(device, texture.format());
+
+        // NOTE: cube faces and array layers each get their own chain
+        for layer in 0..texture.depth_or_array_layers() {
+
+            let views = (0..mip_count)
+                .map(|mip| {
+
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label : Some("mip"),
+                        format : None,
+                        dimension : Some(wgpu::TextureViewDimension::D2),
+                        aspect : wgpu::TextureAspect::All,
+                        base_mip_level : mip,
+                        mip_level_count : NonZeroU32::new(1),
+                        base_array_layer : layer,
+                        array_layer_count : NonZeroU32::new(1),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for target_mip in 1..mip_count as usize {
+
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout : &self.bind_group_layout,
+                    entries : &[
+                        wgpu::BindGroupEntry {
+                            binding : 0,
+                            resource : wgpu::BindingResource::TextureView(&views[target_mip - 1]),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding : 1,
+                            resource : wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                    ],
+                    label : None,
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label : None,
+                    color_attachments : &[Some(wgpu::RenderPassColorAttachment {
+                        view : &views[target_mip],
+                        resolve_target : None,
+                        ops : wgpu::Operations {
+                            load : wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                            store : true,
+                        },
+                    })],
+                    depth_stencil_attachment : None,
+                });
+
+                rpass.set_pipeline(&pipeline);
+
+                rpass.set_bind_group(0, &bind_group, &[]);
+
+                rpass.draw(0..3, 0..1);
+            }
+        }
+    }
+}
+
+// NOTE: what the mipmapped constructors upload with, the mip blits go into `encoder`
+// and the caller submits it, e.g. once for all textures of a model
+pub struct TextureUpload<'a> {
+    pub device : &'a wgpu::Device,
+    pub queue : &'a wgpu::Queue,
+    pub encoder : &'a mut wgpu::CommandEncoder,
+    pub mipmaps : &'a MipmapGenerator,
+}
+
+// NOTE: world direction through a cube texel, u / v in -1..1 with v pointing down
+// faces follow the wgpu layer order +x, -x, +y, -y, +z, -z
+pub fn cube_face_direction(face : usize, u : f32, v : f32) -> cgmath::Vector3<f32> {
+
+    use cgmath::{InnerSpace, Vector3};
+
+    let direction = match face {
+        0 => Vector3::new(1.0, -v, -u),
+        1 => Vector3::new(-1.0, -v, u),
+        2 => Vector3::new(u, 1.0, v),
+        3 => Vector3::new(u, -1.0, -v),
+        4 => Vector3::new(u, -v, 1.0),
+        _ => Vector3::new(-u, -v, -1.0),
+    };
+
+    direction.normalize()
+}
+
+// bilinear lookup in a longitude / latitude panorama, wraps around horizontally
+fn sample_equirectangular(
+    img : &image::Rgba32FImage,
+    direction : cgmath::Vector3<f32>,
+) -> [f32; 4] {
+
+    use std::f32::consts::PI;
+
+    let (width, height) = img.dimensions();
+
+    let u = 0.5 + direction.z.atan2(direction.x) / (2.0 * PI);
+
+    let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+
+    let x = u * width as f32 - 0.5;
+
+    let y = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+
+    let (x0, y0) = (x.floor(), y.floor());
+
+    let (fx, fy) = (x - x0, y - y0);
+
+    let texel = |x : f32, y : f32| {
+
+        let x = (x as i64).rem_euclid(width as i64) as u32;
+
+        let y = (y as u32).min(height - 1);
+
+        img.get_pixel(x, y).0
+    };
+
+    let (a, b, c, d) = (
+        texel(x0, y0),
+        texel(x0 + 1.0, y0),
+        texel(x0, y0 + 1.0),
+        texel(x0 + 1.0, y0 + 1.0),
+    );
+
+    std::array::from_fn(|i| {
+
+        (a[i] * (1.0 - fx) + b[i] * fx) * (1.0 - fy) + (c[i] * (1.0 - fx) + d[i] * fx) * fy
+    })
+}
+
+// NOTE: six size x size faces of rgba floats, projected from a panorama on the cpu
+pub fn equirectangular_to_cube(img : &image::Rgba32FImage, size : u32) -> Vec<Vec<[f32; 4]>> {
+
+    (0..6)
+        .map(|face| {
+
+            (0..size * size)
+                .map(|i| {
+
+                    let u = 2.0 * ((i % size) as f32 + 0.5) / size as f32 - 1.0;
+
+                    let v = 2.0 * ((i / size) as f32 + 0.5) / size as f32 - 1.0;
+
+                    sample_equirectangular(img, cube_face_direction(face, u, v))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// f32 -> f16 bits, round to nearest, small values flush to zero
+pub fn f16_bits(value : f32) -> u16 {
+
+    let bits = value.to_bits();
+
+    let sign = ((bits >> 16) & 0x8000) as u16;
+
+    let exponent = ((bits >> 23) & 0xff) as i32;
+
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+
+        // inf stays inf, nan stays a (quiet) nan
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+
+    if exponent >= 31 {
+
+        return sign | 0x7c00;
+    }
+
+    if exponent <= 0 {
+
+        return sign;
+    }
+
+    // a rounding carry may overflow into the exponent, which is still correct
+    let half = ((exponent as u32) << 10 | mantissa >> 13) + ((mantissa >> 12) & 1);
+
+    sign | half.min(0x7c00) as u16
 }
 
 // impl Copy for image::DynamicImage {
@@ -21,165 +440,907 @@ pub struct Texture {
 //     fn clone(&mut self) -> image::DynamicImage {}
 // }
 
-impl Texture {
-    pub const DEPTH_FORMAT : wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+// NOTE: copy_texture_to_buffer needs bytes_per_row to be a multiple of
+// wgpu::COPY_BYTES_PER_ROW_ALIGNMENT, so rows are padded on the gpu side
+pub struct BufferDimensions {
+    pub width : usize,
+    pub height : usize,
+    pub unpadded_bytes_per_row : usize,
+    pub padded_bytes_per_row : usize,
+}
+
+impl BufferDimensions {
+    pub fn new(width : usize, height : usize) -> Self {
+
+        let bytes_per_pixel = std::mem::size_of::<u32>();
+
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+
+        let padded_bytes_per_row_padding = (align - unpadded_bytes_per_row % align) % align;
+
+        let padded_bytes_per_row = unpadded_bytes_per_row + padded_bytes_per_row_padding;
+
+        Self {
+            width,
+            height,
+            unpadded_bytes_per_row,
+            padded_bytes_per_row,
+        }
+    }
+
+    pub fn buffer_size(&self) -> wgpu::BufferAddress {
+
+        (self.padded_bytes_per_row * self.height) as wgpu::BufferAddress
+    }
+
+    // strip the row padding, returns tightly packed rows
+    pub fn unpad(&self, padded : &[u8]) -> Vec<u8> {
+
+        padded
+            .chunks(self.padded_bytes_per_row)
+            .take(self.height)
+            .flat_map(|row| &row[..self.unpadded_bytes_per_row])
+            .copied()
+            .collect()
+    }
+}
+
+// NOTE: depth buffer setups, reversed-z keeps more float precision far away
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+
+pub enum DepthMode {
+    #[default]
+    Depth32Float,
+    Depth24PlusStencil8,
+    // Depth32Float cleared to 0, nearer fragments have greater depth
+    ReversedZ,
+}
+
+impl DepthMode {
+    pub const ALL : [DepthMode; 3] = [
+        DepthMode::Depth32Float,
+        DepthMode::Depth24PlusStencil8,
+        DepthMode::ReversedZ,
+    ];
+
+    pub fn name(&self) -> &'static str {
+
+        match self {
+            DepthMode::Depth32Float => "Depth32Float",
+            DepthMode::Depth24PlusStencil8 => "Depth24PlusStencil8",
+            DepthMode::ReversedZ => "Reversed-Z",
+        }
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+
+        match self {
+            DepthMode::Depth32Float | DepthMode::ReversedZ => wgpu::TextureFormat::Depth32Float,
+            DepthMode::Depth24PlusStencil8 => wgpu::TextureFormat::Depth24PlusStencil8,
+        }
+    }
+
+    pub fn is_reversed(&self) -> bool { *self == DepthMode::ReversedZ }
+
+    pub fn has_stencil(&self) -> bool { *self == DepthMode::Depth24PlusStencil8 }
+
+    pub fn compare(&self) -> wgpu::CompareFunction {
+
+        if self.is_reversed() {
+
+            wgpu::CompareFunction::Greater
+        } else {
+
+            wgpu::CompareFunction::Less
+        }
+    }
+
+    // the farthest possible depth
+    pub fn clear_value(&self) -> f32 {
+
+        if self.is_reversed() {
+
+            0.0
+        } else {
+
+            1.0
+        }
+    }
+
+    pub fn depth_stencil_state(&self) -> wgpu::DepthStencilState {
+
+        wgpu::DepthStencilState {
+            format : self.format(),
+            depth_write_enabled : true,
+            depth_compare : self.compare(),
+            stencil : wgpu::StencilState::default(),
+            bias : wgpu::DepthBiasState::default(),
+        }
+    }
+
+    pub fn attachment<'a>(
+        &self,
+        view : &'a wgpu::TextureView,
+    ) -> wgpu::RenderPassDepthStencilAttachment<'a> {
+
+        wgpu::RenderPassDepthStencilAttachment {
+            view,
+            depth_ops : Some(wgpu::Operations {
+                load : wgpu::LoadOp::Clear(self.clear_value()),
+                store : true,
+            }),
+            stencil_ops : self.has_stencil().then_some(wgpu::Operations {
+                load : wgpu::LoadOp::Clear(0),
+                store : true,
+            }),
+        }
+    }
+}
+
+impl Texture {
+    pub const DEPTH_FORMAT : wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    pub const OFFSCREEN_FORMAT : wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    // NOTE: render target for headless rendering, can be copied back to the cpu
+    pub fn create_offscreen_target(
+        device : &wgpu::Device,
+        width : u32,
+        height : u32,
+        label : &str,
+    ) -> wgpu::Texture {
+
+        device.create_texture(&wgpu::TextureDescriptor {
+            label : Some(label),
+            size : wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers : 1,
+            },
+            mip_level_count : 1,
+            sample_count : 1,
+            dimension : wgpu::TextureDimension::D2,
+            format : Self::OFFSCREEN_FORMAT,
+            usage : wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats : &[],
+        })
+    }
+
+    // NOTE: texture -> padded buffer -> map -> tightly packed rgba bytes
+    pub fn read_rgba8(
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        texture : &wgpu::Texture,
+        width : u32,
+        height : u32,
+    ) -> Result<Vec<u8>> {
+
+        let dimensions = BufferDimensions::new(width as usize, height as usize);
+
+        let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label : Some("Readback Buffer"),
+            size : dimensions.buffer_size(),
+            usage : wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation : false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label : Some("Readback Encoder"),
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer : &output_buffer,
+                layout : wgpu::ImageDataLayout {
+                    offset : 0,
+                    bytes_per_row : NonZeroU32::new(dimensions.padded_bytes_per_row as u32),
+                    rows_per_image : None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers : 1,
+            },
+        );
+
+        let submission_index = queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = output_buffer.slice(..);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| {
+
+            sender.send(v).ok();
+        });
+
+        device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission_index));
+
+        receiver.recv()??;
+
+        let rgba = dimensions.unpad(&buffer_slice.get_mapped_range());
+
+        output_buffer.unmap();
+
+        Ok(rgba)
+    }
+
+    pub fn write_png(
+        path : &std::path::Path,
+        width : u32,
+        height : u32,
+        rgba : &[u8],
+    ) -> Result<()> {
+
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        let mut png_encoder = png::Encoder::new(file, width, height);
+
+        png_encoder.set_depth(png::BitDepth::Eight);
+
+        png_encoder.set_color(png::ColorType::Rgba);
+
+        let mut png_writer = png_encoder.write_header()?;
+
+        png_writer.write_image_data(rgba)?;
+
+        Ok(())
+    }
+
+    // 1.
+
+    pub fn create_depth_texture(
+        device : &wgpu::Device,
+        config : &wgpu::SurfaceConfiguration,
+        label : &str,
+    ) -> Self {
+
+        Self::create_depth_texture_with_mode(device, config, DepthMode::default(), label)
+    }
+
+    pub fn create_depth_texture_with_mode(
+        device : &wgpu::Device,
+        config : &wgpu::SurfaceConfiguration,
+        mode : DepthMode,
+        label : &str,
+    ) -> Self {
+
+        Self::create_depth_texture_multisampled(device, config, mode, 1, label)
+    }
+
+    // NOTE: sample_count has to match the color target of the pass
+    pub fn create_depth_texture_multisampled(
+        device : &wgpu::Device,
+        config : &wgpu::SurfaceConfiguration,
+        mode : DepthMode,
+        sample_count : u32,
+        label : &str,
+    ) -> Self {
+
+        let size = wgpu::Extent3d {
+            width : config.width,
+            height : config.height,
+            depth_or_array_layers : 1,
+        };
+
+        let desc = wgpu::TextureDescriptor {
+            label : Some(label),
+            size,
+            mip_level_count : 1,
+            sample_count,
+            dimension : wgpu::TextureDimension::D2,
+            format : mode.format(),
+            usage : wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats : &[],
+        };
+
+        let texture = device.create_texture(&desc);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u : wgpu::AddressMode::ClampToEdge,
+            address_mode_v : wgpu::AddressMode::ClampToEdge,
+            address_mode_w : wgpu::AddressMode::ClampToEdge,
+            mag_filter : wgpu::FilterMode::Linear,
+            min_filter : wgpu::FilterMode::Linear,
+            mipmap_filter : wgpu::FilterMode::Nearest,
+            compare : Some(if mode.is_reversed() {
+
+                wgpu::CompareFunction::GreaterEqual
+            } else {
+
+                wgpu::CompareFunction::LessEqual
+            }),
+            lod_min_clamp : 0.0,
+            lod_max_clamp : 100.0,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            view_dimension : wgpu::TextureViewDimension::D2,
+        }
+    }
+
+    pub fn new_texture(context : &mut Context, size : [f32; 2]) -> imgui_wgpu::Texture {
+
+        // Stores a texture for displaying with imgui::Image(),
+        // also as a texture view for rendering into it
+
+        let texture_config = imgui_wgpu::TextureConfig {
+            size : wgpu::Extent3d {
+                width : size[0] as u32,
+                height : size[1] as u32,
+                ..Default::default()
+            },
+            usage : wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            ..Default::default()
+        };
+
+        let imgui_texture_wrapper =
+            imgui_wgpu::Texture::new(&context.device, &context.renderer, texture_config);
+
+        imgui_texture_wrapper
+    }
+
+    pub fn from_bytes(bytes : &[u8], context : &Context, label : &str) -> Result<Self> {
+
+        let img = image::load_from_memory(bytes)?;
+
+        Self::from_image(context, &img, Some(label))
+    }
+
+    #[allow(dead_code)]
+
+    pub fn from_bytes_with_label(
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        bytes : &[u8],
+        label : &str,
+    ) -> Result<Self> {
+
+        let img = image::load_from_memory(bytes)?;
+
+        Self::from_image_with_label(device, queue, &img, Some(label))
+    }
+
+    // NOTE: 1x1 texture, stands in for materials without an image
+    pub fn from_color(
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        color : [u8; 4],
+        label : &str,
+    ) -> Result<Self> {
+
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, image::Rgba(color)));
+
+        Self::from_image_with_label(device, queue, &img, Some(label))
+    }
+
+    pub fn from_image_with_label(
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        img : &image::DynamicImage,
+        label : Option<&str>,
+    ) -> Result<Self> {
+
+        Self::from_image_with_format(
+            device,
+            queue,
+            img,
+            label,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    // NOTE: normal maps store vectors, they must use a linear (non srgb) format
+    pub fn from_image_with_format(
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        img : &image::DynamicImage,
+        label : Option<&str>,
+        format : wgpu::TextureFormat,
+    ) -> Result<Self> {
+
+        Self::upload_image(device, queue, img, label, format, TextureOptions::default())
+    }
+
+    pub fn from_image_with_options(
+        upload : &mut TextureUpload,
+        img : &image::DynamicImage,
+        label : Option<&str>,
+        format : wgpu::TextureFormat,
+        options : TextureOptions,
+    ) -> Result<Self> {
+
+        let texture = Self::upload_image(upload.device, upload.queue, img, label, format, options)?;
+
+        upload
+            .mipmaps
+            .generate(upload.device, upload.encoder, &texture.texture);
+
+        Ok(texture)
+    }
+
+    // level 0 only, the rest of the chain is left to MipmapGenerator
+    fn upload_image(
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        img : &image::DynamicImage,
+        label : Option<&str>,
+        format : wgpu::TextureFormat,
+        options : TextureOptions,
+    ) -> Result<Self> {
+
+        check_rgba8(format)?;
+
+        let dimensions = img.dimensions();
+
+        let mip_level_count = options.mip_level_count(dimensions.0, dimensions.1);
+
+        // the blit passes render into the lower levels
+        let usage = if mip_level_count > 1 {
+
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT
+        } else {
+
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+        };
+
+        let rgba = img.to_rgba8();
+
+        let size = wgpu::Extent3d {
+            width : dimensions.0,
+            height : dimensions.1,
+            depth_or_array_layers : 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count,
+            sample_count : 1,
+            dimension : wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats : &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect : wgpu::TextureAspect::All,
+                texture : &texture,
+                mip_level : 0,
+                origin : wgpu::Origin3d::ZERO,
+            },
+            &rgba,
+            wgpu::ImageDataLayout {
+                offset : 0,
+                bytes_per_row : NonZeroU32::new(4 * dimensions.0),
+                rows_per_image : NonZeroU32::new(dimensions.1),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&options.sampler());
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+            view_dimension : wgpu::TextureViewDimension::D2,
+        })
+    }
+
+    // NOTE: colors are read as srgb, pass false for normal maps and other data
+    pub fn from_dds(
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        bytes : &[u8],
+        label : Option<&str>,
+        srgb : bool,
+    ) -> Result<Self> {
+
+        let data = TextureData::from_dds(bytes)?.with_srgb(srgb);
+
+        Self::from_texture_data(device, queue, &data, label)
+    }
+
+    pub fn from_ktx2(
+        device : &wgpu::Device,
+        queue : &wgpu::Queue,
+        bytes : &[u8],
+        label : Option<&str>,
+        srgb : bool,
+    ) -> Result<Self> {
 
-    // 1.
+        let data = TextureData::from_ktx2(bytes)?.with_srgb(srgb);
 
-    pub fn create_depth_texture(
+        Self::from_texture_data(device, queue, &data, label)
+    }
+
+    // NOTE: uploads every stored level and layer, compressed formats the device
+    // lacks the feature for are decoded on the cpu first. bc6h has no cpu decoder
+    // and fails without TEXTURE_COMPRESSION_BC
+    pub fn from_texture_data(
         device : &wgpu::Device,
-        config : &wgpu::SurfaceConfiguration,
-        label : &str,
-    ) -> Self {
+        queue : &wgpu::Queue,
+        data : &TextureData,
+        label : Option<&str>,
+    ) -> Result<Self> {
+
+        let decoded;
+
+        let data = if device
+            .features()
+            .contains(data.format.describe().required_features)
+        {
+
+            data
+        } else {
+
+            decoded = data.decompress()?;
+
+            &decoded
+        };
 
         let size = wgpu::Extent3d {
-            width : config.width,
-            height : config.height,
-            depth_or_array_layers : 1,
+            width : data.width,
+            height : data.height,
+            depth_or_array_layers : data.layers,
         };
 
-        let desc = wgpu::TextureDescriptor {
-            label : Some(label),
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
             size,
-            mip_level_count : 1,
+            mip_level_count : data.mip_level_count(),
             sample_count : 1,
             dimension : wgpu::TextureDimension::D2,
-            format : Self::DEPTH_FORMAT,
-            usage : wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats : &[Self::DEPTH_FORMAT],
-        };
+            format : data.format,
+            usage : wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats : &[],
+        });
 
-        let texture = device.create_texture(&desc);
+        let block_size = data.format.describe().block_size as u32;
+
+        for (level, bytes) in data.levels.iter().enumerate() {
+
+            let (width, height) = level_extent(data.width, data.height, level as u32);
+
+            let (blocks_x, blocks_y) = block_count(data.format, width, height);
+
+            // NOTE: compressed copies cover whole blocks, the physical size
+            let extent = wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers : data.layers,
+            }
+            .physical_size(data.format);
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect : wgpu::TextureAspect::All,
+                    texture : &texture,
+                    mip_level : level as u32,
+                    origin : wgpu::Origin3d::ZERO,
+                },
+                bytes,
+                wgpu::ImageDataLayout {
+                    offset : 0,
+                    bytes_per_row : NonZeroU32::new(blocks_x * block_size),
+                    rows_per_image : NonZeroU32::new(blocks_y),
+                },
+                extent,
+            );
+        }
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let dimension = if data.cubemap && data.layers == 6 {
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u : wgpu::AddressMode::ClampToEdge,
-            address_mode_v : wgpu::AddressMode::ClampToEdge,
-            address_mode_w : wgpu::AddressMode::ClampToEdge,
-            mag_filter : wgpu::FilterMode::Linear,
-            min_filter : wgpu::FilterMode::Linear,
-            mipmap_filter : wgpu::FilterMode::Nearest,
-            compare : Some(wgpu::CompareFunction::LessEqual),
-            lod_min_clamp : 0.0,
-            lod_max_clamp : 100.0,
+            wgpu::TextureViewDimension::Cube
+        } else if data.layers > 1 {
+
+            wgpu::TextureViewDimension::D2Array
+        } else {
+
+            wgpu::TextureViewDimension::D2
+        };
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension : Some(dimension),
             ..Default::default()
         });
 
-        Self {
+        let options = if data.mip_level_count() > 1 {
+
+            TextureOptions::mipmapped()
+        } else {
+
+            TextureOptions::default()
+        };
+
+        let sampler = device.create_sampler(&options.sampler());
+
+        Ok(Self {
             texture,
             view,
             sampler,
-        }
+            view_dimension : dimension,
+        })
     }
 
-    pub fn new_texture(context : &mut Context, size : [f32; 2]) -> imgui_wgpu::Texture {
+    // NOTE: faces in +x, -x, +y, -y, +z, -z order, square and all the same size
+    pub fn from_cube_images(
+        upload : &mut TextureUpload,
+        faces : &[image::DynamicImage],
+        label : Option<&str>,
+        format : wgpu::TextureFormat,
+        options : TextureOptions,
+    ) -> Result<Self> {
 
-        // Stores a texture for displaying with imgui::Image(),
-        // also as a texture view for rendering into it
+        if faces.len() != 6 {
 
-        let texture_config = imgui_wgpu::TextureConfig {
-            size : wgpu::Extent3d {
-                width : size[0] as u32,
-                height : size[1] as u32,
-                ..Default::default()
-            },
-            usage : wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            ..Default::default()
-        };
+            bail!("a cubemap needs 6 faces, got {}", faces.len());
+        }
 
-        let imgui_texture_wrapper =
-            imgui_wgpu::Texture::new(&context.device, &context.renderer, texture_config);
+        let (width, height) = faces[0].dimensions();
 
-        imgui_texture_wrapper
+        if width != height {
+
+            bail!("cubemap faces must be square, got {}x{}", width, height);
+        }
+
+        Self::from_image_layers(
+            upload,
+            faces,
+            label,
+            format,
+            options,
+            wgpu::TextureViewDimension::Cube,
+        )
     }
 
-    pub fn from_bytes(bytes : &[u8], context : &Context, label : &str) -> Result<Self> {
+    // one layer per image, materials pick theirs by index
+    pub fn from_array_images(
+        upload : &mut TextureUpload,
+        images : &[image::DynamicImage],
+        label : Option<&str>,
+        format : wgpu::TextureFormat,
+        options : TextureOptions,
+    ) -> Result<Self> {
 
-        let img = image::load_from_memory(bytes)?;
+        if images.is_empty() {
 
-        Self::from_image(context, &img, Some(label))
-    }
+            bail!("a texture array needs at least one image");
+        }
 
-    #[allow(dead_code)]
+        Self::from_image_layers(
+            upload,
+            images,
+            label,
+            format,
+            options,
+            wgpu::TextureViewDimension::D2Array,
+        )
+    }
 
-    pub fn from_bytes_with_label(
-        device : &wgpu::Device,
-        queue : &wgpu::Queue,
-        bytes : &[u8],
-        label : &str,
+    // NOTE: hdr panoramas keep their range in Rgba16Float, ldr ones work too
+    pub fn from_equirectangular(
+        upload : &mut TextureUpload,
+        img : &image::DynamicImage,
+        face_size : u32,
+        label : Option<&str>,
+        options : TextureOptions,
     ) -> Result<Self> {
 
-        let img = image::load_from_memory(bytes)?;
+        let format = wgpu::TextureFormat::Rgba16Float;
 
-        Self::from_image_with_label(device, queue, &img, Some(label))
+        let layers = equirectangular_to_cube(&img.to_rgba32f(), face_size)
+            .into_iter()
+            .map(|face| {
+
+                face.iter()
+                    .flatten()
+                    .flat_map(|c| f16_bits(*c).to_le_bytes())
+                    .collect::<Vec<u8>>()
+            })
+            .collect::<Vec<_>>();
+
+        let desc = Self::layered_descriptor(label, face_size, face_size, 6, format, options);
+
+        Self::from_layers(
+            upload,
+            &desc,
+            &layers,
+            options,
+            wgpu::TextureViewDimension::Cube,
+        )
     }
 
-    pub fn from_image_with_label(
-        device : &wgpu::Device,
-        queue : &wgpu::Queue,
-        img : &image::DynamicImage,
+    fn from_image_layers(
+        upload : &mut TextureUpload,
+        images : &[image::DynamicImage],
         label : Option<&str>,
+        format : wgpu::TextureFormat,
+        options : TextureOptions,
+        view_dimension : wgpu::TextureViewDimension,
     ) -> Result<Self> {
 
-        let dimensions = img.dimensions();
+        check_rgba8(format)?;
 
-        let rgba = img.to_rgba8();
+        let (width, height) = images[0].dimensions();
 
-        let size = wgpu::Extent3d {
-            width : dimensions.0,
-            height : dimensions.1,
-            depth_or_array_layers : 1,
-        };
+        if let Some(img) = images
+            .iter()
+            .find(|img| img.dimensions() != (width, height))
+        {
+
+            bail!(
+                "layer is {:?}, expected {}x{}",
+                img.dimensions(),
+                width,
+                height
+            );
+        }
 
-        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let layers = images
+            .iter()
+            .map(|img| img.to_rgba8().into_raw())
+            .collect::<Vec<_>>();
 
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
+        let desc =
+            Self::layered_descriptor(label, width, height, layers.len() as u32, format, options);
+
+        Self::from_layers(upload, &desc, &layers, options, view_dimension)
+    }
+
+    fn layered_descriptor<'a>(
+        label : Option<&'a str>,
+        width : u32,
+        height : u32,
+        layers : u32,
+        format : wgpu::TextureFormat,
+        options : TextureOptions,
+    ) -> wgpu::TextureDescriptor<'a> {
+
+        let mip_level_count = options.mip_level_count(width, height);
+
+        // the blit passes render into the lower levels
+        let usage = if mip_level_count > 1 {
+
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT
+        } else {
+
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+        };
+
+        wgpu::TextureDescriptor {
             label,
-            size,
-            mip_level_count : 1,
+            size : wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers : layers,
+            },
+            mip_level_count,
             sample_count : 1,
             dimension : wgpu::TextureDimension::D2,
             format,
-            usage : wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            usage,
             view_formats : &[],
-        });
+        }
+    }
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                aspect : wgpu::TextureAspect::All,
-                texture : &texture,
-                mip_level : 0,
-                origin : wgpu::Origin3d::ZERO,
-            },
-            &rgba,
-            wgpu::ImageDataLayout {
-                offset : 0,
-                bytes_per_row : NonZeroU32::new(4 * dimensions.0),
-                rows_per_image : NonZeroU32::new(dimensions.1),
-            },
-            size,
-        );
+    // NOTE: every layer is a tightly packed level 0, the rest of the chain is blitted
+    fn from_layers(
+        upload : &mut TextureUpload,
+        desc : &wgpu::TextureDescriptor,
+        layers : &[Vec<u8>],
+        options : TextureOptions,
+        view_dimension : wgpu::TextureViewDimension,
+    ) -> Result<Self> {
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let texture = upload.device.create_texture(desc);
+
+        let bytes_per_texel = desc.format.describe().block_size as u32;
+
+        for (layer, data) in layers.iter().enumerate() {
+
+            upload.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect : wgpu::TextureAspect::All,
+                    texture : &texture,
+                    mip_level : 0,
+                    origin : wgpu::Origin3d {
+                        x : 0,
+                        y : 0,
+                        z : layer as u32,
+                    },
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset : 0,
+                    bytes_per_row : NonZeroU32::new(bytes_per_texel * desc.size.width),
+                    rows_per_image : NonZeroU32::new(desc.size.height),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers : 1,
+                    ..desc.size
+                },
+            );
+        }
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u : wgpu::AddressMode::ClampToEdge,
-            address_mode_v : wgpu::AddressMode::ClampToEdge,
-            address_mode_w : wgpu::AddressMode::ClampToEdge,
-            mag_filter : wgpu::FilterMode::Linear,
-            min_filter : wgpu::FilterMode::Nearest,
-            mipmap_filter : wgpu::FilterMode::Nearest,
+        upload
+            .mipmaps
+            .generate(upload.device, upload.encoder, &texture);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension : Some(view_dimension),
             ..Default::default()
         });
 
+        let sampler = upload.device.create_sampler(&options.sampler());
+
         Ok(Self {
             texture,
             view,
             sampler,
+            view_dimension,
         })
     }
 
+    // NOTE: texture at `binding`, its sampler at `binding + 1`, filterable formats only
+    pub fn layout_entries(
+        &self,
+        binding : u32,
+        visibility : wgpu::ShaderStages,
+    ) -> [wgpu::BindGroupLayoutEntry; 2] {
+
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty : wgpu::BindingType::Texture {
+                    multisampled : false,
+                    view_dimension : self.view_dimension,
+                    sample_type : wgpu::TextureSampleType::Float { filterable : true },
+                },
+                count : None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding : binding + 1,
+                visibility,
+                ty : wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count : None,
+            },
+        ]
+    }
+
+    pub fn bind_group_entries(&self, binding : u32) -> [wgpu::BindGroupEntry<'_>; 2] {
+
+        [
+            wgpu::BindGroupEntry {
+                binding,
+                resource : wgpu::BindingResource::TextureView(&self.view),
+            },
+            wgpu::BindGroupEntry {
+                binding : binding + 1,
+                resource : wgpu::BindingResource::Sampler(&self.sampler),
+            },
+        ]
+    }
+
     pub fn recreate_image(
         context : &mut Context,
         texture_size : u32,
@@ -400,6 +1561,7 @@ impl Texture {
             texture,
             view,
             sampler,
+            view_dimension : wgpu::TextureViewDimension::D2,
         })
     }
 }
@@ -424,4 +1586,158 @@ mod test {
 
         assert!(Some(size.height) != None);
     }
+
+    #[test]
+
+    pub fn test_supported_sample_counts() {
+
+        let formats = [
+            wgpu::TextureFormat::Rgba16Float,
+            wgpu::TextureFormat::Depth32Float,
+        ];
+
+        let guaranteed = supported_sample_counts(&formats, |format| {
+
+            format.describe().guaranteed_format_features
+        });
+
+        assert_eq!(guaranteed, vec![1, 4]);
+
+        let none = supported_sample_counts(&formats, |_| wgpu::TextureFormatFeatures {
+            allowed_usages : wgpu::TextureUsages::empty(),
+            flags : wgpu::TextureFormatFeatureFlags::empty(),
+        });
+
+        assert_eq!(none, vec![1]);
+    }
+
+    #[test]
+
+    pub fn test_anisotropy_clamp() {
+
+        let clamp = |anisotropy| {
+
+            TextureOptions {
+                mipmaps : true,
+                anisotropy,
+            }
+            .sampler()
+            .anisotropy_clamp
+            .map(|clamp| clamp.get())
+        };
+
+        assert_eq!(clamp(0), None);
+
+        assert_eq!(clamp(1), None);
+
+        assert_eq!(clamp(3), Some(2));
+
+        assert_eq!(clamp(5), Some(4));
+
+        assert_eq!(clamp(12), Some(8));
+
+        assert_eq!(clamp(16), Some(16));
+
+        assert_eq!(clamp(255), Some(16));
+    }
+
+    #[test]
+
+    pub fn test_check_rgba8() {
+
+        assert!(check_rgba8(wgpu::TextureFormat::Rgba8UnormSrgb).is_ok());
+
+        assert!(check_rgba8(wgpu::TextureFormat::Rgba8Unorm).is_ok());
+
+        assert!(check_rgba8(wgpu::TextureFormat::Rgba16Float).is_err());
+
+        assert!(check_rgba8(wgpu::TextureFormat::Bgra8UnormSrgb).is_err());
+    }
+
+    #[test]
+
+    pub fn test_mip_level_count() {
+
+        assert_eq!(mip_level_count(1, 1), 1);
+
+        assert_eq!(mip_level_count(256, 256), 9);
+
+        // non power of two sizes round down at every level
+        assert_eq!(mip_level_count(300, 17), 9);
+
+        assert_eq!(TextureOptions::default().mip_level_count(256, 256), 1);
+
+        assert_eq!(TextureOptions::mipmapped().mip_level_count(256, 1), 9);
+    }
+
+    #[test]
+
+    pub fn test_f16_bits() {
+
+        assert_eq!(f16_bits(0.0), 0);
+
+        assert_eq!(f16_bits(1.0), 0x3c00);
+
+        assert_eq!(f16_bits(-2.0), 0xc000);
+
+        assert_eq!(f16_bits(0.5), 0x3800);
+
+        assert_eq!(f16_bits(1e6), 0x7c00);
+
+        assert_eq!(f16_bits(65504.0), 0x7bff);
+    }
+
+    #[test]
+
+    pub fn test_equirectangular_to_cube() {
+
+        use cgmath::InnerSpace;
+
+        // face centers look straight down their axis
+        assert!((cube_face_direction(0, 0.0, 0.0) - cgmath::Vector3::unit_x()).magnitude() < 1e-6);
+
+        assert!((cube_face_direction(3, 0.0, 0.0) + cgmath::Vector3::unit_y()).magnitude() < 1e-6);
+
+        // top half of the panorama is sky, bottom half is ground
+        let panorama = image::Rgba32FImage::from_fn(8, 4, |_, y| {
+            if y < 2 {
+
+                image::Rgba([0.0, 0.0, 4.0, 1.0])
+            } else {
+
+                image::Rgba([1.0, 0.5, 0.0, 1.0])
+            }
+        });
+
+        let faces = equirectangular_to_cube(&panorama, 4);
+
+        assert_eq!(faces.len(), 6);
+
+        assert!(faces[2].iter().all(|c| *c == [0.0, 0.0, 4.0, 1.0]));
+
+        assert!(faces[3].iter().all(|c| *c == [1.0, 0.5, 0.0, 1.0]));
+    }
+
+    #[test]
+
+    pub fn test_unpad_rows() {
+
+        let dimensions = BufferDimensions::new(3, 2);
+
+        assert_eq!(dimensions.padded_bytes_per_row, 256);
+
+        let mut padded = vec![0u8; dimensions.buffer_size() as usize];
+
+        padded[..12].fill(1);
+
+        padded[256..268].fill(2);
+
+        let rgba = dimensions.unpad(&padded);
+
+        assert_eq!(rgba.len(), 3 * 2 * 4);
+
+        assert!(rgba[..12].iter().all(|&b| b == 1));
+
+        assert!(rgba[12..].iter().all(|&b| b == 2));
+    }
 }
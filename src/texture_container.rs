@@ -0,0 +1,1736 @@
+// NOTE: DDS / KTX2 containers, pre-built mips and array layers in their stored format
+// block compressed levels go to the gpu as is, the cpu decoders cover devices without the feature
+
+use anyhow::*;
+
+const DDS_MAGIC : &[u8; 4] = b"DDS ";
+
+const KTX2_IDENTIFIER : [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+// dds pixel format flags
+const DDPF_ALPHAPIXELS : u32 = 0x1;
+
+const DDPF_FOURCC : u32 = 0x4;
+
+const DDPF_RGB : u32 = 0x40;
+
+const DDSCAPS2_CUBEMAP : u32 = 0x200;
+
+const DDSCAPS2_VOLUME : u32 = 0x200000;
+
+const DDS_RESOURCE_MISC_TEXTURECUBE : u32 = 0x4;
+
+const DDS_DIMENSION_TEXTURE3D : u32 = 4;
+
+// NOTE: well past what any adapter samples, keeps a bad header from sizing huge allocations
+const MAX_DIMENSION : u32 = 1 << 14;
+
+// same block order in DXGI and Vulkan, unorm / srgb pairs
+const ASTC_BLOCKS : [wgpu::AstcBlock; 14] = [
+    wgpu::AstcBlock::B4x4,
+    wgpu::AstcBlock::B5x4,
+    wgpu::AstcBlock::B5x5,
+    wgpu::AstcBlock::B6x5,
+    wgpu::AstcBlock::B6x6,
+    wgpu::AstcBlock::B8x5,
+    wgpu::AstcBlock::B8x6,
+    wgpu::AstcBlock::B8x8,
+    wgpu::AstcBlock::B10x5,
+    wgpu::AstcBlock::B10x6,
+    wgpu::AstcBlock::B10x8,
+    wgpu::AstcBlock::B10x10,
+    wgpu::AstcBlock::B12x10,
+    wgpu::AstcBlock::B12x12,
+];
+
+fn astc(index : u32, srgb : bool) -> Option<wgpu::TextureFormat> {
+
+    let block = *ASTC_BLOCKS.get(index as usize)?;
+
+    let channel = if srgb {
+
+        wgpu::AstcChannel::UnormSrgb
+    } else {
+
+        wgpu::AstcChannel::Unorm
+    };
+
+    Some(wgpu::TextureFormat::Astc { block, channel })
+}
+
+fn u32_at(bytes : &[u8], offset : usize) -> Result<u32> {
+
+    slice(bytes, offset, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn u64_at(bytes : &[u8], offset : usize) -> Result<u64> {
+
+    Ok(u32_at(bytes, offset)? as u64 | (u32_at(bytes, offset + 4)? as u64) << 32)
+}
+
+fn slice(bytes : &[u8], offset : usize, len : usize) -> Result<&[u8]> {
+
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or_else(|| {
+            anyhow!(
+                "texture container truncated at byte {}",
+                offset.saturating_add(len)
+            )
+        })
+}
+
+// bytes of one layer of a level, whole blocks in both directions
+pub fn level_size(format : wgpu::TextureFormat, width : u32, height : u32) -> usize {
+
+    let (blocks_x, blocks_y) = block_count(format, width, height);
+
+    (blocks_x * blocks_y) as usize * format.describe().block_size as usize
+}
+
+pub fn block_count(format : wgpu::TextureFormat, width : u32, height : u32) -> (u32, u32) {
+
+    let (bw, bh) = format.describe().block_dimensions;
+
+    let (bw, bh) = (bw as u32, bh as u32);
+
+    (width.div_ceil(bw), height.div_ceil(bh))
+}
+
+pub fn level_extent(width : u32, height : u32, level : u32) -> (u32, u32) {
+
+    let shrink = |size : u32| size.checked_shr(level).unwrap_or(0).max(1);
+
+    (shrink(width), shrink(height))
+}
+
+// every level of every layer
+fn stored_size(
+    format : wgpu::TextureFormat,
+    width : u32,
+    height : u32,
+    levels : u32,
+    layers : u32,
+) -> usize {
+
+    (0..levels)
+        .map(|level| {
+
+            let (w, h) = level_extent(width, height, level);
+
+            level_size(format, w, h)
+        })
+        .sum::<usize>()
+        .saturating_mul(layers as usize)
+}
+
+// header values are only trusted after this, the level count is capped at a full chain
+fn check_extent(width : u32, height : u32, levels : u32) -> Result<u32> {
+
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+
+        bail!("unsupported texture size {}x{}", width, height);
+    }
+
+    Ok(levels.min(crate::texture::mip_level_count(width, height)))
+}
+
+fn check_layers(layers : u32, faces : u32) -> Result<u32> {
+
+    layers
+        .checked_mul(faces)
+        .ok_or_else(|| anyhow!("{} layers of {} faces overflow", layers, faces))
+}
+
+fn dxgi_format(dxgi : u32) -> Option<wgpu::TextureFormat> {
+
+    use wgpu::TextureFormat::*;
+
+    let format = match dxgi {
+        2 => Rgba32Float,
+        10 => Rgba16Float,
+        28 => Rgba8Unorm,
+        29 => Rgba8UnormSrgb,
+        87 => Bgra8Unorm,
+        91 => Bgra8UnormSrgb,
+        // BC1_TYPELESS, see from_dds
+        70 | 71 => Bc1RgbaUnorm,
+        72 => Bc1RgbaUnormSrgb,
+        74 => Bc2RgbaUnorm,
+        75 => Bc2RgbaUnormSrgb,
+        77 => Bc3RgbaUnorm,
+        78 => Bc3RgbaUnormSrgb,
+        80 => Bc4RUnorm,
+        81 => Bc4RSnorm,
+        83 => Bc5RgUnorm,
+        84 => Bc5RgSnorm,
+        95 => Bc6hRgbUfloat,
+        96 => Bc6hRgbSfloat,
+        98 => Bc7RgbaUnorm,
+        99 => Bc7RgbaUnormSrgb,
+        // NOTE: ASTC_4X4_UNORM = 134, every block size takes typeless / unorm / srgb / reserved
+        134..=189 if (dxgi - 134) % 4 < 2 => return astc((dxgi - 134) / 4, (dxgi - 134) % 4 == 1),
+        _ => return None,
+    };
+
+    Some(format)
+}
+
+fn fourcc_format(fourcc : &[u8]) -> Option<wgpu::TextureFormat> {
+
+    use wgpu::TextureFormat::*;
+
+    let format = match fourcc {
+        b"DXT1" => Bc1RgbaUnorm,
+        b"DXT2" | b"DXT3" => Bc2RgbaUnorm,
+        b"DXT4" | b"DXT5" => Bc3RgbaUnorm,
+        b"ATI1" | b"BC4U" => Bc4RUnorm,
+        b"BC4S" => Bc4RSnorm,
+        b"ATI2" | b"BC5U" => Bc5RgUnorm,
+        b"BC5S" => Bc5RgSnorm,
+        // not standard, written by some encoders, etc1 is a subset of etc2
+        b"ETC1" | b"ETC2" => Etc2Rgb8Unorm,
+        _ => return None,
+    };
+
+    Some(format)
+}
+
+fn vk_format(vk : u32) -> Option<wgpu::TextureFormat> {
+
+    use wgpu::TextureFormat::*;
+
+    let format = match vk {
+        37 => Rgba8Unorm,
+        43 => Rgba8UnormSrgb,
+        44 => Bgra8Unorm,
+        50 => Bgra8UnormSrgb,
+        97 => Rgba16Float,
+        109 => Rgba32Float,
+        131 | 133 => Bc1RgbaUnorm,
+        132 | 134 => Bc1RgbaUnormSrgb,
+        135 => Bc2RgbaUnorm,
+        136 => Bc2RgbaUnormSrgb,
+        137 => Bc3RgbaUnorm,
+        138 => Bc3RgbaUnormSrgb,
+        139 => Bc4RUnorm,
+        140 => Bc4RSnorm,
+        141 => Bc5RgUnorm,
+        142 => Bc5RgSnorm,
+        143 => Bc6hRgbUfloat,
+        144 => Bc6hRgbSfloat,
+        145 => Bc7RgbaUnorm,
+        146 => Bc7RgbaUnormSrgb,
+        147 => Etc2Rgb8Unorm,
+        148 => Etc2Rgb8UnormSrgb,
+        149 => Etc2Rgb8A1Unorm,
+        150 => Etc2Rgb8A1UnormSrgb,
+        151 => Etc2Rgba8Unorm,
+        152 => Etc2Rgba8UnormSrgb,
+        153 => EacR11Unorm,
+        154 => EacR11Snorm,
+        155 => EacRg11Unorm,
+        156 => EacRg11Snorm,
+        157..=184 => return astc((vk - 157) / 2, (vk - 157) % 2 == 1),
+        _ => return None,
+    };
+
+    Some(format)
+}
+
+// NOTE: decoded container, every level keeps all of its layers back to back
+// which is the layout queue.write_texture expects for an array texture
+#[derive(Debug, Clone, PartialEq)]
+
+pub struct TextureData {
+    pub format : wgpu::TextureFormat,
+    pub width : u32,
+    pub height : u32,
+    // array layers, 6 per cube
+    pub layers : u32,
+    pub cubemap : bool,
+    // levels[0] is the full size image
+    pub levels : Vec<Vec<u8>>,
+}
+
+impl TextureData {
+    pub fn mip_level_count(&self) -> u32 { self.levels.len() as u32 }
+
+    pub fn from_dds(bytes : &[u8]) -> Result<Self> {
+
+        if bytes.get(..4) != Some(DDS_MAGIC.as_slice()) {
+
+            bail!("not a dds file");
+        }
+
+        let height = u32_at(bytes, 12)?;
+
+        let width = u32_at(bytes, 16)?;
+
+        let mip_count = check_extent(width, height, u32_at(bytes, 28)?.max(1))?;
+
+        let pf_flags = u32_at(bytes, 80)?;
+
+        let fourcc = slice(bytes, 84, 4)?;
+
+        let caps2 = u32_at(bytes, 112)?;
+
+        if caps2 & DDSCAPS2_VOLUME != 0 {
+
+            bail!("volume dds textures are not supported");
+        }
+
+        let mut offset = 128;
+
+        let mut cubemap = caps2 & DDSCAPS2_CUBEMAP != 0;
+
+        let mut layers = 1;
+
+        let mut dxgi = None;
+
+        let mut format = if pf_flags & DDPF_FOURCC != 0 && fourcc == b"DX10" {
+
+            let code = u32_at(bytes, 128)?;
+
+            dxgi = Some(code);
+
+            if u32_at(bytes, 132)? == DDS_DIMENSION_TEXTURE3D {
+
+                bail!("volume dds textures are not supported");
+            }
+
+            cubemap |= u32_at(bytes, 136)? & DDS_RESOURCE_MISC_TEXTURECUBE != 0;
+
+            layers = u32_at(bytes, 140)?.max(1);
+
+            offset += 20;
+
+            dxgi_format(code).ok_or_else(|| anyhow!("unsupported dxgi format {}", code))?
+        } else if pf_flags & DDPF_FOURCC != 0 {
+
+            fourcc_format(fourcc).ok_or_else(|| {
+
+                anyhow!(
+                    "unsupported dds fourcc {:?}",
+                    String::from_utf8_lossy(fourcc)
+                )
+            })?
+        } else if pf_flags & DDPF_RGB != 0 && u32_at(bytes, 88)? == 32 {
+
+            // 8 bit channels, the red mask tells rgba from bgra
+            match (u32_at(bytes, 92)?, pf_flags & DDPF_ALPHAPIXELS != 0) {
+                (0x00ff0000, true) => wgpu::TextureFormat::Bgra8Unorm,
+                (0x000000ff, true) => wgpu::TextureFormat::Rgba8Unorm,
+                (mask, _) => bail!("unsupported dds rgb layout, red mask {:#x}", mask),
+            }
+        } else {
+
+            bail!("unsupported dds pixel format, flags {:#x}", pf_flags);
+        };
+
+        if cubemap {
+
+            layers = check_layers(layers, 6)?;
+        }
+
+        // NOTE: dxgi had no astc codes at first, some tools write BC1_TYPELESS in front of
+        // astc 4x4 data (the example skybox astc.dds is one). the payload size tells them apart
+        let astc_4x4 = astc(0, false).unwrap();
+
+        let payload = bytes.len().checked_sub(offset);
+
+        if dxgi == Some(70)
+            && payload == Some(stored_size(astc_4x4, width, height, mip_count, layers))
+        {
+
+            format = astc_4x4;
+        }
+
+        // NOTE: dds stores every layer with its whole mip chain, regroup by level
+        let mut levels = vec![Vec::new(); mip_count as usize];
+
+        for _ in 0..layers {
+
+            for (level, data) in levels.iter_mut().enumerate() {
+
+                let (w, h) = level_extent(width, height, level as u32);
+
+                let size = level_size(format, w, h);
+
+                data.extend_from_slice(slice(bytes, offset, size)?);
+
+                offset += size;
+            }
+        }
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            layers,
+            cubemap,
+            levels,
+        })
+    }
+
+    pub fn from_ktx2(bytes : &[u8]) -> Result<Self> {
+
+        if bytes.get(..12) != Some(KTX2_IDENTIFIER.as_slice()) {
+
+            bail!("not a ktx2 file");
+        }
+
+        let vk = u32_at(bytes, 12)?;
+
+        let width = u32_at(bytes, 20)?;
+
+        let height = u32_at(bytes, 24)?.max(1);
+
+        let depth = u32_at(bytes, 28)?;
+
+        let layer_count = u32_at(bytes, 32)?.max(1);
+
+        let faces = u32_at(bytes, 36)?.max(1);
+
+        // 0 asks the loader to generate the mips, only the base level is stored
+        let level_count = check_extent(width, height, u32_at(bytes, 40)?.max(1))?;
+
+        let supercompression = u32_at(bytes, 44)?;
+
+        if depth > 1 {
+
+            bail!("volume ktx2 textures are not supported");
+        }
+
+        if supercompression != 0 {
+
+            bail!(
+                "supercompressed ktx2 (scheme {}) is not supported",
+                supercompression
+            );
+        }
+
+        if faces != 1 && faces != 6 {
+
+            bail!("ktx2 with {} faces, expected 1 or 6", faces);
+        }
+
+        let format = vk_format(vk).ok_or_else(|| anyhow!("unsupported vk format {}", vk))?;
+
+        let layers = check_layers(layer_count, faces)?;
+
+        // NOTE: the level index follows the fixed header, 3 u64 per level
+        let levels = (0..level_count)
+            .map(|level| {
+
+                let entry = 80 + level as usize * 24;
+
+                let offset = u64_at(bytes, entry)? as usize;
+
+                let length = u64_at(bytes, entry + 8)? as usize;
+
+                let (w, h) = level_extent(width, height, level);
+
+                let expected = level_size(format, w, h)
+                    .checked_mul(layers as usize)
+                    .ok_or_else(|| anyhow!("ktx2 level {} is too large", level))?;
+
+                if length != expected {
+
+                    bail!(
+                        "ktx2 level {} is {} bytes, expected {}",
+                        level,
+                        length,
+                        expected
+                    );
+                }
+
+                Ok(slice(bytes, offset, length)?.to_vec())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            layers,
+            cubemap : faces == 6,
+            levels,
+        })
+    }
+
+    // NOTE: containers rarely say whether colors are srgb, model textures and skyboxes are
+    pub fn with_srgb(mut self, srgb : bool) -> Self {
+
+        self.format = if srgb {
+
+            self.format.add_srgb_suffix()
+        } else {
+
+            self.format.remove_srgb_suffix()
+        };
+
+        self
+    }
+
+    // NOTE: cpu fallback, decodes every level and layer into rgba8, snorm formats into
+    // rgba8 snorm. bc6h is hdr and has no decoder here
+    pub fn decompress(&self) -> Result<Self> {
+
+        use wgpu::TextureFormat::*;
+
+        type Decode = Box<dyn Fn(&[u8], &mut [[u8; 4]])>;
+
+        // the bc / etc decoders all work on 4 x 4 blocks
+        let block4 = |decode : fn(&[u8], &mut [[u8; 4]; 16])| -> Decode {
+
+            Box::new(move |block, out| decode(block, out.try_into().unwrap()))
+        };
+
+        let describe = self.format.describe();
+
+        let (bw, bh) = (
+            describe.block_dimensions.0 as u32,
+            describe.block_dimensions.1 as u32,
+        );
+
+        let decode = match self.format {
+            Bc1RgbaUnorm | Bc1RgbaUnormSrgb => block4(|b, out| decode_bc1(b, out, true)),
+            Bc2RgbaUnorm | Bc2RgbaUnormSrgb => block4(decode_bc2),
+            Bc3RgbaUnorm | Bc3RgbaUnormSrgb => block4(decode_bc3),
+            Bc4RUnorm => block4(|b, out| decode_bc4(b, out, false)),
+            Bc4RSnorm => block4(|b, out| decode_bc4(b, out, true)),
+            Bc5RgUnorm => block4(|b, out| decode_bc5(b, out, false)),
+            Bc5RgSnorm => block4(|b, out| decode_bc5(b, out, true)),
+            Bc7RgbaUnorm | Bc7RgbaUnormSrgb => block4(decode_bc7),
+            Etc2Rgb8Unorm | Etc2Rgb8UnormSrgb => block4(|b, out| decode_etc2(b, out, false)),
+            Etc2Rgb8A1Unorm | Etc2Rgb8A1UnormSrgb => block4(|b, out| decode_etc2(b, out, true)),
+            Etc2Rgba8Unorm | Etc2Rgba8UnormSrgb => block4(decode_etc2_eac),
+            EacR11Unorm => block4(|b, out| decode_eac11(b, out, false, 1)),
+            EacR11Snorm => block4(|b, out| decode_eac11(b, out, true, 1)),
+            EacRg11Unorm => block4(|b, out| decode_eac11(b, out, false, 2)),
+            EacRg11Snorm => block4(|b, out| decode_eac11(b, out, true, 2)),
+            Astc { .. } => {
+
+                let srgb = describe.srgb;
+
+                Box::new(move |block : &[u8], out : &mut [[u8; 4]]| {
+
+                    crate::astc::decode_block(block, bw as usize, bh as usize, srgb, out)
+                })
+            }
+            format => bail!("no cpu decoder for {:?}", format),
+        };
+
+        let block_size = describe.block_size as usize;
+
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+
+                let (w, h) = level_extent(self.width, self.height, level as u32);
+
+                let (blocks_x, _) = block_count(self.format, w, h);
+
+                let mut rgba = vec![0u8; (w * h * 4) as usize * self.layers as usize];
+
+                let mut texels = vec![[0u8; 4]; (bw * bh) as usize];
+
+                for (layer, blocks) in data.chunks_exact(level_size(self.format, w, h)).enumerate()
+                {
+
+                    let image = &mut rgba[(w * h * 4) as usize * layer..];
+
+                    for (i, block) in blocks.chunks_exact(block_size).enumerate() {
+
+                        decode(block, &mut texels);
+
+                        let (bx, by) = (i as u32 % blocks_x, i as u32 / blocks_x);
+
+                        // edge blocks are clipped to the level size
+                        for y in 0..bh.min(h - by * bh) {
+
+                            for x in 0..bw.min(w - bx * bw) {
+
+                                let p = (((by * bh + y) * w + bx * bw + x) * 4) as usize;
+
+                                image[p..p + 4].copy_from_slice(&texels[(y * bw + x) as usize]);
+                            }
+                        }
+                    }
+                }
+
+                rgba
+            })
+            .collect();
+
+        let format = match self.format {
+            Bc4RSnorm | Bc5RgSnorm | EacR11Snorm | EacRg11Snorm => Rgba8Snorm,
+            _ if describe.srgb => Rgba8UnormSrgb,
+            _ => Rgba8Unorm,
+        };
+
+        Ok(Self {
+            format,
+            levels,
+            ..*self
+        })
+    }
+}
+
+// 565 to 888, the top bits are repeated into the low ones
+fn rgb565(c : u16) -> [u8; 3] {
+
+    let (r, g, b) = ((c >> 11) & 31, (c >> 5) & 63, c & 31);
+
+    [
+        (r << 3 | r >> 2) as u8,
+        (g << 2 | g >> 4) as u8,
+        (b << 3 | b >> 2) as u8,
+    ]
+}
+
+fn mix(a : [u8; 3], b : [u8; 3], wa : u32, wb : u32) -> [u8; 3] {
+
+    let f = |i : usize| ((a[i] as u32 * wa + b[i] as u32 * wb) / (wa + wb)) as u8;
+
+    [f(0), f(1), f(2)]
+}
+
+// NOTE: texels are row major, bc color indices are 2 bits each from the lsb
+// only bc1 itself has the 3 color + transparent mode
+fn decode_bc1(block : &[u8], out : &mut [[u8; 4]; 16], punchthrough : bool) {
+
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+
+    let (a, b) = (rgb565(c0), rgb565(c1));
+
+    let palette = if c0 > c1 || !punchthrough {
+
+        [a, b, mix(a, b, 2, 1), mix(a, b, 1, 2)]
+    } else {
+
+        [a, b, mix(a, b, 1, 1), [0, 0, 0]]
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    for (i, texel) in out.iter_mut().enumerate() {
+
+        let index = (indices >> (i * 2)) as usize & 3;
+
+        let [r, g, b] = palette[index];
+
+        let alpha = if punchthrough && c0 <= c1 && index == 3 {
+
+            0
+        } else {
+
+            255
+        };
+
+        *texel = [r, g, b, alpha];
+    }
+}
+
+fn decode_bc2(block : &[u8], out : &mut [[u8; 4]; 16]) {
+
+    decode_bc1(&block[8..], out, false);
+
+    for (i, texel) in out.iter_mut().enumerate() {
+
+        texel[3] = (block[i / 2] >> ((i % 2) * 4) & 15) * 17;
+    }
+}
+
+// bc3 alpha / bc4 / bc5 channel, 2 endpoints + 3 bit indices
+// NOTE: snorm endpoints are signed bytes with -128 read as -127, texels stay in that encoding
+fn decode_bc4_channel(block : &[u8], out : &mut [[u8; 4]; 16], channel : usize, signed : bool) {
+
+    let (a0, a1, min, max) = if signed {
+
+        let end = |b : u8| (b as i8 as i32).max(-127);
+
+        (end(block[0]), end(block[1]), -127, 127)
+    } else {
+
+        (block[0] as i32, block[1] as i32, 0, 255)
+    };
+
+    let palette : [i32; 8] = if a0 > a1 {
+
+        let f = |i : i32| ((7 - i) * a0 + i * a1) / 7;
+
+        [a0, a1, f(1), f(2), f(3), f(4), f(5), f(6)]
+    } else {
+
+        let f = |i : i32| ((5 - i) * a0 + i * a1) / 5;
+
+        [a0, a1, f(1), f(2), f(3), f(4), min, max]
+    };
+
+    let mut bits = [0u8; 8];
+
+    bits[..6].copy_from_slice(&block[2..8]);
+
+    let indices = u64::from_le_bytes(bits);
+
+    for (i, texel) in out.iter_mut().enumerate() {
+
+        texel[channel] = palette[(indices >> (i * 3)) as usize & 7] as u8;
+    }
+}
+
+fn decode_bc3(block : &[u8], out : &mut [[u8; 4]; 16]) {
+
+    decode_bc1(&block[8..], out, false);
+
+    decode_bc4_channel(block, out, 3, false);
+}
+
+fn decode_bc4(block : &[u8], out : &mut [[u8; 4]; 16], signed : bool) {
+
+    out.fill(if signed {
+
+        [0, 0, 0, 127]
+    } else {
+
+        [0, 0, 0, 255]
+    });
+
+    decode_bc4_channel(block, out, 0, signed);
+}
+
+fn decode_bc5(block : &[u8], out : &mut [[u8; 4]; 16], signed : bool) {
+
+    decode_bc4(block, out, signed);
+
+    decode_bc4_channel(&block[8..], out, 1, signed);
+}
+
+// (subsets, partition bits, rotation bits, index selection bits, color bits, alpha bits,
+// p bit per endpoint, p bit per subset, index bits, second index bits)
+const BC7_MODES : [[u32; 10]; 8] = [
+    [3, 4, 0, 0, 4, 0, 1, 0, 3, 0],
+    [2, 6, 0, 0, 6, 0, 0, 1, 3, 0],
+    [3, 6, 0, 0, 5, 0, 0, 0, 2, 0],
+    [2, 6, 0, 0, 7, 0, 1, 0, 2, 0],
+    [1, 0, 2, 1, 5, 6, 0, 0, 2, 3],
+    [1, 0, 2, 0, 7, 8, 0, 0, 2, 2],
+    [1, 0, 0, 0, 7, 7, 1, 0, 4, 0],
+    [2, 6, 0, 0, 5, 5, 1, 0, 2, 0],
+];
+
+// NOTE: bc7 partition tables from the spec, the subset of each texel in row order
+const BC7_PARTITIONS_2 : [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1],
+    [0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 1],
+    [0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1],
+    [0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0],
+    [0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0],
+    [0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0],
+    [0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1],
+    [0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0],
+    [0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0],
+    [0, 0, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 1, 1, 0, 0],
+    [0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 0, 1, 0, 0, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0],
+    [0, 1, 1, 1, 0, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 0],
+    [0, 0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0, 0],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1],
+    [0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1],
+    [0, 1, 0, 1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 0, 1, 0],
+    [0, 0, 1, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0],
+    [0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1, 0, 0],
+    [0, 1, 0, 1, 0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 1, 0],
+    [0, 1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 0, 1, 0, 0, 1],
+    [0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 1, 0, 0, 1, 0, 1],
+    [0, 1, 1, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 1, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 0, 0, 0],
+    [0, 0, 1, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 1, 0, 0],
+    [0, 0, 1, 1, 1, 0, 1, 1, 1, 1, 0, 1, 1, 1, 0, 0],
+    [0, 1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 1, 0, 1, 1, 0],
+    [0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 1, 1],
+    [0, 1, 1, 0, 0, 1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 1],
+    [0, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0],
+    [0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0],
+    [0, 1, 1, 0, 1, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 1, 1, 0, 1, 1, 0, 0, 1, 0, 0, 1],
+    [0, 1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0],
+    [0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0, 0, 1, 1, 0],
+    [0, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 0, 0, 1],
+    [0, 1, 1, 0, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0, 0, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0, 1, 0, 0, 0, 0, 0, 0, 1],
+    [0, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1],
+    [0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 0, 0, 0, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0],
+    [0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 1, 1, 1],
+];
+
+const BC7_PARTITIONS_3 : [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+// texel holding the implicit high index bit of subset 1, and of subsets 1 and 2 (one row each)
+const BC7_ANCHORS_2 : [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+const BC7_ANCHORS_3 : [[u8; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6,
+        8, 5, 15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8,
+        5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3,
+        15, 6, 10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15,
+        15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+fn bc7_weights(bits : u32) -> &'static [u32] {
+
+    match bits {
+        2 => &[0, 21, 43, 64],
+        3 => &[0, 9, 18, 27, 37, 46, 55, 64],
+        _ => &[0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64],
+    }
+}
+
+// fields of a bc7 block, least significant bit first
+struct BitReader {
+    value : u128,
+    pos : u32,
+}
+
+impl BitReader {
+    fn read(&mut self, len : u32) -> u32 {
+
+        let value = (self.value >> self.pos) as u32 & ((1 << len) - 1);
+
+        self.pos += len;
+
+        value
+    }
+}
+
+// NOTE: the mode is the number of zero bits before the first set one, a block without
+// any is reserved and decodes to transparent black
+fn decode_bc7(block : &[u8], out : &mut [[u8; 4]; 16]) {
+
+    let mut bits = BitReader {
+        value : u128::from_le_bytes(block[..16].try_into().unwrap()),
+        pos : 0,
+    };
+
+    let Some(mode) = (0..8).find(|_| bits.read(1) == 1) else {
+
+        out.fill([0; 4]);
+
+        return;
+    };
+
+    let [subsets, partition_bits, rotation_bits, selection_bits, color_bits, alpha_bits, endpoint_p, subset_p, index_bits, index2_bits] =
+        BC7_MODES[mode];
+
+    let partition = bits.read(partition_bits) as usize;
+
+    let rotation = bits.read(rotation_bits);
+
+    let selection = bits.read(selection_bits);
+
+    // endpoint pairs per subset, stored channel by channel
+    let mut ends = [[255u32; 4]; 6];
+
+    let ends = &mut ends[..subsets as usize * 2];
+
+    let channels = if alpha_bits > 0 { 4 } else { 3 };
+
+    for channel in 0..channels {
+
+        let len = if channel == 3 { alpha_bits } else { color_bits };
+
+        for end in ends.iter_mut() {
+
+            end[channel] = bits.read(len);
+        }
+    }
+
+    let p_bits = endpoint_p + subset_p;
+
+    if p_bits > 0 {
+
+        let mut p = 0;
+
+        for (i, end) in ends.iter_mut().enumerate() {
+
+            // a shared bit covers both endpoints of the subset
+            if endpoint_p == 1 || i % 2 == 0 {
+
+                p = bits.read(1);
+            }
+
+            for c in &mut end[..channels] {
+
+                *c = *c << 1 | p;
+            }
+        }
+    }
+
+    let expand = |v : u32, len : u32| v << (8 - len) | v >> (2 * len - 8);
+
+    for end in ends.iter_mut() {
+
+        for (channel, c) in end[..channels].iter_mut().enumerate() {
+
+            let len = if channel == 3 { alpha_bits } else { color_bits };
+
+            *c = expand(*c, len + p_bits);
+        }
+    }
+
+    let subset = |i : usize| match subsets {
+        2 => BC7_PARTITIONS_2[partition][i] as usize,
+        3 => BC7_PARTITIONS_3[partition][i] as usize,
+        _ => 0,
+    };
+
+    // anchors store their index one bit short, the high bit is implied 0
+    let anchor = |i : usize| {
+
+        i == 0
+            || match subsets {
+                2 => BC7_ANCHORS_2[partition] as usize == i,
+                3 => BC7_ANCHORS_3
+                    .iter()
+                    .any(|anchors| anchors[partition] as usize == i),
+                _ => false,
+            }
+    };
+
+    let mut read_indices = |len : u32| {
+
+        let mut indices = [0u32; 16];
+
+        for (i, index) in indices.iter_mut().enumerate() {
+
+            *index = bits.read(len - anchor(i) as u32);
+        }
+
+        indices
+    };
+
+    let primary = read_indices(index_bits);
+
+    // modes 4 and 5 have a second set for alpha, in mode 4 the selection bit swaps them
+    let (color, alpha) = if index2_bits == 0 {
+
+        ((primary, index_bits), (primary, index_bits))
+    } else {
+
+        let secondary = (read_indices(index2_bits), index2_bits);
+
+        if selection == 1 {
+
+            (secondary, (primary, index_bits))
+        } else {
+
+            ((primary, index_bits), secondary)
+        }
+    };
+
+    for (i, texel) in out.iter_mut().enumerate() {
+
+        let (e0, e1) = (ends[subset(i) * 2], ends[subset(i) * 2 + 1]);
+
+        let wc = bc7_weights(color.1)[color.0[i] as usize];
+
+        let wa = bc7_weights(alpha.1)[alpha.0[i] as usize];
+
+        let mix = |c : usize, w : u32| (((64 - w) * e0[c] + w * e1[c] + 32) >> 6) as u8;
+
+        *texel = [mix(0, wc), mix(1, wc), mix(2, wc), mix(3, wa)];
+
+        if rotation > 0 {
+
+            texel.swap(rotation as usize - 1, 3);
+        }
+    }
+}
+
+const ETC_MODIFIERS : [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const ETC_DISTANCES : [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS : [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+fn clamp_u8(v : i32) -> u8 { v.clamp(0, 255) as u8 }
+
+fn offset_rgb(c : [i32; 3], d : i32) -> [u8; 4] {
+
+    [
+        clamp_u8(c[0] + d),
+        clamp_u8(c[1] + d),
+        clamp_u8(c[2] + d),
+        255,
+    ]
+}
+
+// NOTE: etc2 rgb block, big endian, texel indices are column major (x * 4 + y)
+// with punchthrough the "diff" bit means opaque and index 2 is transparent
+fn decode_etc2(block : &[u8], out : &mut [[u8; 4]; 16], punchthrough : bool) {
+
+    let bits = u64::from_be_bytes([
+        block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7],
+    ]);
+
+    let field = |shift : u32, len : u32| ((bits >> shift) & ((1 << len) - 1)) as i32;
+
+    let ext4 = |v : i32| v << 4 | v;
+
+    let ext5 = |v : i32| v << 3 | v >> 2;
+
+    let ext6 = |v : i32| v << 2 | v >> 4;
+
+    let ext7 = |v : i32| v << 1 | v >> 6;
+
+    let index = |x : usize, y : usize| {
+
+        let i = x * 4 + y;
+
+        (field(16 + i as u32, 1) << 1 | field(i as u32, 1)) as usize
+    };
+
+    let diff = field(33, 1) == 1;
+
+    let opaque = !punchthrough || diff;
+
+    let transparent = |i : usize| !opaque && i == 2;
+
+    let (r, g, b) = (field(59, 5), field(51, 5), field(43, 5));
+
+    // 3 bit two's complement deltas
+    let delta = |shift : u32| (field(shift, 3) << 29) >> 29;
+
+    let (r2, g2, b2) = (r + delta(56), g + delta(48), b + delta(40));
+
+    if (diff || punchthrough) && !(0..32).contains(&r2) {
+
+        // T mode
+        let c1 = [
+            ext4(field(59, 2) << 2 | field(56, 2)),
+            ext4(field(52, 4)),
+            ext4(field(48, 4)),
+        ];
+
+        let c2 = [ext4(field(44, 4)), ext4(field(40, 4)), ext4(field(36, 4))];
+
+        let d = ETC_DISTANCES[(field(34, 2) << 1 | field(32, 1)) as usize];
+
+        let paint = [
+            offset_rgb(c1, 0),
+            offset_rgb(c2, d),
+            offset_rgb(c2, 0),
+            offset_rgb(c2, -d),
+        ];
+
+        for (i, texel) in out.iter_mut().enumerate() {
+
+            let p = index(i % 4, i / 4);
+
+            *texel = if transparent(p) { [0; 4] } else { paint[p] };
+        }
+    } else if (diff || punchthrough) && !(0..32).contains(&g2) {
+
+        // H mode
+        let c1 = [
+            field(59, 4),
+            field(56, 3) << 1 | field(52, 1),
+            field(51, 1) << 3 | field(47, 3),
+        ];
+
+        let c2 = [field(43, 4), field(39, 4), field(35, 4)];
+
+        let key = |c : [i32; 3]| c[0] << 8 | c[1] << 4 | c[2];
+
+        let order = (key(c1) >= key(c2)) as i32;
+
+        let d = ETC_DISTANCES[(field(34, 1) << 2 | field(32, 1) << 1 | order) as usize];
+
+        let (c1, c2) = (c1.map(ext4), c2.map(ext4));
+
+        let paint = [
+            offset_rgb(c1, d),
+            offset_rgb(c1, -d),
+            offset_rgb(c2, d),
+            offset_rgb(c2, -d),
+        ];
+
+        for (i, texel) in out.iter_mut().enumerate() {
+
+            let p = index(i % 4, i / 4);
+
+            *texel = if transparent(p) { [0; 4] } else { paint[p] };
+        }
+    } else if (diff || punchthrough) && !(0..32).contains(&b2) {
+
+        // planar mode, a gradient from the origin to the h and v corners
+        let o = [
+            ext6(field(57, 6)),
+            ext7(field(56, 1) << 6 | field(49, 6)),
+            ext6(field(48, 1) << 5 | field(43, 2) << 3 | field(39, 3)),
+        ];
+
+        let h = [
+            ext6(field(34, 5) << 1 | field(32, 1)),
+            ext7(field(25, 7)),
+            ext6(field(19, 6)),
+        ];
+
+        let v = [ext6(field(13, 6)), ext7(field(6, 7)), ext6(field(0, 6))];
+
+        for (i, texel) in out.iter_mut().enumerate() {
+
+            let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+
+            let c =
+                |k : usize| clamp_u8((x * (h[k] - o[k]) + y * (v[k] - o[k]) + 4 * o[k] + 2) >> 2);
+
+            *texel = [c(0), c(1), c(2), 255];
+        }
+    } else {
+
+        // two 2x4 / 4x2 sub blocks with a base color and a modifier table each
+        let (base1, base2) = if diff || punchthrough {
+
+            ([r, g, b].map(ext5), [r2, g2, b2].map(ext5))
+        } else {
+
+            (
+                [field(60, 4), field(52, 4), field(44, 4)].map(ext4),
+                [field(56, 4), field(48, 4), field(40, 4)].map(ext4),
+            )
+        };
+
+        let tables = [
+            ETC_MODIFIERS[field(37, 3) as usize],
+            ETC_MODIFIERS[field(34, 3) as usize],
+        ];
+
+        let flip = field(32, 1) == 1;
+
+        for (i, texel) in out.iter_mut().enumerate() {
+
+            let (x, y) = (i % 4, i / 4);
+
+            let second = if flip { y >= 2 } else { x >= 2 };
+
+            let (base, table) = if second {
+
+                (base2, tables[1])
+            } else {
+
+                (base1, tables[0])
+            };
+
+            let p = index(x, y);
+
+            let modifier = match p {
+                // punchthrough blocks that are not opaque have no small modifier
+                0 if !opaque => 0,
+                0 => table[0],
+                1 => table[1],
+                2 => -table[0],
+                _ => -table[1],
+            };
+
+            *texel = if transparent(p) {
+
+                [0; 4]
+            } else {
+
+                offset_rgb(base, modifier)
+            };
+        }
+    }
+}
+
+// NOTE: eac alpha block in front of an etc2 color block
+fn decode_etc2_eac(block : &[u8], out : &mut [[u8; 4]; 16]) {
+
+    decode_etc2(&block[8..], out, false);
+
+    let base = block[0] as i32;
+
+    let multiplier = (block[1] >> 4) as i32;
+
+    let table = EAC_MODIFIERS[(block[1] & 15) as usize];
+
+    let bits = u64::from_be_bytes([
+        0, 0, block[2], block[3], block[4], block[5], block[6], block[7],
+    ]);
+
+    for (i, texel) in out.iter_mut().enumerate() {
+
+        let (x, y) = (i % 4, i / 4);
+
+        let index = (bits >> (45 - (x * 4 + y) * 3)) as usize & 7;
+
+        texel[3] = clamp_u8(base + table[index] * multiplier);
+    }
+}
+
+// NOTE: eac r11 / rg11 channel, the 11 bit result is rounded to the 8 bit texel
+fn decode_eac11_channel(block : &[u8], out : &mut [[u8; 4]; 16], channel : usize, signed : bool) {
+
+    let base = if signed {
+
+        (block[0] as i8 as i32).max(-127) * 8
+    } else {
+
+        block[0] as i32 * 8 + 4
+    };
+
+    let multiplier = match (block[1] >> 4) as i32 {
+        0 => 1,
+        m => m * 8,
+    };
+
+    let table = EAC_MODIFIERS[(block[1] & 15) as usize];
+
+    let bits = u64::from_be_bytes([
+        0, 0, block[2], block[3], block[4], block[5], block[6], block[7],
+    ]);
+
+    for (i, texel) in out.iter_mut().enumerate() {
+
+        let (x, y) = (i % 4, i / 4);
+
+        let v = base + table[(bits >> (45 - (x * 4 + y) * 3)) as usize & 7] * multiplier;
+
+        texel[channel] = if signed {
+
+            let v = v.clamp(-1023, 1023);
+
+            ((v * 127 + v.signum() * 511) / 1023) as i8 as u8
+        } else {
+
+            ((v.clamp(0, 2047) * 255 + 1023) / 2047) as u8
+        };
+    }
+}
+
+fn decode_eac11(block : &[u8], out : &mut [[u8; 4]; 16], signed : bool, channels : usize) {
+
+    out.fill(if signed {
+
+        [0, 0, 0, 127]
+    } else {
+
+        [0, 0, 0, 255]
+    });
+
+    for channel in 0..channels {
+
+        decode_eac11_channel(&block[channel * 8..], out, channel, signed);
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    #[test]
+
+    fn test_dds_cubemap() {
+
+        let data =
+            TextureData::from_dds(include_bytes!("../example/skybox/images/bc1.dds")).unwrap();
+
+        assert_eq!(data.format, wgpu::TextureFormat::Bc1RgbaUnorm);
+
+        assert_eq!((data.width, data.height, data.layers), (128, 128, 6));
+
+        assert!(data.cubemap);
+
+        assert_eq!(data.mip_level_count(), 8);
+
+        // 32 x 32 blocks of 8 bytes, for each face
+        assert_eq!(data.levels[0].len(), 32 * 32 * 8 * 6);
+
+        assert_eq!(data.levels[7].len(), 8 * 6);
+
+        let rgba = data.with_srgb(true).decompress().unwrap();
+
+        assert_eq!(rgba.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+
+        assert_eq!(rgba.levels[7].len(), 4 * 6);
+
+        let bgra =
+            TextureData::from_dds(include_bytes!("../example/skybox/images/bgra.dds")).unwrap();
+
+        assert_eq!(bgra.format, wgpu::TextureFormat::Bgra8Unorm);
+
+        let etc2 =
+            TextureData::from_dds(include_bytes!("../example/skybox/images/etc2.dds")).unwrap();
+
+        assert_eq!(etc2.format, wgpu::TextureFormat::Etc2Rgb8Unorm);
+
+        assert_eq!(
+            etc2.decompress().unwrap().levels[0].len(),
+            128 * 128 * 4 * 6
+        );
+
+        assert!(TextureData::from_dds(b"not a dds").is_err());
+    }
+
+    // mean absolute difference against the uncompressed copy of the same skybox
+    fn skybox_error(data : &TextureData) -> f64 {
+
+        let bgra =
+            TextureData::from_dds(include_bytes!("../example/skybox/images/bgra.dds")).unwrap();
+
+        let rgba = data.decompress().unwrap();
+
+        let (a, b) = (&rgba.levels[0], &bgra.levels[0]);
+
+        assert_eq!(a.len(), b.len());
+
+        let sum = a
+            .chunks_exact(4)
+            .zip(b.chunks_exact(4))
+            .map(|(a, b)| {
+
+                [(0, 2), (1, 1), (2, 0)]
+                    .iter()
+                    .map(|&(i, j)| (a[i] as i32 - b[j] as i32).unsigned_abs() as u64)
+                    .sum::<u64>()
+            })
+            .sum::<u64>();
+
+        sum as f64 / (a.len() / 4 * 3) as f64
+    }
+
+    #[test]
+
+    fn test_dds_astc() {
+
+        let data =
+            TextureData::from_dds(include_bytes!("../example/skybox/images/astc.dds")).unwrap();
+
+        assert_eq!(data.format, astc(0, false).unwrap());
+
+        assert_eq!((data.width, data.height, data.layers), (128, 128, 6));
+
+        assert_eq!(data.mip_level_count(), 8);
+
+        let error = skybox_error(&data);
+
+        let bc1 = skybox_error(
+            &TextureData::from_dds(include_bytes!("../example/skybox/images/bc1.dds")).unwrap(),
+        );
+
+        let etc2 = skybox_error(
+            &TextureData::from_dds(include_bytes!("../example/skybox/images/etc2.dds")).unwrap(),
+        );
+
+        // NOTE: astc 4x4 keeps more than bc1 or etc2, a broken decoder is far off all of them
+        assert!(
+            error < 1.0 && error < bc1 && error < etc2,
+            "astc differs by {}",
+            error
+        );
+    }
+
+    fn set_u32(bytes : &mut [u8], offset : usize, value : u32) {
+
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+
+    fn test_dds_bad_headers() {
+
+        let bc1 = include_bytes!("../example/skybox/images/bc1.dds");
+
+        // more mips than a 128 x 128 chain has are capped
+        let mut bytes = bc1.to_vec();
+
+        set_u32(&mut bytes, 28, u32::MAX);
+
+        assert_eq!(TextureData::from_dds(&bytes).unwrap().mip_level_count(), 8);
+
+        for (offset, value) in [(12, 0), (16, 0), (12, u32::MAX), (16, MAX_DIMENSION + 1)] {
+
+            let mut bytes = bc1.to_vec();
+
+            set_u32(&mut bytes, offset, value);
+
+            assert!(TextureData::from_dds(&bytes).is_err());
+        }
+
+        // dx10 cube array whose layer count overflows once multiplied by 6
+        let mut header = vec![0u8; 148];
+
+        header[..4].copy_from_slice(DDS_MAGIC);
+
+        for (offset, value) in [
+            (12, 4),
+            (16, 4),
+            (28, u32::MAX),
+            (80, DDPF_FOURCC),
+            (128, 71),
+            (136, DDS_RESOURCE_MISC_TEXTURECUBE),
+            (140, u32::MAX),
+        ] {
+
+            set_u32(&mut header, offset, value);
+        }
+
+        header[84..88].copy_from_slice(b"DX10");
+
+        assert!(TextureData::from_dds(&header).is_err());
+
+        // a sane layer count, but no data behind the header
+        set_u32(&mut header, 140, 2);
+
+        assert!(TextureData::from_dds(&header).is_err());
+    }
+
+    fn ktx2_header(values : [u32; 17]) -> Vec<u8> {
+
+        let mut bytes = KTX2_IDENTIFIER.to_vec();
+
+        for value in values {
+
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    #[test]
+
+    fn test_ktx2_bad_headers() {
+
+        // layer count * 6 faces overflows
+        let bytes = ktx2_header([37, 1, 4, 4, 0, 0x8000_0000, 6, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert!(TextureData::from_ktx2(&bytes).is_err());
+
+        // 3 faces
+        let bytes = ktx2_header([37, 1, 4, 4, 0, 1, 3, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert!(TextureData::from_ktx2(&bytes).is_err());
+
+        // zero width
+        let bytes = ktx2_header([37, 1, 0, 4, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert!(TextureData::from_ktx2(&bytes).is_err());
+
+        // a level index pointing past the end of the address space
+        let mut bytes = ktx2_header([37, 1, 1, 1, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        for value in [u64::MAX - 1, 4, 4] {
+
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        assert!(TextureData::from_ktx2(&bytes).is_err());
+    }
+
+    #[test]
+
+    fn test_ktx2() {
+
+        // rgba8, 2 x 1, one layer, one face, two levels, more are capped to the chain
+        let mut bytes = ktx2_header([37, 1, 2, 1, 0, 0, 1, u32::MAX, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        let data_start = 80 + 2 * 24;
+
+        for (offset, length) in [(data_start, 8u64), (data_start + 8, 4)] {
+
+            for value in [offset as u64, length, length] {
+
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        bytes.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+
+        let data = TextureData::from_ktx2(&bytes).unwrap();
+
+        assert_eq!(data.format, wgpu::TextureFormat::Rgba8Unorm);
+
+        assert_eq!(
+            data.levels,
+            vec![vec![1, 2, 3, 4, 5, 6, 7, 8], vec![9, 10, 11, 12]]
+        );
+
+        assert_eq!(vk_format(158), astc(0, true));
+    }
+
+    #[test]
+
+    fn test_decode_blocks() {
+
+        let mut texels = [[0u8; 4]; 16];
+
+        // bc1, pure red and pure blue endpoints, first texel 0, second 1, third 2
+        decode_bc1(
+            &[0x00, 0xf8, 0x1f, 0x00, 0b100100, 0, 0, 0],
+            &mut texels,
+            true,
+        );
+
+        assert_eq!(texels[0], [255, 0, 0, 255]);
+
+        assert_eq!(texels[1], [0, 0, 255, 255]);
+
+        assert_eq!(texels[2], [170, 0, 85, 255]);
+
+        // etc2 differential mode, every texel index 0 of table 0 adds 2
+        decode_etc2(&[0x80, 0x40, 0x20, 0x02, 0, 0, 0, 0], &mut texels, false);
+
+        assert!(texels.iter().all(|t| *t == [134, 68, 35, 255]));
+
+        // eac alpha, base 100 with a zero multiplier
+        decode_etc2_eac(
+            &[100, 0, 0, 0, 0, 0, 0, 0, 0x80, 0x40, 0x20, 0x02, 0, 0, 0, 0],
+            &mut texels,
+        );
+
+        assert!(texels.iter().all(|t| t[3] == 100));
+
+        // bc4 snorm, -128 reads as -127, indices 6 and 7 of the 6 value palette are the limits
+        decode_bc4(&[0x80, 0x7f, 0b111_110, 0, 0, 0, 0, 0], &mut texels, true);
+
+        assert_eq!(texels[0], [-127i8 as u8, 0, 0, 127]);
+
+        assert_eq!(texels[1], [127, 0, 0, 127]);
+
+        // eac r11, base 128 with a zero multiplier, every index adds -3
+        decode_eac11(&[128, 0, 0, 0, 0, 0, 0, 0], &mut texels, false, 1);
+
+        assert!(texels.iter().all(|t| *t == [128, 0, 0, 255]));
+    }
+
+    // (first bit, length, value) fields of a 16 byte block
+    fn block(fields : &[(u32, u32, u128)]) -> Vec<u8> {
+
+        fields
+            .iter()
+            .fold(0u128, |block, &(start, len, value)| block | value << start)
+            .to_le_bytes()
+            .to_vec()
+    }
+
+    #[test]
+
+    fn test_decode_bc7() {
+
+        let mut texels = [[0u8; 4]; 16];
+
+        // mode 6, transparent black to white, texel 0 is the 3 bit anchor
+        decode_bc7(
+            &block(&[
+                (6, 1, 1),
+                (14, 7, 127),
+                (28, 7, 127),
+                (42, 7, 127),
+                (56, 7, 127),
+                (64, 1, 1),
+                (68, 4, 15),
+                (72, 4, 8),
+            ]),
+            &mut texels,
+        );
+
+        assert_eq!(texels[0], [0, 0, 0, 0]);
+
+        assert_eq!(texels[1], [255; 4]);
+
+        assert_eq!(texels[2], [135; 4]);
+
+        // mode 5, red with half alpha and rotation 1, which swaps red and alpha
+        decode_bc7(
+            &block(&[(5, 1, 1), (6, 2, 1), (8, 7, 127), (50, 8, 128)]),
+            &mut texels,
+        );
+
+        assert_eq!(texels[0], [128, 0, 0, 255]);
+
+        // mode 1 partition 14, the top row is subset 0 (black), the rest subset 1 (white)
+        let mut fields = vec![(1, 1, 1), (2, 6, 14)];
+
+        for channel in 0..3 {
+
+            for end in [2, 3] {
+
+                fields.push((8 + channel * 24 + end * 6, 6, 63));
+            }
+        }
+
+        fields.push((81, 1, 1));
+
+        decode_bc7(&block(&fields), &mut texels);
+
+        assert!(texels[..4].iter().all(|t| *t == [0, 0, 0, 255]));
+
+        assert!(texels[4..].iter().all(|t| *t == [255; 4]));
+
+        // the anchor tables agree with the partitions they belong to
+        for p in 0..64 {
+
+            assert_eq!(BC7_PARTITIONS_2[p][BC7_ANCHORS_2[p] as usize], 1);
+
+            assert_eq!(BC7_PARTITIONS_3[p][BC7_ANCHORS_3[0][p] as usize], 1);
+
+            assert_eq!(BC7_PARTITIONS_3[p][BC7_ANCHORS_3[1][p] as usize], 2);
+        }
+
+        // no mode bit set is reserved
+        decode_bc7(&[0; 16], &mut texels);
+
+        assert!(texels.iter().all(|t| *t == [0; 4]));
+    }
+
+    #[test]
+
+    fn test_no_bc6h_decoder() {
+
+        let data = TextureData {
+            format : wgpu::TextureFormat::Bc6hRgbUfloat,
+            width : 4,
+            height : 4,
+            layers : 1,
+            cubemap : false,
+            levels : vec![vec![0; 16]],
+        };
+
+        assert!(data.decompress().is_err());
+    }
+}
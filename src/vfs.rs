@@ -0,0 +1,587 @@
+// NOTE: virtual file system behind resource::load_string / load_binary
+// asset paths are '/' separated and relative, the last mount wins
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+pub enum Mount {
+    // plain directory, e.g. the working directory or next to the executable
+    Dir(PathBuf),
+    // files kept in memory, archives are unpacked into one of these when mounted
+    Memory(HashMap<String, Vec<u8>>),
+}
+
+impl Mount {
+    pub fn dir(path : impl Into<PathBuf>) -> Self { Mount::Dir(path.into()) }
+
+    // `sub` below the current working directory
+    pub fn working_dir(sub : &str) -> std::io::Result<Self> {
+
+        Ok(Mount::Dir(std::env::current_dir()?.join(sub)))
+    }
+
+    // `sub` next to the running executable, packaged installs put res/ there
+    pub fn exe_dir(sub : &str) -> std::io::Result<Self> {
+
+        let exe = std::env::current_exe()?;
+
+        let dir = exe.parent().unwrap_or_else(|| Path::new(""));
+
+        Ok(Mount::Dir(dir.join(sub)))
+    }
+
+    // `sub` in the source tree, only for debug builds and only while the tree is there
+    pub fn source_dir(sub : &str) -> Option<Self> {
+
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(sub);
+
+        (cfg!(debug_assertions) && dir.is_dir()).then_some(Mount::Dir(dir))
+    }
+
+    pub fn memory<P : AsRef<str>>(files : impl IntoIterator<Item = (P, Vec<u8>)>) -> Self {
+
+        Mount::Memory(
+            files
+                .into_iter()
+                .map(|(path, data)| (normalize(path.as_ref()), data))
+                .collect(),
+        )
+    }
+
+    // stored and deflated entries, no zip64 or encryption
+    pub fn zip(bytes : &[u8]) -> anyhow::Result<Self> { Ok(Mount::memory(read_zip(bytes)?)) }
+
+    // ustar / gnu tar, optionally gzipped
+    pub fn tar(bytes : &[u8]) -> anyhow::Result<Self> {
+
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+
+            let mut data = Vec::new();
+
+            flate2::read::GzDecoder::new(bytes).read_to_end(&mut data)?;
+
+            return Ok(Mount::memory(read_tar(&data)?));
+        }
+
+        Ok(Mount::memory(read_tar(bytes)?))
+    }
+
+    // picks the archive format from the extension
+    pub fn archive_file(path : impl AsRef<Path>) -> anyhow::Result<Self> {
+
+        let path = path.as_ref();
+
+        let bytes = std::fs::read(path)?;
+
+        let name = path.to_string_lossy().to_ascii_lowercase();
+
+        if name.ends_with(".zip") {
+
+            Mount::zip(&bytes)
+        } else if name.ends_with(".tar") || name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+
+            Mount::tar(&bytes)
+        } else {
+
+            anyhow::bail!("unknown archive type: {}", path.display())
+        }
+    }
+
+    // None when the file is not in this mount
+    fn read(&self, path : &str) -> Option<std::io::Result<Vec<u8>>> {
+
+        match self {
+            Mount::Dir(dir) => {
+
+                let path = dir.join(path);
+
+                path.is_file().then(|| std::fs::read(path))
+            }
+            Mount::Memory(files) => files.get(path).cloned().map(Ok),
+        }
+    }
+}
+
+#[derive(Default)]
+
+pub struct Vfs {
+    // (prefix, mount), searched back to front
+    mounts : Vec<(String, Mount)>,
+}
+
+impl Vfs {
+    pub fn new() -> Self { Self::default() }
+
+    // res/ in the working directory over res/ in the source tree (debug builds, so
+    // `cargo run --example` finds it from anywhere) over res/ next to the executable
+    pub fn with_default_mounts() -> Self {
+
+        let mut vfs = Self::new();
+
+        for mount in [
+            Mount::exe_dir("res").ok(),
+            Mount::source_dir("res"),
+            Mount::working_dir("res").ok(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+
+            vfs.mount("", mount);
+        }
+
+        vfs
+    }
+
+    // files of `mount` appear under `prefix`, "" is the root
+    pub fn mount(&mut self, prefix : &str, mount : Mount) {
+
+        self.mounts.push((normalize(prefix), mount));
+    }
+
+    pub fn unmount(&mut self, prefix : &str) -> Option<Mount> {
+
+        let prefix = normalize(prefix);
+
+        let index = self.mounts.iter().rposition(|(p, _)| *p == prefix)?;
+
+        Some(self.mounts.remove(index).1)
+    }
+
+    // NOTE: absolute paths skip the mounts and go straight to the disk
+    pub fn read(&self, path : &str) -> anyhow::Result<Vec<u8>> {
+
+        if Path::new(path).is_absolute() {
+
+            return Ok(std::fs::read(path)?);
+        }
+
+        let path = normalize(path);
+
+        for (prefix, mount) in self.mounts.iter().rev() {
+
+            let relative = match strip_mount_prefix(&path, prefix) {
+                Some(relative) => relative,
+                None => continue,
+            };
+
+            if let Some(data) = mount.read(relative) {
+
+                return Ok(data?);
+            }
+        }
+
+        anyhow::bail!("{:?} not found in any mount", path)
+    }
+
+    pub fn read_string(&self, path : &str) -> anyhow::Result<String> {
+
+        Ok(String::from_utf8(self.read(path)?)?)
+    }
+
+    pub fn exists(&self, path : &str) -> bool {
+
+        if Path::new(path).is_absolute() {
+
+            return Path::new(path).is_file();
+        }
+
+        let path = normalize(path);
+
+        self.mounts.iter().any(|(prefix, mount)| {
+
+            strip_mount_prefix(&path, prefix).is_some_and(|relative| match mount {
+                Mount::Dir(dir) => dir.join(relative).is_file(),
+                Mount::Memory(files) => files.contains_key(relative),
+            })
+        })
+    }
+}
+
+fn strip_mount_prefix<'a>(path : &'a str, prefix : &str) -> Option<&'a str> {
+
+    if prefix.is_empty() {
+
+        return Some(path);
+    }
+
+    path.strip_prefix(prefix)?.strip_prefix('/')
+}
+
+// "a/./b\\..//c" -> "a/c", leading ".." are dropped
+pub fn normalize(path : &str) -> String {
+
+    let mut parts = Vec::new();
+
+    for part in path.split(['/', '\\']) {
+
+        match part {
+            "" | "." => {}
+            ".." => {
+
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+
+    parts.join("/")
+}
+
+// `path` as referenced from `file_name`, e.g. an mtl next to its obj
+pub fn resolve_relative(file_name : &str, path : &str) -> String {
+
+    if Path::new(path).is_absolute() {
+
+        return path.to_string();
+    }
+
+    match normalize(file_name).rsplit_once('/') {
+        Some((dir, _)) => normalize(&format!("{}/{}", dir, path)),
+        None => normalize(path),
+    }
+}
+
+fn u16_at(bytes : &[u8], offset : usize) -> anyhow::Result<u16> {
+
+    let b = bytes
+        .get(offset..offset + 2)
+        .ok_or_else(|| anyhow::anyhow!("zip truncated at {}", offset))?;
+
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(bytes : &[u8], offset : usize) -> anyhow::Result<u32> {
+
+    let b = bytes
+        .get(offset..offset + 4)
+        .ok_or_else(|| anyhow::anyhow!("zip truncated at {}", offset))?;
+
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// [doc] https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT
+fn read_zip(bytes : &[u8]) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+
+    // end of central directory, followed by a comment of up to 64k
+    let eocd = (0..bytes.len().saturating_sub(21))
+        .rev()
+        .take(22 + 0xffff)
+        .find(|&i| bytes[i..].starts_with(&[0x50, 0x4b, 0x05, 0x06]))
+        .ok_or_else(|| anyhow::anyhow!("not a zip archive"))?;
+
+    let count = u16_at(bytes, eocd + 10)? as usize;
+
+    let mut offset = u32_at(bytes, eocd + 16)? as usize;
+
+    let mut files = Vec::with_capacity(count);
+
+    for _ in 0..count {
+
+        if u32_at(bytes, offset)? != 0x0201_4b50 {
+
+            anyhow::bail!("bad zip central directory entry at {}", offset);
+        }
+
+        let method = u16_at(bytes, offset + 10)?;
+
+        let compressed_size = u32_at(bytes, offset + 20)? as usize;
+
+        let size = u32_at(bytes, offset + 24)? as usize;
+
+        let name_len = u16_at(bytes, offset + 28)? as usize;
+
+        let entry_len = 46
+            + name_len
+            + u16_at(bytes, offset + 30)? as usize
+            + u16_at(bytes, offset + 32)? as usize;
+
+        let local = u32_at(bytes, offset + 42)? as usize;
+
+        let name = String::from_utf8_lossy(
+            bytes
+                .get(offset + 46..offset + 46 + name_len)
+                .ok_or_else(|| anyhow::anyhow!("zip truncated"))?,
+        )
+        .into_owned();
+
+        offset += entry_len;
+
+        if name.ends_with('/') {
+
+            continue;
+        }
+
+        if compressed_size == 0xffff_ffff || size == 0xffff_ffff || local == 0xffff_ffff {
+
+            anyhow::bail!("{}: zip64 is not supported", name);
+        }
+
+        // the local header repeats name and extra field, with its own lengths
+        let start =
+            local + 30 + u16_at(bytes, local + 26)? as usize + u16_at(bytes, local + 28)? as usize;
+
+        let raw = bytes
+            .get(start..start + compressed_size)
+            .ok_or_else(|| anyhow::anyhow!("{}: zip entry out of bounds", name))?;
+
+        let data = match method {
+            0 => raw.to_vec(),
+            8 => {
+
+                let mut data = Vec::with_capacity(size);
+
+                flate2::read::DeflateDecoder::new(raw).read_to_end(&mut data)?;
+
+                data
+            }
+            _ => anyhow::bail!("{}: unsupported zip compression method {}", name, method),
+        };
+
+        files.push((name, data));
+    }
+
+    Ok(files)
+}
+
+fn tar_octal(field : &[u8]) -> anyhow::Result<usize> {
+
+    let text = std::str::from_utf8(field)?.trim_matches(|c : char| c == '\0' || c == ' ');
+
+    if text.is_empty() {
+
+        return Ok(0);
+    }
+
+    Ok(usize::from_str_radix(text, 8)?)
+}
+
+fn tar_str(field : &[u8]) -> String {
+
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+// [doc] https://www.gnu.org/software/tar/manual/html_node/Standard.html
+fn read_tar(bytes : &[u8]) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+
+    let mut files = Vec::new();
+
+    let mut offset = 0;
+
+    // gnu 'L' entries carry the name of the next entry
+    let mut long_name = None;
+
+    while let Some(header) = bytes.get(offset..offset + 512) {
+
+        if header.iter().all(|&b| b == 0) {
+
+            break;
+        }
+
+        let size = tar_octal(&header[124..136])?;
+
+        let data = bytes
+            .get(offset + 512..offset + 512 + size)
+            .ok_or_else(|| anyhow::anyhow!("tar entry out of bounds at {}", offset))?;
+
+        offset += 512 + size.div_ceil(512) * 512;
+
+        let name = match long_name.take() {
+            Some(name) => name,
+            None if &header[257..262] == b"ustar" && header[345] != 0 => {
+
+                format!("{}/{}", tar_str(&header[345..500]), tar_str(&header[..100]))
+            }
+            None => tar_str(&header[..100]),
+        };
+
+        match header[156] {
+            b'0' | 0 => files.push((name, data.to_vec())),
+            b'L' => long_name = Some(tar_str(data)),
+            // directories, links, pax headers
+            _ => {}
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    fn tar_entry(name : &str, data : &[u8]) -> Vec<u8> {
+
+        let mut header = vec![0u8; 512];
+
+        header[..name.len()].copy_from_slice(name.as_bytes());
+
+        header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+
+        header[156] = b'0';
+
+        header[257..262].copy_from_slice(b"ustar");
+
+        let mut entry = header;
+
+        entry.extend_from_slice(data);
+
+        entry.resize(512 + data.len().div_ceil(512) * 512, 0);
+
+        entry
+    }
+
+    // single stored entry, crc is not checked
+    fn stored_zip(name : &str, data : &[u8]) -> Vec<u8> {
+
+        let le16 = |v : usize| (v as u16).to_le_bytes();
+
+        let le32 = |v : usize| (v as u32).to_le_bytes();
+
+        let mut zip = Vec::new();
+
+        zip.extend_from_slice(&[0x50, 0x4b, 0x03, 0x04]);
+
+        zip.extend_from_slice(&[0; 14]);
+
+        zip.extend_from_slice(&le32(data.len()));
+
+        zip.extend_from_slice(&le32(data.len()));
+
+        zip.extend_from_slice(&le16(name.len()));
+
+        zip.extend_from_slice(&le16(0));
+
+        zip.extend_from_slice(name.as_bytes());
+
+        zip.extend_from_slice(data);
+
+        let directory = zip.len();
+
+        zip.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02]);
+
+        zip.extend_from_slice(&[0; 16]);
+
+        zip.extend_from_slice(&le32(data.len()));
+
+        zip.extend_from_slice(&le32(data.len()));
+
+        zip.extend_from_slice(&le16(name.len()));
+
+        zip.extend_from_slice(&[0; 12]);
+
+        zip.extend_from_slice(&le32(0));
+
+        zip.extend_from_slice(name.as_bytes());
+
+        let directory_len = zip.len() - directory;
+
+        zip.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+
+        zip.extend_from_slice(&le16(1));
+
+        zip.extend_from_slice(&le16(1));
+
+        zip.extend_from_slice(&le32(directory_len));
+
+        zip.extend_from_slice(&le32(directory));
+
+        zip.extend_from_slice(&le16(0));
+
+        zip
+    }
+
+    #[test]
+
+    fn test_normalize() {
+
+        assert_eq!(normalize("./models\\cube/../cube.obj"), "models/cube.obj");
+
+        assert_eq!(
+            resolve_relative("models/cube.obj", "cube.mtl"),
+            "models/cube.mtl"
+        );
+
+        assert_eq!(
+            resolve_relative("models/cube.obj", "../textures/a.png"),
+            "textures/a.png"
+        );
+
+        assert_eq!(resolve_relative("cube.obj", "cube.mtl"), "cube.mtl");
+    }
+
+    #[test]
+
+    fn test_mounts() {
+
+        let mut vfs = Vfs::new();
+
+        vfs.mount("", Mount::memory([("a.txt", b"root".to_vec())]));
+
+        vfs.mount("pack", Mount::memory([("a.txt", b"pack".to_vec())]));
+
+        assert_eq!(vfs.read_string("a.txt").unwrap(), "root");
+
+        assert_eq!(vfs.read_string("./pack/a.txt").unwrap(), "pack");
+
+        // later mounts shadow earlier ones
+        vfs.mount("", Mount::memory([("a.txt", b"override".to_vec())]));
+
+        assert_eq!(vfs.read_string("a.txt").unwrap(), "override");
+
+        assert!(vfs.unmount("").is_some());
+
+        assert_eq!(vfs.read_string("a.txt").unwrap(), "root");
+
+        assert!(!vfs.exists("missing.txt"));
+
+        assert!(vfs.read("missing.txt").is_err());
+    }
+
+    #[test]
+
+    fn test_default_mounts() {
+
+        // tests are debug builds run from the source tree
+        let source = Mount::source_dir("res").unwrap();
+
+        assert!(source.read("cube.mtl").is_some());
+
+        assert!(Mount::source_dir("no such dir").is_none());
+
+        assert!(Vfs::with_default_mounts().exists("cube.mtl"));
+    }
+
+    #[test]
+
+    fn test_archives() {
+
+        let mut tar = tar_entry("models/cube.obj", b"o cube");
+
+        tar.extend(tar_entry("cube.mtl", &[b'x'; 600]));
+
+        tar.extend([0; 1024]);
+
+        let mut vfs = Vfs::new();
+
+        vfs.mount("", Mount::tar(&tar).unwrap());
+
+        vfs.mount(
+            "zip",
+            Mount::zip(&stored_zip("res/cube.mtl", b"newmtl a")).unwrap(),
+        );
+
+        assert_eq!(vfs.read_string("models/cube.obj").unwrap(), "o cube");
+
+        assert_eq!(vfs.read("cube.mtl").unwrap().len(), 600);
+
+        assert_eq!(vfs.read_string("zip/res/cube.mtl").unwrap(), "newmtl a");
+
+        assert!(Mount::zip(b"not a zip").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+// NOTE: small fixed size thread pool for cpu heavy work, e.g. asset decoding
+// jobs are picked up in submission order by whichever worker is free
+
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+pub struct WorkerPool {
+    sender : Option<mpsc::Sender<Job>>,
+    workers : Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(name : &str, threads : usize) -> Self {
+
+        let (sender, receiver) = mpsc::channel::<Job>();
+
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..threads.max(1))
+            .map(|i| {
+
+                let receiver = receiver.clone();
+
+                std::thread::Builder::new()
+                    .name(format!("{} {}", name, i))
+                    .spawn(move || loop {
+
+                        // the lock is only held while waiting, not while running the job
+                        let job = receiver.lock().unwrap().recv();
+
+                        match job {
+                            Ok(job) => job(),
+                            // the pool was dropped
+                            Err(_) => break,
+                        }
+                    })
+                    .expect("failed to spawn worker thread")
+            })
+            .collect();
+
+        Self {
+            sender : Some(sender),
+            workers,
+        }
+    }
+
+    // one worker per core, at most 4, the render thread needs some too
+    pub fn with_default_threads(name : &str) -> Self {
+
+        let threads = std::thread::available_parallelism().map_or(2, |n| n.get().min(4));
+
+        Self::new(name, threads)
+    }
+
+    pub fn spawn(&self, job : impl FnOnce() + Send + 'static) {
+
+        if let Some(sender) = &self.sender {
+
+            sender.send(Box::new(job)).expect("worker threads are gone");
+        }
+    }
+
+    pub fn threads(&self) -> usize { self.workers.len() }
+}
+
+impl Drop for WorkerPool {
+    // NOTE: queued jobs still run, then the workers exit
+    fn drop(&mut self) {
+
+        self.sender.take();
+
+        for worker in self.workers.drain(..) {
+
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+
+mod test {
+
+    use super::*;
+
+    #[test]
+
+    fn test_worker_pool() {
+
+        let pool = WorkerPool::new("test", 3);
+
+        assert_eq!(pool.threads(), 3);
+
+        let (sender, receiver) = mpsc::channel();
+
+        for i in 0..16 {
+
+            let sender = sender.clone();
+
+            pool.spawn(move || sender.send(i * i).unwrap());
+        }
+
+        drop(pool);
+
+        let mut results = receiver.try_iter().collect::<Vec<_>>();
+
+        results.sort();
+
+        assert_eq!(results, (0..16).map(|i| i * i).collect::<Vec<_>>());
+    }
+}